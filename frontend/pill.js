@@ -99,23 +99,33 @@ async function subscribeEvents() {
     const payload = event.payload;
     setState(payload.state);
 
-    if (payload.state === "Ready") {
+    if (payload.state === "ready") {
+      const translated = payload.translated
+        ? ` (translated ${payload.sourceLanguage || "?"}→en)`
+        : "";
+      const fillers = payload.fillersRemoved ? ` (-${payload.fillersRemoved} fillers)` : "";
+      const via = payload.injectionBackend ? ` via ${payload.injectionBackend}` : "";
       latencyHint.textContent = payload.finalText
-        ? "typed to focused app / Alt+X: start"
+        ? `typed to focused app${via}${translated}${fillers} / Alt+X: start`
         : "no speech / Alt+X: retry";
       return;
     }
 
-    if (payload.state === "Recording") {
+    if (payload.state === "recording") {
       latencyHint.textContent = "recording... / Alt+X: stop";
       return;
     }
 
-    if (payload.state === "Processing") {
+    if (payload.state === "processing") {
       latencyHint.textContent = "processing...";
       return;
     }
 
+    if (payload.state === "postProcessing") {
+      latencyHint.textContent = "LLM post-processing...";
+      return;
+    }
+
     latencyHint.textContent = "Alt+X: start/stop";
   });
 
@@ -125,6 +135,21 @@ async function subscribeEvents() {
     setErrorState(payload.userMessage);
   });
 
+  const filteredUnlisten = await listen("notype://filtered", (event) => {
+    const payload = event.payload;
+    latencyHint.textContent = `filtered: ${payload.reasons[0] || "no speech"} / Alt+X: retry`;
+  });
+
+  const macroUnlisten = await listen("notype://macro", (event) => {
+    const payload = event.payload;
+    const prefix = payload.dryRun ? "macro (dry run)" : "macro";
+    latencyHint.textContent = `${prefix}: ${payload.steps.join(", ")}`;
+  });
+
+  const cancelledUnlisten = await listen("notype://cancelled", (event) => {
+    latencyHint.textContent = `cancelled: ${event.payload.reason} / Alt+X: start`;
+  });
+
   const modelUnlisten = await listen("notype://model-download", (event) => {
     const payload = event.payload;
     latencyHint.textContent = `${payload.status} ${payload.progress}%`;
@@ -135,7 +160,15 @@ async function subscribeEvents() {
     latencyHint.textContent = `missing: ${payload.missing.join(", ")}`;
   });
 
-  [transcriptUnlisten, errorUnlisten, modelUnlisten, dependencyUnlisten]
+  [
+    transcriptUnlisten,
+    errorUnlisten,
+    filteredUnlisten,
+    macroUnlisten,
+    cancelledUnlisten,
+    modelUnlisten,
+    dependencyUnlisten
+  ]
     .filter((fn) => typeof fn === "function")
     .forEach((fn) => unlistenFns.push(fn));
 }
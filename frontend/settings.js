@@ -8,20 +8,58 @@ const panels = Array.from(document.querySelectorAll(".panel"));
 const form = {
   maxRecord: document.getElementById("maxRecord"),
   model: document.getElementById("model"),
+  language: document.getElementById("language"),
+  translate: document.getElementById("translate"),
   autoType: document.getElementById("autoType"),
+  injectionBackends: document.getElementById("injectionBackends"),
+  pasteMode: document.getElementById("pasteMode"),
+  pasteChord: document.getElementById("pasteChord"),
+  pasteMinChars: document.getElementById("pasteMinChars"),
   textCleanup: document.getElementById("textCleanup"),
+  autoPunctuation: document.getElementById("autoPunctuation"),
+  sentenceNewline: document.getElementById("sentenceNewline"),
+  fillerRemovalEnabled: document.getElementById("fillerRemovalEnabled"),
+  voiceCommandsEnabled: document.getElementById("voiceCommandsEnabled"),
+  macroDryRun: document.getElementById("macroDryRun"),
+  codeMode: document.getElementById("codeMode"),
+  normalizeNumbers: document.getElementById("normalizeNumbers"),
+  normalizeDates: document.getElementById("normalizeDates"),
+  normalizeTimes: document.getElementById("normalizeTimes"),
+  normalizePercentages: document.getElementById("normalizePercentages"),
+  normalizeUnits: document.getElementById("normalizeUnits"),
+  alnumWidth: document.getElementById("alnumWidth"),
+  punctuationWidth: document.getElementById("punctuationWidth"),
+  japanesePunctuation: document.getElementById("japanesePunctuation"),
+  cjkLatinSpacing: document.getElementById("cjkLatinSpacing"),
   realtimeEnabled: document.getElementById("realtimeEnabled"),
   partialMode: document.getElementById("partialMode"),
   llmEnabled: document.getElementById("llmEnabled"),
   llmProvider: document.getElementById("llmProvider"),
   llmModel: document.getElementById("llmModel"),
+  llmTemplate: document.getElementById("llmTemplate"),
   llmApiBaseUrl: document.getElementById("llmApiBaseUrl"),
-  llmApiKey: document.getElementById("llmApiKey")
+  llmApiKey: document.getElementById("llmApiKey"),
+  llmTimeoutMs: document.getElementById("llmTimeoutMs"),
+  llmStreaming: document.getElementById("llmStreaming"),
+  redactionEnabled: document.getElementById("redactionEnabled"),
+  redactionTerms: document.getElementById("redactionTerms"),
+  customVocabulary: document.getElementById("customVocabulary"),
+  initialPrompt: document.getElementById("initialPrompt"),
+  usePreviousContext: document.getElementById("usePreviousContext"),
+  whisperThreads: document.getElementById("whisperThreads"),
+  whisperBeamSize: document.getElementById("whisperBeamSize"),
+  whisperBestOf: document.getElementById("whisperBestOf"),
+  whisperAudioCtx: document.getElementById("whisperAudioCtx"),
+  whisperNoFallback: document.getElementById("whisperNoFallback"),
+  keepLastRecording: document.getElementById("keepLastRecording")
 };
 
 const saveSettingsBtn = document.getElementById("saveSettings");
 const reloadSettingsBtn = document.getElementById("reloadSettings");
 const checkDepsBtn = document.getElementById("checkDeps");
+const clearLlmApiKeyBtn = document.getElementById("clearLlmApiKey");
+const importVocabularyBtn = document.getElementById("importVocabulary");
+const vocabularyImportPath = document.getElementById("vocabularyImportPath");
 const depsList = document.getElementById("depsList");
 
 let currentConfig = null;
@@ -41,15 +79,51 @@ function switchPanel(id) {
 function applyConfig(cfg) {
   form.maxRecord.value = cfg.maxRecordSeconds;
   form.model.value = cfg.model;
+  form.language.value = cfg.language || "ja";
+  form.translate.checked = cfg.translate;
   form.autoType.checked = cfg.autoType;
+  form.injectionBackends.value = (cfg.injectionBackends || []).join(", ");
+  form.pasteMode.value = cfg.pasteMode || "never";
+  form.pasteChord.value = cfg.pasteChord || "ctrl+v";
+  form.pasteMinChars.value = cfg.pasteMinChars;
   form.textCleanup.checked = cfg.textCleanup;
+  form.autoPunctuation.checked = cfg.autoPunctuation;
+  form.sentenceNewline.checked = cfg.sentenceNewline;
+  form.fillerRemovalEnabled.checked = cfg.fillerRemovalEnabled;
+  form.voiceCommandsEnabled.checked = cfg.voiceCommandsEnabled;
+  form.macroDryRun.checked = cfg.macroDryRun;
+  form.codeMode.checked = cfg.codeMode;
+  form.normalizeNumbers.checked = cfg.normalizeNumbers;
+  form.normalizeDates.checked = cfg.normalizeDates;
+  form.normalizeTimes.checked = cfg.normalizeTimes;
+  form.normalizePercentages.checked = cfg.normalizePercentages;
+  form.normalizeUnits.checked = cfg.normalizeUnits;
+  form.alnumWidth.value = cfg.alnumWidth || "keep";
+  form.punctuationWidth.value = cfg.punctuationWidth || "keep";
+  form.japanesePunctuation.value = cfg.japanesePunctuation || "keep";
+  form.cjkLatinSpacing.value = cfg.cjkLatinSpacing || "keep";
   form.realtimeEnabled.checked = cfg.realtimeEnabled;
   form.partialMode.value = cfg.partialAutotypeMode;
   form.llmEnabled.checked = cfg.llmPostprocessEnabled;
   form.llmProvider.value = cfg.llmProvider || "";
   form.llmModel.value = cfg.llmModel || "";
+  form.llmTemplate.value = cfg.llmTemplate || "cleanup";
   form.llmApiBaseUrl.value = cfg.llmApiBaseUrl || "";
-  form.llmApiKey.value = cfg.llmApiKey || "";
+  form.llmApiKey.value = "";
+  form.llmApiKey.placeholder = cfg.llmApiKeyRef ? "(stored)" : "sk-...";
+  form.llmTimeoutMs.value = cfg.llmTimeoutMs;
+  form.llmStreaming.checked = cfg.llmStreaming;
+  form.redactionEnabled.checked = cfg.redactionEnabled;
+  form.redactionTerms.value = (cfg.redactionTerms || []).join("\n");
+  form.customVocabulary.value = (cfg.customVocabulary || []).join("\n");
+  form.initialPrompt.value = cfg.initialPrompt || "";
+  form.usePreviousContext.checked = cfg.usePreviousContext;
+  form.whisperThreads.value = cfg.whisperThreads;
+  form.whisperBeamSize.value = cfg.whisperBeamSize;
+  form.whisperBestOf.value = cfg.whisperBestOf;
+  form.whisperAudioCtx.value = cfg.whisperAudioCtx;
+  form.whisperNoFallback.checked = cfg.whisperNoFallback;
+  form.keepLastRecording.checked = cfg.keepLastRecording;
 }
 
 function buildConfig() {
@@ -57,19 +131,80 @@ function buildConfig() {
     ...currentConfig,
     maxRecordSeconds: Number(form.maxRecord.value || 60),
     model: form.model.value,
+    language: form.language.value,
+    translate: form.translate.checked,
     autoType: form.autoType.checked,
+    injectionBackends: form.injectionBackends.value
+      .split(",")
+      .map((name) => name.trim())
+      .filter((name) => ["ime", "wtype", "ydotool", "xdotool", "portal", "clipboard"].includes(name)),
+    pasteMode: form.pasteMode.value,
+    pasteChord: form.pasteChord.value.trim() || "ctrl+v",
+    pasteMinChars: Number(form.pasteMinChars.value || 200),
     textCleanup: form.textCleanup.checked,
+    autoPunctuation: form.autoPunctuation.checked,
+    sentenceNewline: form.sentenceNewline.checked,
+    fillerRemovalEnabled: form.fillerRemovalEnabled.checked,
+    voiceCommandsEnabled: form.voiceCommandsEnabled.checked,
+    macroDryRun: form.macroDryRun.checked,
+    codeMode: form.codeMode.checked,
+    normalizeNumbers: form.normalizeNumbers.checked,
+    normalizeDates: form.normalizeDates.checked,
+    normalizeTimes: form.normalizeTimes.checked,
+    normalizePercentages: form.normalizePercentages.checked,
+    normalizeUnits: form.normalizeUnits.checked,
+    alnumWidth: form.alnumWidth.value,
+    punctuationWidth: form.punctuationWidth.value,
+    japanesePunctuation: form.japanesePunctuation.value,
+    cjkLatinSpacing: form.cjkLatinSpacing.value,
     realtimeEnabled: form.realtimeEnabled.checked,
     partialAutotypeMode: form.partialMode.value,
     llmPostprocessEnabled: form.llmEnabled.checked,
     llmProvider: form.llmProvider.value.trim(),
     llmModel: form.llmModel.value.trim(),
+    llmTemplate: form.llmTemplate.value,
     llmApiBaseUrl: form.llmApiBaseUrl.value.trim(),
-    llmApiKey: form.llmApiKey.value.trim()
+    llmApiKey: form.llmApiKey.value.trim(),
+    llmTimeoutMs: Number(form.llmTimeoutMs.value || 5000),
+    llmStreaming: form.llmStreaming.checked,
+    redactionEnabled: form.redactionEnabled.checked,
+    redactionTerms: form.redactionTerms.value
+      .split("\n")
+      .map((term) => term.trim())
+      .filter((term) => term.length > 0),
+    customVocabulary: form.customVocabulary.value
+      .split("\n")
+      .map((term) => term.trim())
+      .filter((term) => term.length > 0),
+    initialPrompt: form.initialPrompt.value.trim(),
+    usePreviousContext: form.usePreviousContext.checked,
+    whisperThreads: Number(form.whisperThreads.value || 0),
+    whisperBeamSize: Number(form.whisperBeamSize.value || 0),
+    whisperBestOf: Number(form.whisperBestOf.value || 0),
+    whisperAudioCtx: Number(form.whisperAudioCtx.value || 0),
+    whisperNoFallback: form.whisperNoFallback.checked,
+    keepLastRecording: form.keepLastRecording.checked
   };
 }
 
+async function loadTemplates() {
+  const templates = await invoke("list_prompt_templates");
+  form.llmTemplate.replaceChildren(
+    ...templates.map((template) => {
+      const option = document.createElement("option");
+      option.value = template.name;
+      option.textContent = template.description
+        ? `${template.name} — ${template.description}`
+        : template.name;
+      return option;
+    })
+  );
+}
+
 async function loadConfig() {
+  await loadTemplates().catch((e) => {
+    statusText.textContent = String(e);
+  });
   const cfg = await invoke("get_config");
   currentConfig = cfg;
   applyConfig(cfg);
@@ -126,6 +261,26 @@ saveSettingsBtn.addEventListener("click", async () => {
   }
 });
 
+clearLlmApiKeyBtn.addEventListener("click", async () => {
+  try {
+    await invoke("clear_llm_api_key");
+    await loadConfig();
+    statusText.textContent = "API key removed";
+  } catch (e) {
+    statusText.textContent = String(e);
+  }
+});
+
+importVocabularyBtn.addEventListener("click", async () => {
+  try {
+    const added = await invoke("import_vocabulary", { path: vocabularyImportPath.value.trim() });
+    await loadConfig();
+    statusText.textContent = `imported ${added} terms`;
+  } catch (e) {
+    statusText.textContent = String(e);
+  }
+});
+
 checkDepsBtn.addEventListener("click", async () => {
   try {
     await checkDependencies();
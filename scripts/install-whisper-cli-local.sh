@@ -4,6 +4,7 @@ set -euo pipefail
 ROOT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
 BUILD_DIR="${ROOT_DIR}/.tmp/whisper.cpp"
 INSTALL_DIR="${HOME}/.local/bin"
+MODEL_DIR="${NOTYPE_MODEL_DIR:-$HOME/.cache/notype/models}"
 
 if ! command -v git >/dev/null 2>&1; then
   echo "git が必要です。先にインストールしてください。"
@@ -34,6 +35,10 @@ mkdir -p "${INSTALL_DIR}"
 cp "${BIN_PATH}" "${INSTALL_DIR}/whisper-cli"
 chmod +x "${INSTALL_DIR}/whisper-cli"
 
+# Reference clip for `notype benchmark`, from the same checkout.
+mkdir -p "${MODEL_DIR}"
+cp "${BUILD_DIR}/samples/jfk.wav" "${MODEL_DIR}/benchmark-reference.wav"
+
 if [[ ":${PATH}:" != *":${INSTALL_DIR}:"* ]]; then
   echo "PATH に ${INSTALL_DIR} が含まれていません。"
   echo "次を ~/.bashrc に追加してください:"
@@ -41,3 +46,4 @@ if [[ ":${PATH}:" != *":${INSTALL_DIR}:"* ]]; then
 fi
 
 echo "installed: ${INSTALL_DIR}/whisper-cli"
+echo "benchmark clip: ${MODEL_DIR}/benchmark-reference.wav"
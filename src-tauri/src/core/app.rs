@@ -1,16 +1,32 @@
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex, Notify};
 
-use super::audio::{cleanup_temp_file, RecordingSession};
-use super::config::{save_config, AppConfig};
+use super::audio::{
+    cleanup_temp_file, discard_retained_recording, retain_recording, RecordingSession,
+};
+use super::commands::{EditHistory, EditStep, Segment};
+use super::config::{save_config, AppConfig, ModelSize};
 use super::error::AppError;
-use super::inject::{InjectionSession, Injector};
-use super::state::{ErrorEvent, ModelDownloadEvent, RuntimeState, TranscriptEvent};
-use super::stt::SttService;
+use super::filter::HallucinationFilter;
+use super::focus::focused_app;
+use super::inject::{InjectionSession, Injector, PastePolicy};
+use super::llm::{ensure_llm_enabled, rewrite_selection};
+use super::macros::{spawn_command, MacroStep};
+use super::pipeline::{PipelineStage, StageContext, TextPipeline};
+use super::secret::{delete_secret, store_llm_api_key};
+use super::selection::read_primary_selection;
+use super::state::{
+    CancelledEvent, ErrorEvent, FilteredEvent, MacroEvent, ModelDownloadEvent, RuntimeState,
+    TranscriptEvent,
+};
+use super::stt::{SttService, Transcription};
+use super::templates::{find_template, PromptVars};
+use super::vocab::{build_prompt, load_vocabulary_file, merge_vocabulary};
 
 struct RuntimeInner {
     state: RuntimeState,
@@ -19,6 +35,61 @@ struct RuntimeInner {
     partial_task: Option<tokio::task::JoinHandle<()>>,
     watchdog_task: Option<tokio::task::JoinHandle<()>>,
     last_toggle_at: Option<std::time::Instant>,
+    session: SessionOverrides,
+    cancel: Option<Arc<CancelToken>>,
+    /// App focused when the last recording started, for prompt templates.
+    target_app: Option<String>,
+    last_recording: Option<LastRecording>,
+    /// Text selected when an edit session started; the recording is the
+    /// instruction for it.
+    selection: Option<String>,
+    last_edit: Option<SelectionEdit>,
+}
+
+/// Cancellation of one session. Awaits are interrupted through `cancelled`,
+/// and nothing is typed once `is_cancelled` is set.
+#[derive(Debug, Default)]
+struct CancelToken {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the session is cancelled, also when that already
+    /// happened.
+    async fn cancelled(&self) {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Audio of the last session, kept only when `keep_last_recording` is on.
+#[derive(Debug, Clone)]
+struct LastRecording {
+    wav: PathBuf,
+    /// What was typed for it, so a re-transcription can erase it first.
+    typed: String,
+}
+
+/// The last selection replaced by a voice edit, for `undo_edit`.
+#[derive(Debug, Clone)]
+struct SelectionEdit {
+    original: String,
+    replaced: String,
 }
 
 impl Default for RuntimeInner {
@@ -30,6 +101,111 @@ impl Default for RuntimeInner {
             partial_task: None,
             watchdog_task: None,
             last_toggle_at: None,
+            session: SessionOverrides::default(),
+            cancel: None,
+            target_app: None,
+            last_recording: None,
+            selection: None,
+            last_edit: None,
+        }
+    }
+}
+
+/// Per-session options requested over D-Bus or the CLI. They are layered over
+/// the persisted config for one recording and never saved.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOverrides {
+    pub translate: Option<bool>,
+    /// LLM prompt template for this session; needs post-processing on.
+    pub template: Option<String>,
+    /// Code dictation for this session, over the app profile.
+    pub code_mode: Option<bool>,
+    /// Speak an instruction for the selected text instead of dictating.
+    pub edit_selection: bool,
+}
+
+impl SessionOverrides {
+    pub fn translate() -> Self {
+        Self {
+            translate: Some(true),
+            ..Self::default()
+        }
+    }
+
+    pub fn code() -> Self {
+        Self {
+            code_mode: Some(true),
+            ..Self::default()
+        }
+    }
+
+    pub fn edit_selection() -> Self {
+        Self {
+            edit_selection: true,
+            ..Self::default()
+        }
+    }
+
+    /// Fails when the template does not exist so the user hears about a typo
+    /// before dictating rather than after.
+    pub fn with_template(name: &str) -> Result<Self, AppError> {
+        let template = find_template(name.trim())?;
+        Ok(Self {
+            template: Some(template.name),
+            ..Self::default()
+        })
+    }
+
+    fn apply(&self, config: &mut AppConfig) {
+        if let Some(translate) = self.translate {
+            config.translate = translate;
+        }
+        if let Some(template) = &self.template {
+            config.llm_template = template.clone();
+        }
+        if let Some(code_mode) = self.code_mode {
+            config.code_mode = code_mode;
+        }
+        // The instruction must not be typed over the selection.
+        if self.edit_selection {
+            config.realtime_enabled = false;
+        }
+    }
+}
+
+/// Model and language overrides for re-transcribing the last recording;
+/// `None` keeps the configured value.
+#[derive(Debug, Clone, Default)]
+pub struct RetranscribeOverrides {
+    pub model: Option<ModelSize>,
+    pub language: Option<String>,
+}
+
+impl RetranscribeOverrides {
+    /// Empty strings, as sent over D-Bus and the CLI, mean "keep the
+    /// configured value".
+    pub fn parse(model: &str, language: &str) -> Result<Self, AppError> {
+        let model = match model.trim() {
+            "" => None,
+            name => Some(ModelSize::parse(name).ok_or_else(|| {
+                AppError::new(
+                    "指定されたモデルは利用できません",
+                    format!("unknown model: {name}"),
+                )
+            })?),
+        };
+        let language = Some(language.trim())
+            .filter(|l| !l.is_empty())
+            .map(str::to_string);
+        Ok(Self { model, language })
+    }
+
+    fn apply(&self, config: &mut AppConfig) {
+        if let Some(model) = self.model {
+            config.model = model;
+        }
+        if let Some(language) = &self.language {
+            config.language = language.clone();
         }
     }
 }
@@ -48,6 +224,7 @@ impl RecordingUsecase {
         recording.stop().await
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn spawn_partial_task(
         &self,
         app: AppHandle,
@@ -56,9 +233,11 @@ impl RecordingUsecase {
         injection: Arc<Mutex<InjectionUsecase>>,
         wav_path: std::path::PathBuf,
         started_at: std::time::Instant,
+        previous_text: String,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
-            let stt = SttService::new(config.model.clone());
+            let mut stt = SttService::from_config(&config);
+            stt.options.prompt = build_prompt(&config, &previous_text);
             let mut last_partial = String::new();
             let partial_in_flight = Arc::new(AtomicBool::new(false));
             let mut tick_ms: u64 = 250;
@@ -84,6 +263,7 @@ impl RecordingUsecase {
                             final_text: None,
                             state: RuntimeState::Recording,
                             latency_ms: None,
+                            ..TranscriptEvent::default()
                         },
                     );
                     tick_ms = 400;
@@ -98,6 +278,7 @@ impl RecordingUsecase {
                             final_text: None,
                             state: RuntimeState::Recording,
                             latency_ms: None,
+                            ..TranscriptEvent::default()
                         },
                     );
                     tick_ms = 400;
@@ -119,6 +300,7 @@ impl RecordingUsecase {
                             final_text: None,
                             state: RuntimeState::Recording,
                             latency_ms: None,
+                            ..TranscriptEvent::default()
                         },
                     );
                     continue;
@@ -132,6 +314,7 @@ impl RecordingUsecase {
                         final_text: None,
                         state: RuntimeState::Recording,
                         latency_ms: Some(partial_latency),
+                        ..TranscriptEvent::default()
                     },
                 );
 
@@ -189,6 +372,7 @@ impl RecordingUsecase {
                     final_text: None,
                     state: RuntimeState::Idle,
                     latency_ms: None,
+                    ..TranscriptEvent::default()
                 },
             );
             emit_error(
@@ -210,8 +394,10 @@ impl TranscriptionUsecase {
         app: &AppHandle,
         config: &AppConfig,
         wav: &std::path::Path,
-    ) -> Result<(String, u64), AppError> {
-        let stt = SttService::new(config.model.clone());
+        previous_text: &str,
+    ) -> Result<(Transcription, u64), AppError> {
+        let mut stt = SttService::from_config(config);
+        stt.options.prompt = build_prompt(config, previous_text);
         tracing::info!("transcription: ensure model started");
 
         let model_ready = tokio::time::timeout(
@@ -260,20 +446,27 @@ struct InjectionUsecase {
     injector: Injector,
     session: InjectionSession,
     partial_degraded: bool,
+    history: EditHistory,
+    /// What `type_chunk` typed for the result being delivered, erased if
+    /// it is cancelled.
+    streamed: String,
 }
 
 impl InjectionUsecase {
-    fn new() -> Self {
+    fn new(config: &AppConfig) -> Self {
         Self {
-            injector: Injector::new(),
+            injector: Injector::from_config(config),
             session: InjectionSession::new(),
             partial_degraded: false,
+            history: EditHistory::default(),
+            streamed: String::new(),
         }
     }
 
     fn reset_session(&mut self) {
         self.session.reset();
         self.partial_degraded = false;
+        self.streamed.clear();
     }
 
     fn mark_partial_degraded_once(&mut self) -> bool {
@@ -293,6 +486,108 @@ impl InjectionUsecase {
     async fn type_final(&mut self, text: &str) -> Result<(), AppError> {
         self.injector.type_final(&mut self.session, text).await
     }
+
+    /// Erases `previous` (typed for an earlier result), then types the text
+    /// and runs the commands in `segments`. Returns the text this result
+    /// leaves on screen, for a later re-transcription to erase.
+    async fn replace_final(
+        &mut self,
+        previous: &str,
+        segments: &[Segment],
+    ) -> Result<String, AppError> {
+        self.erase_previous(previous).await?;
+
+        let steps = self.history.plan(segments);
+        let mut typed = String::new();
+        for step in &steps {
+            match step {
+                EditStep::Type(text) => {
+                    self.injector.type_final(&mut self.session, text).await?;
+                    typed.push_str(text);
+                }
+                EditStep::Key { key, repeat } => {
+                    self.injector.press(key, *repeat).await?;
+                    if key == "Return" {
+                        typed.push_str(&"\n".repeat(*repeat as usize));
+                    }
+                }
+                EditStep::Erase(text) => {
+                    self.injector.erase(text).await?;
+                    match typed.strip_suffix(text.as_str()) {
+                        Some(rest) => typed.truncate(rest.len()),
+                        // Text from an earlier result went; erasing this
+                        // result again would no longer line up.
+                        None => typed.clear(),
+                    }
+                }
+            }
+        }
+        Ok(typed)
+    }
+
+    /// Leaves nothing of this result on screen: the live preview and
+    /// `previous` (typed for an earlier result) are erased.
+    async fn discard(&mut self, previous: &str) -> Result<(), AppError> {
+        self.clear_partial().await?;
+        self.erase_previous(previous).await
+    }
+
+    async fn erase_previous(&mut self, previous: &str) -> Result<(), AppError> {
+        self.injector.erase(previous).await?;
+        self.history.forget(previous);
+        Ok(())
+    }
+
+    /// Types one piece of a streamed result; `record_streamed` records the
+    /// whole once the stream is over.
+    async fn type_chunk(&mut self, chunk: &str) -> Result<(), AppError> {
+        self.injector.type_streamed(chunk).await?;
+        self.streamed.push_str(chunk);
+        Ok(())
+    }
+
+    /// Lets voice commands delete or undo a result that was typed as it
+    /// streamed in. The stream replaced the live preview.
+    fn record_streamed(&mut self, text: &str) {
+        self.history.plan(&[Segment::Text(text.to_string())]);
+        self.session.set_last_partial("");
+        self.streamed.clear();
+    }
+
+    /// Replaces what was streamed with the final result.
+    async fn replace_streamed(&mut self, segments: &[Segment]) -> Result<String, AppError> {
+        let streamed = std::mem::take(&mut self.streamed);
+        self.replace_final(&streamed, segments).await
+    }
+
+    /// Erases what a cancelled result left on screen: the live preview and
+    /// anything streamed so far.
+    async fn abandon(&mut self) -> Result<(), AppError> {
+        self.clear_partial().await?;
+        let streamed = std::mem::take(&mut self.streamed);
+        self.injector.erase(&streamed).await
+    }
+
+    async fn clear_partial(&mut self) -> Result<(), AppError> {
+        self.injector.clear_partial(&mut self.session).await
+    }
+
+    /// Name of the backend that typed the result, for the Ready event.
+    fn take_backend(&mut self) -> Option<String> {
+        self.injector
+            .take_last_used()
+            .map(|kind| kind.as_str().to_string())
+    }
+
+    async fn run_macro(&mut self, steps: &[MacroStep]) -> Result<(), AppError> {
+        for step in steps {
+            match step {
+                MacroStep::Chord(chord) => self.injector.press_chord(chord).await?,
+                MacroStep::Run { program, args } => spawn_command(program, args)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct AppRuntime {
@@ -305,12 +600,16 @@ pub struct AppRuntime {
 
 impl AppRuntime {
     pub fn new(config: AppConfig) -> Self {
+        // A recording left behind by a previous run can no longer be matched
+        // to what it typed, so it is useless and only a privacy liability.
+        discard_retained_recording();
+        let injection = InjectionUsecase::new(&config);
         Self {
             inner: Arc::new(Mutex::new(RuntimeInner::default())),
             config: Arc::new(Mutex::new(config)),
             recording: RecordingUsecase,
             transcription: TranscriptionUsecase,
-            injection: Arc::new(Mutex::new(InjectionUsecase::new())),
+            injection: Arc::new(Mutex::new(injection)),
         }
     }
 
@@ -324,16 +623,88 @@ impl AppRuntime {
         if merged.pill_position.is_none() {
             merged.pill_position = current.pill_position;
         }
+        // The settings UI never sees the stored key; an empty field keeps it.
+        merged.llm_api_key = merged.llm_api_key.trim().to_string();
+        if merged.llm_api_key.is_empty() {
+            merged.llm_api_key = current.llm_api_key.clone();
+            merged.llm_api_key_ref = current.llm_api_key_ref.clone();
+        } else if merged.llm_api_key != current.llm_api_key || current.llm_api_key_ref.is_empty() {
+            merged.llm_api_key_ref = store_llm_api_key(&merged.llm_api_key).await?;
+        } else {
+            merged.llm_api_key_ref = current.llm_api_key_ref.clone();
+        }
         save_config(&merged).map_err(AppError::from)?;
+        let keep_last_recording = merged.keep_last_recording;
+        if merged.injection_backends != current.injection_backends {
+            self.injection.lock().await.injector = Injector::from_config(&merged);
+        } else {
+            self.injection
+                .lock()
+                .await
+                .injector
+                .set_paste(PastePolicy::from_config(&merged));
+        }
         *self.config.lock().await = merged;
+        if !keep_last_recording {
+            self.inner.lock().await.last_recording = None;
+            discard_retained_recording();
+        }
         Ok(())
     }
 
+    /// Removes the stored LLM API key from the keyring (or fallback file).
+    pub async fn clear_llm_api_key(&self) -> Result<(), AppError> {
+        let mut cfg = self.config.lock().await;
+        if !cfg.llm_api_key_ref.is_empty() {
+            delete_secret(&cfg.llm_api_key_ref).await?;
+        }
+        cfg.llm_api_key.clear();
+        cfg.llm_api_key_ref.clear();
+        save_config(&cfg).map_err(AppError::from)
+    }
+
+    pub async fn get_vocabulary(&self) -> Vec<String> {
+        self.config.lock().await.custom_vocabulary.clone()
+    }
+
+    pub async fn add_vocabulary_term(&self, term: String) -> Result<Vec<String>, AppError> {
+        let mut cfg = self.get_config().await;
+        merge_vocabulary(&mut cfg.custom_vocabulary, vec![term]);
+        let vocabulary = cfg.custom_vocabulary.clone();
+        self.update_config(cfg).await?;
+        Ok(vocabulary)
+    }
+
+    pub async fn remove_vocabulary_term(&self, term: String) -> Result<Vec<String>, AppError> {
+        let mut cfg = self.get_config().await;
+        cfg.custom_vocabulary.retain(|t| t != term.trim());
+        let vocabulary = cfg.custom_vocabulary.clone();
+        self.update_config(cfg).await?;
+        Ok(vocabulary)
+    }
+
+    pub async fn import_vocabulary(&self, path: &std::path::Path) -> Result<usize, AppError> {
+        let terms = load_vocabulary_file(path)?;
+        let mut cfg = self.get_config().await;
+        let added = merge_vocabulary(&mut cfg.custom_vocabulary, terms);
+        self.update_config(cfg).await?;
+        Ok(added)
+    }
+
     pub async fn state(&self) -> RuntimeState {
         self.inner.lock().await.state
     }
 
     pub async fn toggle_recording(&self, app: AppHandle) -> Result<RuntimeState, AppError> {
+        self.toggle_recording_with(app, SessionOverrides::default())
+            .await
+    }
+
+    pub async fn toggle_recording_with(
+        &self,
+        app: AppHandle,
+        overrides: SessionOverrides,
+    ) -> Result<RuntimeState, AppError> {
         let state = {
             let mut inner = self.inner.lock().await;
             let now = std::time::Instant::now();
@@ -348,9 +719,9 @@ impl AppRuntime {
         };
 
         tracing::info!("toggle request received; state={:?}", state);
-        if state == RuntimeState::Processing {
+        if state.is_busy() {
             tracing::info!("toggle ignored while processing");
-            return Ok(RuntimeState::Processing);
+            return Ok(state);
         }
 
         if state == RuntimeState::Recording {
@@ -368,7 +739,7 @@ impl AppRuntime {
         }
 
         tracing::info!("toggle action=start");
-        match self.start_recording(app).await {
+        match self.start_recording_with(app, overrides).await {
             Ok(_) => {
                 tracing::info!("toggle action=start done");
                 Ok(RuntimeState::Recording)
@@ -381,12 +752,21 @@ impl AppRuntime {
     }
 
     pub async fn start_recording(&self, app: AppHandle) -> Result<(), AppError> {
+        self.start_recording_with(app, SessionOverrides::default())
+            .await
+    }
+
+    pub async fn start_recording_with(
+        &self,
+        app: AppHandle,
+        overrides: SessionOverrides,
+    ) -> Result<(), AppError> {
         {
             let inner = self.inner.lock().await;
             if inner.state == RuntimeState::Recording {
                 return Ok(());
             }
-            if inner.state == RuntimeState::Processing {
+            if inner.state.is_busy() {
                 return Err(AppError::new(
                     "まだ前回の処理中です。完了後に Alt+X を押してください",
                     "cannot start while processing",
@@ -394,9 +774,22 @@ impl AppRuntime {
             }
         }
 
+        // Both only make sense with the LLM, which the user has to opt in to.
+        if overrides.edit_selection || overrides.template.is_some() {
+            ensure_llm_enabled(&*self.config.lock().await)?;
+        }
+
+        // Read before anything else can change the selection.
+        let selection = if overrides.edit_selection {
+            Some(read_primary_selection().await?)
+        } else {
+            None
+        };
+
         self.injection.lock().await.reset_session();
 
-        let recording = self.recording.start_session().await?;
+        let (recording, target_app) = tokio::join!(self.recording.start_session(), focused_app());
+        let recording = recording?;
         let start_path = recording.audio_path.clone();
         let started_at = recording.started_at;
 
@@ -407,6 +800,12 @@ impl AppRuntime {
             }
             inner.recording = Some(recording);
             inner.state = RuntimeState::Recording;
+            inner.session = overrides.clone();
+            inner.cancel = Some(Arc::default());
+            inner.target_app = target_app;
+            inner.selection = selection;
+            // Undo only lines up while the edit is the last thing typed.
+            inner.last_edit = None;
         }
 
         emit_transcript(
@@ -416,10 +815,13 @@ impl AppRuntime {
                 final_text: None,
                 state: RuntimeState::Recording,
                 latency_ms: None,
+                ..TranscriptEvent::default()
             },
         );
 
-        let config = self.config.lock().await.clone();
+        let mut config = self.config.lock().await.clone();
+        overrides.apply(&mut config);
+        let previous_text = self.current_text().await;
         let partial_task = self.recording.spawn_partial_task(
             app.clone(),
             self.inner.clone(),
@@ -427,6 +829,7 @@ impl AppRuntime {
             self.injection.clone(),
             start_path,
             started_at,
+            previous_text,
         );
         let watchdog_task = self.recording.spawn_watchdog_task(
             app,
@@ -442,7 +845,7 @@ impl AppRuntime {
     }
 
     pub async fn stop_recording(&self, app: AppHandle) -> Result<String, AppError> {
-        let maybe_recording = {
+        let (maybe_recording, session, cancel, target_app, selection) = {
             let mut inner = self.inner.lock().await;
             if inner.state != RuntimeState::Recording {
                 return Ok(inner.current_text.clone());
@@ -456,7 +859,14 @@ impl AppRuntime {
             }
 
             inner.state = RuntimeState::Processing;
-            inner.recording.take()
+            let cancel = inner.cancel.get_or_insert_with(Default::default).clone();
+            (
+                inner.recording.take(),
+                std::mem::take(&mut inner.session),
+                cancel,
+                inner.target_app.clone(),
+                inner.selection.take(),
+            )
         };
 
         emit_transcript(
@@ -466,12 +876,14 @@ impl AppRuntime {
                 final_text: None,
                 state: RuntimeState::Processing,
                 latency_ms: None,
+                ..TranscriptEvent::default()
             },
         );
 
         let stop_result: Result<String, AppError> = async {
-            let mut recording = maybe_recording
-                .ok_or_else(|| AppError::new("録音セッションが見つかりません", "missing recording"))?;
+            let mut recording = maybe_recording.ok_or_else(|| {
+                AppError::new("録音セッションが見つかりません", "missing recording")
+            })?;
 
             tracing::info!("stop_recording: stopping audio session");
             let wav = tokio::time::timeout(
@@ -487,49 +899,54 @@ impl AppRuntime {
             })??;
 
             tracing::info!("stop_recording: audio session stopped");
-            let config = self.config.lock().await.clone();
+            let mut config = self.config.lock().await.clone();
+            config.apply_app_profile(target_app.as_deref());
+            session.apply(&mut config);
+            let previous_text = self.current_text().await;
             tracing::info!("stop_recording: transcription started");
-            let result = self.transcription.transcribe(&app, &config, &wav).await;
-            cleanup_temp_file(&wav);
-            let (mut text, latency) = result?;
-            tracing::info!("stop_recording: transcription done");
-
-            if config.text_cleanup {
-                text = cleanup_text(text);
-            }
-
-            {
-                let mut inner = self.inner.lock().await;
-                inner.current_text = text.clone();
-                inner.state = RuntimeState::Ready;
-            }
-
-            if config.auto_type {
-                tokio::time::sleep(Duration::from_millis(120)).await;
-                if let Err(err) = self.injection.lock().await.type_final(&text).await {
-                    emit_error(
-                        &app,
-                        AppError::new(
-                            "自動入力に失敗しました。フォーカス先を確認して Alt+X で再試行してください",
-                            err.details,
-                        ),
-                    );
-                }
+            // Dropping the transcription future kills whisper-cli (kill_on_drop).
+            let result = tokio::select! {
+                result = self
+                    .transcription
+                    .transcribe(&app, &config, &wav, &previous_text) => Some(result),
+                _ = cancel.cancelled() => None,
+            };
+            // An edit instruction has nothing to re-transcribe into.
+            if result.is_some() && config.keep_last_recording && selection.is_none() {
+                self.retain_last_recording(&wav).await;
+            } else {
+                cleanup_temp_file(&wav);
             }
+            let Some(result) = result else {
+                tracing::info!("stop_recording: transcription cancelled");
+                self.finish_cancelled(&app, "文字起こしを中断しました", RuntimeState::Processing)
+                    .await;
+                return Ok(String::new());
+            };
+            let (transcription, latency) = result?;
+            tracing::info!("stop_recording: transcription done");
 
-            if let Some(main) = app.get_webview_window("main") {
-                let _ = main.show();
+            if let Some(selection) = selection {
+                return self
+                    .deliver_edit(&app, &config, transcription, latency, selection, &cancel)
+                    .await;
             }
 
-            emit_transcript(
-                &app,
-                TranscriptEvent {
-                    partial_text: String::new(),
-                    final_text: Some(text.clone()),
-                    state: RuntimeState::Ready,
-                    latency_ms: Some(latency),
-                },
-            );
+            let text = self
+                .deliver_final(
+                    &app,
+                    &config,
+                    transcription,
+                    latency,
+                    PromptVars {
+                        app: target_app.unwrap_or_default(),
+                        previous: previous_text,
+                        ..PromptVars::default()
+                    },
+                    "",
+                    &cancel,
+                )
+                .await;
             tracing::info!("recording stopped");
 
             Ok(text)
@@ -547,6 +964,564 @@ impl AppRuntime {
         }
     }
 
+    /// Re-runs whisper on the retained last recording with `overrides` and
+    /// replaces what was typed for it. Requires `keep_last_recording`.
+    pub async fn retranscribe_last(
+        &self,
+        app: AppHandle,
+        overrides: RetranscribeOverrides,
+    ) -> Result<String, AppError> {
+        let (last, cancel, target_app) = {
+            let mut inner = self.inner.lock().await;
+            if inner.state == RuntimeState::Recording || inner.state.is_busy() {
+                return Err(AppError::new(
+                    "録音中または処理中は再文字起こしできません",
+                    format!("retranscribe rejected; state={:?}", inner.state),
+                ));
+            }
+            let Some(last) = inner.last_recording.clone() else {
+                return Err(AppError::new(
+                    "再文字起こしできる録音がありません。設定で「直前の録音を保持」を有効にしてください",
+                    "no retained recording",
+                ));
+            };
+            let cancel = Arc::new(CancelToken::default());
+            inner.state = RuntimeState::Processing;
+            inner.cancel = Some(cancel.clone());
+            (last, cancel, inner.target_app.clone())
+        };
+
+        emit_transcript(
+            &app,
+            TranscriptEvent {
+                state: RuntimeState::Processing,
+                ..TranscriptEvent::default()
+            },
+        );
+
+        let mut config = self.config.lock().await.clone();
+        config.apply_app_profile(target_app.as_deref());
+        overrides.apply(&mut config);
+        tracing::info!(
+            "retranscribe: started model={} language={}",
+            config.model.as_str(),
+            config.language
+        );
+        // The text being replaced must not be fed back as previous context.
+        let result = tokio::select! {
+            result = self.transcription.transcribe(&app, &config, &last.wav, "") => Some(result),
+            _ = cancel.cancelled() => None,
+        };
+        let Some(result) = result else {
+            tracing::info!("retranscribe: cancelled");
+            self.finish_cancelled(&app, "再文字起こしを中断しました", RuntimeState::Processing)
+                .await;
+            return Ok(String::new());
+        };
+
+        match result {
+            Ok((transcription, latency)) => Ok(self
+                .deliver_final(
+                    &app,
+                    &config,
+                    transcription,
+                    latency,
+                    PromptVars {
+                        app: target_app.unwrap_or_default(),
+                        ..PromptVars::default()
+                    },
+                    &last.typed,
+                    &cancel,
+                )
+                .await),
+            Err(err) => {
+                self.reset_idle(&app).await;
+                emit_error(&app, err.clone());
+                tracing::warn!("retranscribe failed: {}", err.details);
+                Err(err)
+            }
+        }
+    }
+
+    async fn retain_last_recording(&self, wav: &Path) {
+        let last_recording = match retain_recording(wav) {
+            Ok(wav) => Some(LastRecording {
+                wav,
+                typed: String::new(),
+            }),
+            Err(err) => {
+                tracing::warn!("failed to retain last recording: {}", err.details);
+                None
+            }
+        };
+        self.inner.lock().await.last_recording = last_recording;
+    }
+
+    /// Filters a final transcription, runs it through the text pipeline and
+    /// types it, replacing `previous_typed` when it is the re-transcription of an
+    /// earlier result. `prompt` carries the template context except the text.
+    /// Nothing is typed once `cancel` fires.
+    #[allow(clippy::too_many_arguments)]
+    async fn deliver_final(
+        &self,
+        app: &AppHandle,
+        config: &AppConfig,
+        transcription: Transcription,
+        latency: u64,
+        prompt: PromptVars,
+        previous_typed: &str,
+        cancel: &CancelToken,
+    ) -> String {
+        let mut during = RuntimeState::Processing;
+        if cancel.is_cancelled() {
+            return self.finish_delivery_cancelled(app, during).await;
+        }
+        let translated = transcription.translated;
+        let source_language = transcription.language.clone();
+        let mut text = transcription.text.clone();
+        if config.hallucination_filter_enabled {
+            let outcome = HallucinationFilter::from_config(config).apply(&transcription);
+            if outcome.is_suppressed() {
+                tracing::info!("deliver_final: transcript suppressed by filter");
+                let reasons = outcome.reasons.iter().map(|r| r.describe()).collect();
+                let original = transcription.text;
+                return self
+                    .finish_filtered(app, config, original, reasons, latency, previous_typed)
+                    .await;
+            }
+            text = outcome.text;
+        }
+
+        let pipeline = TextPipeline::from_config(config);
+        if pipeline.stages().contains(&PipelineStage::Llm) {
+            during = RuntimeState::PostProcessing;
+            self.inner.lock().await.state = RuntimeState::PostProcessing;
+            emit_transcript(
+                app,
+                TranscriptEvent {
+                    state: RuntimeState::PostProcessing,
+                    ..TranscriptEvent::default()
+                },
+            );
+        }
+        let (chunks, streamed) = if config.auto_type && config.llm_streaming {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
+        let ctx = StageContext {
+            config,
+            language: transcription.output_language(),
+            segments: &transcription.segments,
+            prompt,
+            stream: chunks,
+        };
+        // The context owns the sender, so the stream ends with the pipeline.
+        let run = async move { pipeline.run(&ctx, text).await };
+        let typing = type_stream(&self.injection, cancel, previous_typed, streamed);
+        // Dropping the pipeline on cancel also drops the LLM request.
+        let joined = tokio::select! {
+            joined = async { tokio::join!(run, typing) } => Some(joined),
+            _ = cancel.cancelled() => None,
+        };
+        let Some((output, streamed)) = joined else {
+            return self.finish_delivery_cancelled(app, during).await;
+        };
+        for trace in &output.traces {
+            tracing::debug!(
+                "pipeline: {} {:?} -> {:?} ({} ms)",
+                trace.stage.as_str(),
+                trace.before,
+                trace.after,
+                trace.elapsed_ms
+            );
+        }
+        if cancel.is_cancelled() {
+            return self.finish_delivery_cancelled(app, during).await;
+        }
+        if let Some(steps) = output.macro_steps {
+            return self
+                .finish_macro(app, config, output.text, steps, latency)
+                .await;
+        }
+        let fillers_removed = output.fillers_removed;
+        let segments = output.segments;
+        let text = output.text;
+
+        {
+            // Checked under the lock: once the state is Ready, `cancel` no
+            // longer fires.
+            let mut inner = self.inner.lock().await;
+            if cancel.is_cancelled() {
+                drop(inner);
+                return self.finish_delivery_cancelled(app, during).await;
+            }
+            inner.current_text = text.clone();
+            inner.state = RuntimeState::Ready;
+        }
+
+        let mut injection_backend = None;
+        if config.auto_type {
+            self.injection
+                .lock()
+                .await
+                .injector
+                .set_paste(PastePolicy::from_config(config));
+            let typed = match streamed {
+                Ok(None) => {
+                    tokio::time::sleep(Duration::from_millis(120)).await;
+                    self.injection
+                        .lock()
+                        .await
+                        .replace_final(previous_typed, &segments)
+                        .await
+                }
+                Ok(Some(streamed)) if segments == [Segment::Text(streamed.clone())] => {
+                    self.injection.lock().await.record_streamed(&streamed);
+                    Ok(streamed)
+                }
+                // The stream failed midway or later stages changed the text:
+                // the final result replaces what was streamed.
+                Ok(Some(_)) => {
+                    tracing::info!("deliver_final: replacing streamed text");
+                    self.injection
+                        .lock()
+                        .await
+                        .replace_streamed(&segments)
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            match typed {
+                Ok(typed) => {
+                    injection_backend = self.injection.lock().await.take_backend();
+                    if let Some(last) = self.inner.lock().await.last_recording.as_mut() {
+                        last.typed = typed;
+                    }
+                }
+                Err(err) => emit_error(
+                    app,
+                    AppError::new(
+                        "自動入力に失敗しました。フォーカス先を確認して Alt+X で再試行してください",
+                        err.details,
+                    ),
+                ),
+            }
+        }
+
+        if let Some(main) = app.get_webview_window("main") {
+            let _ = main.show();
+        }
+
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                partial_text: String::new(),
+                final_text: Some(text.clone()),
+                state: RuntimeState::Ready,
+                latency_ms: Some(latency),
+                translated,
+                source_language,
+                fillers_removed,
+                injection_backend,
+            },
+        );
+        text
+    }
+
+    /// Rewrites the selection an edit session started with by the spoken
+    /// instruction and types the result over it. On failure the selection
+    /// is left alone.
+    async fn deliver_edit(
+        &self,
+        app: &AppHandle,
+        config: &AppConfig,
+        transcription: Transcription,
+        latency: u64,
+        selection: String,
+        cancel: &CancelToken,
+    ) -> Result<String, AppError> {
+        let instruction = transcription.text.trim();
+        if instruction.is_empty() {
+            return Err(AppError::new(
+                "編集の指示を聞き取れませんでした",
+                "empty edit instruction",
+            ));
+        }
+        tracing::info!("deliver_edit: instruction {instruction:?}");
+
+        self.inner.lock().await.state = RuntimeState::PostProcessing;
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                state: RuntimeState::PostProcessing,
+                ..TranscriptEvent::default()
+            },
+        );
+        let edited = tokio::select! {
+            edited = rewrite_selection(config, instruction, &selection) => edited?,
+            _ = cancel.cancelled() => {
+                return Ok(self
+                    .finish_delivery_cancelled(app, RuntimeState::PostProcessing)
+                    .await);
+            }
+        };
+
+        // Typing replaces the selection in the focused app.
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let injection_backend = {
+            let mut injection = self.injection.lock().await;
+            if cancel.is_cancelled() {
+                drop(injection);
+                return Ok(self
+                    .finish_delivery_cancelled(app, RuntimeState::PostProcessing)
+                    .await);
+            }
+            injection
+                .injector
+                .set_paste(PastePolicy::from_config(config));
+            injection.type_final(&edited).await?;
+            injection.take_backend()
+        };
+        {
+            let mut inner = self.inner.lock().await;
+            inner.current_text = edited.clone();
+            inner.state = RuntimeState::Ready;
+            inner.last_edit = Some(SelectionEdit {
+                original: selection,
+                replaced: edited.clone(),
+            });
+        }
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                final_text: Some(edited.clone()),
+                state: RuntimeState::Ready,
+                latency_ms: Some(latency),
+                injection_backend,
+                ..TranscriptEvent::default()
+            },
+        );
+        Ok(edited)
+    }
+
+    /// Puts back the text the last voice edit replaced. The cursor has to be
+    /// where the edit left it.
+    pub async fn undo_edit(&self) -> Result<(), AppError> {
+        let edit = {
+            let mut inner = self.inner.lock().await;
+            if inner.state == RuntimeState::Recording || inner.state.is_busy() {
+                return Err(AppError::new(
+                    "録音中または処理中は元に戻せません",
+                    format!("undo edit rejected; state={:?}", inner.state),
+                ));
+            }
+            inner.last_edit.take().ok_or_else(|| {
+                AppError::new("元に戻せる編集がありません", "no selection edit to undo")
+            })?
+        };
+        tracing::info!(
+            "undo_edit: restoring {} chars",
+            edit.original.chars().count()
+        );
+        let mut injection = self.injection.lock().await;
+        injection.erase_previous(&edit.replaced).await?;
+        injection.type_final(&edit.original).await
+    }
+
+    /// Discards the current session. A recording is killed and its audio
+    /// deleted right away; an in-flight transcription or post-processing is
+    /// interrupted and the delivering task finishes the cleanup without
+    /// typing anything more.
+    pub async fn cancel(&self, app: &AppHandle) -> Result<RuntimeState, AppError> {
+        let mut killed_recording = {
+            let mut inner = self.inner.lock().await;
+            match inner.state {
+                RuntimeState::Recording => {
+                    if let Some(task) = inner.partial_task.take() {
+                        task.abort();
+                    }
+                    if let Some(task) = inner.watchdog_task.take() {
+                        task.abort();
+                    }
+                    inner.session = SessionOverrides::default();
+                    inner.selection = None;
+                    inner.cancel = None;
+                    inner.state = RuntimeState::Idle;
+                    inner.recording.take()
+                }
+                RuntimeState::Processing | RuntimeState::PostProcessing => {
+                    tracing::info!("cancel: interrupting {:?}", inner.state);
+                    if let Some(cancel) = inner.cancel.take() {
+                        cancel.cancel();
+                    }
+                    return Ok(RuntimeState::Idle);
+                }
+                state => {
+                    tracing::info!("cancel: nothing to cancel; state={state:?}");
+                    return Ok(state);
+                }
+            }
+        };
+
+        tracing::info!("cancel: discarding recording");
+        if let Some(recording) = killed_recording.as_mut() {
+            let wav = recording.kill().await;
+            cleanup_temp_file(&wav);
+        }
+        self.finish_cancelled(app, "録音を破棄しました", RuntimeState::Recording)
+            .await;
+        Ok(RuntimeState::Idle)
+    }
+
+    async fn finish_cancelled(&self, app: &AppHandle, reason: &str, during: RuntimeState) {
+        if let Err(err) = self.injection.lock().await.abandon().await {
+            tracing::warn!("failed to clear text after cancel: {}", err.details);
+        }
+
+        self.inner.lock().await.state = RuntimeState::Idle;
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                state: RuntimeState::Idle,
+                ..TranscriptEvent::default()
+            },
+        );
+        emit_cancelled(
+            app,
+            CancelledEvent {
+                reason: reason.to_string(),
+                during,
+            },
+        );
+    }
+
+    async fn finish_delivery_cancelled(&self, app: &AppHandle, during: RuntimeState) -> String {
+        tracing::info!("deliver: cancelled during {during:?}");
+        let reason = match during {
+            RuntimeState::PostProcessing => "後処理を中断しました",
+            _ => "文字起こしを中断しました",
+        };
+        self.finish_cancelled(app, reason, during).await;
+        String::new()
+    }
+
+    async fn finish_filtered(
+        &self,
+        app: &AppHandle,
+        config: &AppConfig,
+        original_text: String,
+        reasons: Vec<String>,
+        latency: u64,
+        previous_typed: &str,
+    ) -> String {
+        {
+            let mut inner = self.inner.lock().await;
+            inner.current_text.clear();
+            inner.state = RuntimeState::Ready;
+        }
+
+        if config.auto_type {
+            // A filtered re-transcription replaces the earlier result with
+            // nothing.
+            match self.injection.lock().await.discard(previous_typed).await {
+                Ok(()) => {
+                    if let Some(last) = self.inner.lock().await.last_recording.as_mut() {
+                        last.typed.clear();
+                    }
+                }
+                Err(err) => tracing::warn!("failed to clear text after filtering: {}", err.details),
+            }
+        }
+
+        if let Some(main) = app.get_webview_window("main") {
+            let _ = main.show();
+        }
+
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                partial_text: String::new(),
+                final_text: None,
+                state: RuntimeState::Ready,
+                latency_ms: Some(latency),
+                ..TranscriptEvent::default()
+            },
+        );
+        emit_filtered(
+            app,
+            FilteredEvent {
+                reasons,
+                original_text,
+            },
+        );
+        String::new()
+    }
+
+    /// Runs a macro in place of typing, or only reports it in dry-run mode.
+    async fn finish_macro(
+        &self,
+        app: &AppHandle,
+        config: &AppConfig,
+        phrase: String,
+        steps: Result<Vec<MacroStep>, AppError>,
+        latency: u64,
+    ) -> String {
+        {
+            let mut inner = self.inner.lock().await;
+            inner.current_text.clear();
+            inner.state = RuntimeState::Ready;
+        }
+
+        match steps {
+            Ok(steps) => {
+                let described: Vec<String> = steps.iter().map(MacroStep::describe).collect();
+                tracing::info!(
+                    "deliver_final: macro {:?} -> {} (dry run: {})",
+                    phrase,
+                    described.join(", "),
+                    config.macro_dry_run
+                );
+                let mut injection = self.injection.lock().await;
+                if let Err(err) = injection.clear_partial().await {
+                    tracing::warn!("failed to clear partial before macro: {}", err.details);
+                }
+                if !config.macro_dry_run {
+                    if let Err(err) = injection.run_macro(&steps).await {
+                        emit_error(app, err);
+                    }
+                }
+                drop(injection);
+                emit_macro(
+                    app,
+                    MacroEvent {
+                        phrase,
+                        steps: described,
+                        dry_run: config.macro_dry_run,
+                    },
+                );
+            }
+            Err(err) => emit_error(app, err),
+        }
+
+        if let Some(main) = app.get_webview_window("main") {
+            let _ = main.show();
+        }
+
+        emit_transcript(
+            app,
+            TranscriptEvent {
+                partial_text: String::new(),
+                final_text: None,
+                state: RuntimeState::Ready,
+                latency_ms: Some(latency),
+                ..TranscriptEvent::default()
+            },
+        );
+        String::new()
+    }
+
     pub async fn type_text(&self, text: String) -> Result<(), AppError> {
         self.injection.lock().await.type_final(&text).await
     }
@@ -573,18 +1548,42 @@ impl AppRuntime {
                 final_text: None,
                 state: RuntimeState::Idle,
                 latency_ms: None,
+                ..TranscriptEvent::default()
             },
         );
     }
 }
 
-fn cleanup_text(input: String) -> String {
-    input
-        .lines()
-        .map(str::trim)
-        .filter(|l| !l.is_empty())
-        .collect::<Vec<_>>()
-        .join(" ")
+/// Types the LLM's answer as it streams in, first erasing the live preview
+/// and `previous_typed`. Returns the streamed text, or `None` when nothing
+/// was streamed and the result still has to be typed. Stops typing once
+/// `cancel` fires.
+async fn type_stream(
+    injection: &Mutex<InjectionUsecase>,
+    cancel: &CancelToken,
+    previous_typed: &str,
+    chunks: Option<mpsc::UnboundedReceiver<String>>,
+) -> Result<Option<String>, AppError> {
+    let Some(mut chunks) = chunks else {
+        return Ok(None);
+    };
+    let mut streamed: Option<String> = None;
+    while let Some(chunk) = chunks.recv().await {
+        if streamed.is_none() {
+            tokio::time::sleep(Duration::from_millis(120)).await;
+        }
+        let mut injection = injection.lock().await;
+        if cancel.is_cancelled() {
+            break;
+        }
+        if streamed.is_none() {
+            injection.clear_partial().await?;
+            injection.erase_previous(previous_typed).await?;
+        }
+        injection.type_chunk(&chunk).await?;
+        streamed.get_or_insert_with(String::new).push_str(&chunk);
+    }
+    Ok(streamed)
 }
 
 fn emit_transcript(app: &AppHandle, event: TranscriptEvent) {
@@ -601,6 +1600,152 @@ fn emit_error(app: &AppHandle, err: AppError) {
     );
 }
 
+fn emit_cancelled(app: &AppHandle, event: CancelledEvent) {
+    let _ = app.emit("notype://cancelled", event);
+}
+
+fn emit_filtered(app: &AppHandle, event: FilteredEvent) {
+    let _ = app.emit("notype://filtered", event);
+}
+
+fn emit_macro(app: &AppHandle, event: MacroEvent) {
+    let _ = app.emit("notype://macro", event);
+}
+
 fn emit_model_download(app: &AppHandle, event: ModelDownloadEvent) {
     let _ = app.emit("notype://model-download", event);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex as StdMutex;
+
+    use async_trait::async_trait;
+
+    use super::super::inject_backend::{BackendKind, InjectionBackend};
+    use super::super::macros::KeyChord;
+    use super::*;
+
+    struct Recorder(Arc<StdMutex<Vec<String>>>);
+
+    #[async_trait]
+    impl InjectionBackend for Recorder {
+        fn kind(&self) -> BackendKind {
+            BackendKind::Wtype
+        }
+
+        async fn type_text(&self, text: &str) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(text.to_string());
+            Ok(())
+        }
+
+        async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(format!("{key} x{count}"));
+            Ok(())
+        }
+
+        async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(chord.key.clone());
+            Ok(())
+        }
+    }
+
+    fn recording_injection() -> (Arc<StdMutex<Vec<String>>>, InjectionUsecase) {
+        let typed = Arc::new(StdMutex::new(Vec::new()));
+        let injection = InjectionUsecase {
+            injector: Injector::with_backends(vec![Box::new(Recorder(typed.clone()))]),
+            session: InjectionSession::new(),
+            partial_degraded: false,
+            history: EditHistory::default(),
+            streamed: String::new(),
+        };
+        (typed, injection)
+    }
+
+    #[tokio::test]
+    async fn filtered_retranscription_erases_the_earlier_result() {
+        let (typed, mut injection) = recording_injection();
+        injection
+            .type_partial_replace("ご視聴")
+            .await
+            .expect("partial");
+        typed.lock().unwrap().clear();
+
+        injection.discard("前の結果").await.expect("discard");
+        assert_eq!(*typed.lock().unwrap(), ["BackSpace x3", "BackSpace x4"]);
+    }
+
+    #[tokio::test]
+    async fn cancel_after_transcription_types_nothing() {
+        let (typed, injection) = recording_injection();
+        let injection = Mutex::new(injection);
+        let cancel = CancelToken::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send("最初の".to_string()).expect("send");
+        tx.send("回答".to_string()).expect("send");
+        drop(tx);
+
+        cancel.cancel();
+        // Already cancelled: an await on it must not hang.
+        cancel.cancelled().await;
+        let streamed = type_stream(&injection, &cancel, "前の結果", Some(rx))
+            .await
+            .expect("stream");
+        assert_eq!(streamed, None);
+        assert!(typed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancel_while_streaming_erases_the_streamed_text() {
+        let (typed, injection) = recording_injection();
+        let injection = Mutex::new(injection);
+        let cancel = CancelToken::default();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send("最初の".to_string()).expect("send");
+
+        // The stream stays open, as when the LLM is still answering.
+        tokio::select! {
+            _ = type_stream(&injection, &cancel, "前の結果", Some(rx)) => {
+                panic!("the stream is still open")
+            }
+            _ = async {
+                while typed.lock().unwrap().len() < 2 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            } => {}
+        }
+        injection.lock().await.abandon().await.expect("abandon");
+        assert_eq!(
+            *typed.lock().unwrap(),
+            ["BackSpace x4", "最初の", "BackSpace x3"]
+        );
+        drop(tx);
+    }
+
+    #[tokio::test]
+    async fn streaming_replaces_the_live_preview() {
+        let (typed, mut injection) = recording_injection();
+        injection
+            .type_partial_replace("ご視聴")
+            .await
+            .expect("partial");
+        typed.lock().unwrap().clear();
+        let injection = Mutex::new(injection);
+        let (tx, rx) = mpsc::unbounded_channel();
+        tx.send("最初の".to_string()).expect("send");
+        tx.send("回答".to_string()).expect("send");
+        drop(tx);
+
+        let streamed = type_stream(&injection, &CancelToken::default(), "", Some(rx))
+            .await
+            .expect("stream");
+        assert_eq!(streamed.as_deref(), Some("最初の回答"));
+        assert_eq!(*typed.lock().unwrap(), ["BackSpace x3", "最初の", "回答"]);
+
+        let mut injection = injection.lock().await;
+        injection.record_streamed("最初の回答");
+        assert_eq!(injection.session.last_partial(), "");
+        injection.abandon().await.expect("abandon");
+        assert_eq!(typed.lock().unwrap().len(), 3);
+    }
+}
@@ -1,6 +1,8 @@
-use std::path::PathBuf;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use directories::ProjectDirs;
 use tokio::process::{Child, Command};
 use tokio::time::{timeout, Duration};
 
@@ -25,6 +27,7 @@ impl RecordingSession {
             .arg("-c")
             .arg("1")
             .arg(&audio_path)
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| {
                 AppError::new(
@@ -66,8 +69,148 @@ impl RecordingSession {
         }
         Ok(self.audio_path.clone())
     }
+
+    /// Kills the recorder without waiting for it to finalize the WAV header.
+    pub async fn kill(&mut self) -> PathBuf {
+        if let Err(err) = self.child.start_kill() {
+            tracing::warn!("failed to kill recording child: {err}");
+        }
+        let _ = timeout(Duration::from_millis(900), self.child.wait()).await;
+        self.audio_path.clone()
+    }
 }
 
 pub fn cleanup_temp_file(path: &PathBuf) {
     let _ = std::fs::remove_file(path);
 }
+
+const RETAINED_RECORDING_FILENAME: &str = "last-recording.wav";
+
+/// Per-user directory for audio kept for re-transcription. Prefers
+/// `$XDG_RUNTIME_DIR`, which is tmpfs and wiped on logout.
+fn retained_dir() -> PathBuf {
+    match ProjectDirs::from("dev", "notype", "notype") {
+        Some(dirs) => dirs
+            .runtime_dir()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| dirs.cache_dir().join("runtime")),
+        None => std::env::temp_dir().join("notype-runtime"),
+    }
+}
+
+/// Moves a finished recording into the private runtime dir, replacing the
+/// previously retained one.
+pub fn retain_recording(wav: &Path) -> Result<PathBuf, AppError> {
+    let dir = retained_dir();
+    let target = dir.join(RETAINED_RECORDING_FILENAME);
+    let result: std::io::Result<()> = (|| {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        // rename fails across filesystems (/tmp and /run/user usually differ).
+        if std::fs::rename(wav, &target).is_err() {
+            std::fs::copy(wav, &target)?;
+            std::fs::remove_file(wav)?;
+        }
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o600))
+    })();
+
+    result.map_err(|e| {
+        let _ = std::fs::remove_file(wav);
+        AppError::new(
+            "録音の保持に失敗しました",
+            format!("retain {} -> {}: {e}", wav.display(), target.display()),
+        )
+    })?;
+    Ok(target)
+}
+
+pub fn discard_retained_recording() {
+    let _ = std::fs::remove_file(retained_dir().join(RETAINED_RECORDING_FILENAME));
+}
+
+/// Reads the playback length of a PCM WAV file from its RIFF header.
+pub fn wav_duration(path: &Path) -> Result<Duration, AppError> {
+    let bytes = std::fs::read(path).map_err(|e| {
+        AppError::new(
+            "音声ファイルの読み込みに失敗しました",
+            format!("read {}: {e}", path.display()),
+        )
+    })?;
+    parse_wav_duration(&bytes).ok_or_else(|| {
+        AppError::new(
+            "WAV ファイルの形式を認識できませんでした",
+            format!("invalid wav header: {}", path.display()),
+        )
+    })
+}
+
+fn parse_wav_duration(bytes: &[u8]) -> Option<Duration> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut byte_rate: Option<u32> = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = offset + 8;
+        if id == b"fmt " && body + 12 <= bytes.len() {
+            byte_rate = Some(u32::from_le_bytes(
+                bytes[body + 8..body + 12].try_into().ok()?,
+            ));
+        }
+        if id == b"data" {
+            // arecord leaves a placeholder size when it is killed mid-write.
+            let data_len = size.min(bytes.len() - body) as u64;
+            let rate = byte_rate.filter(|r| *r > 0)? as u64;
+            return Some(Duration::from_millis(data_len * 1000 / rate));
+        }
+        offset = body + size + (size % 2);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_header(data_len: u32, declared_len: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&16_000u32.to_le_bytes());
+        bytes.extend_from_slice(&32_000u32.to_le_bytes()); // byte rate
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&declared_len.to_le_bytes());
+        bytes.resize(bytes.len() + data_len as usize, 0);
+        bytes
+    }
+
+    #[test]
+    fn duration_comes_from_data_size_and_byte_rate() {
+        let bytes = wav_header(48_000, 48_000);
+        assert_eq!(
+            parse_wav_duration(&bytes),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn placeholder_data_size_is_clamped_to_file_length() {
+        let bytes = wav_header(32_000, 0x7fff_ffff);
+        assert_eq!(parse_wav_duration(&bytes), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn non_wav_input_is_rejected() {
+        assert_eq!(parse_wav_duration(b"not a wav file"), None);
+    }
+}
@@ -0,0 +1,329 @@
+use std::path::{Path, PathBuf};
+
+use super::audio::{cleanup_temp_file, wav_duration};
+use super::config::{load_config, save_config, ModelSize};
+use super::error::AppError;
+use super::secret::resolve_llm_api_key;
+use super::stt::{PerfParams, SttService};
+
+/// whisper.cpp's own JFK sample (~11 s of English speech). The whisper-cli
+/// installer copies it from the same checkout it builds from into the model
+/// directory, so the benchmark needs no network.
+const REFERENCE_CLIP_FILENAME: &str = "benchmark-reference.wav";
+const REFERENCE_CLIP_LANGUAGE: &str = "en";
+
+/// NFR-03: the final transcript should land within 1.5 s of stopping a
+/// roughly 10 s utterance.
+const TARGET_UTTERANCE_MS: u64 = 10_000;
+const TARGET_FINAL_LATENCY_MS: u64 = 1_500;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkPreset {
+    pub name: &'static str,
+    pub perf: PerfParams,
+}
+
+pub fn presets(cpus: u32) -> Vec<BenchmarkPreset> {
+    let greedy = PerfParams {
+        threads: cpus,
+        beam_size: 1,
+        best_of: 1,
+        ..PerfParams::default()
+    };
+    vec![
+        BenchmarkPreset {
+            name: "default",
+            perf: PerfParams {
+                threads: cpus,
+                ..PerfParams::default()
+            },
+        },
+        BenchmarkPreset {
+            name: "greedy",
+            perf: greedy,
+        },
+        BenchmarkPreset {
+            name: "greedy-no-fallback",
+            perf: PerfParams {
+                no_fallback: true,
+                ..greedy
+            },
+        },
+        BenchmarkPreset {
+            name: "greedy-half-threads",
+            perf: PerfParams {
+                threads: (cpus / 2).max(1),
+                no_fallback: true,
+                ..greedy
+            },
+        },
+        BenchmarkPreset {
+            name: "fast-short-ctx",
+            perf: PerfParams {
+                no_fallback: true,
+                audio_ctx: 768,
+                ..greedy
+            },
+        },
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub model: ModelSize,
+    pub preset: &'static str,
+    pub perf: PerfParams,
+    pub latency_ms: u64,
+    pub clip_ms: u64,
+}
+
+impl BenchmarkResult {
+    pub fn real_time_factor(&self) -> f64 {
+        self.latency_ms as f64 / self.clip_ms.max(1) as f64
+    }
+
+    /// Latency scaled to the 10 s utterance that NFR-03 is phrased in.
+    pub fn estimated_final_ms(&self) -> u64 {
+        self.latency_ms * TARGET_UTTERANCE_MS / self.clip_ms.max(1)
+    }
+
+    pub fn meets_target(&self) -> bool {
+        self.estimated_final_ms() <= TARGET_FINAL_LATENCY_MS
+    }
+}
+
+/// Picks the fastest setup that meets NFR-03, or the fastest overall when
+/// nothing does so the user still gets the least-bad option.
+pub fn recommend(results: &[BenchmarkResult]) -> Option<&BenchmarkResult> {
+    results
+        .iter()
+        .filter(|r| r.meets_target())
+        .min_by_key(|r| r.latency_ms)
+        .or_else(|| results.iter().min_by_key(|r| r.latency_ms))
+}
+
+/// Entry point for `notype benchmark [--apply] [--clip <wav>]`.
+/// Returns the process exit code.
+pub async fn run_cli(args: &[String]) -> i32 {
+    match run(args).await {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("benchmark failed: {err}");
+            1
+        }
+    }
+}
+
+async fn run(args: &[String]) -> Result<(), AppError> {
+    let apply = args.iter().any(|a| a == "--apply");
+    let user_clip = args
+        .iter()
+        .position(|a| a == "--clip")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from);
+
+    let mut config = load_config().map_err(AppError::from)?;
+    // Migrate a plaintext key first; saving below would otherwise drop it.
+    resolve_llm_api_key(&mut config).await;
+    let (source, language) = match user_clip {
+        Some(path) => (path, config.language.clone()),
+        None => (reference_clip()?, REFERENCE_CLIP_LANGUAGE.to_string()),
+    };
+
+    // whisper-cli writes its JSON next to the input, so work on a private copy.
+    let clip = std::env::temp_dir().join(format!("notype-bench-{}.wav", uuid::Uuid::new_v4()));
+    std::fs::copy(&source, &clip).map_err(|e| {
+        AppError::new(
+            "ベンチマーク用音声の準備に失敗しました",
+            format!("copy {}: {e}", source.display()),
+        )
+    })?;
+
+    let result = run_matrix(&clip, &language).await;
+    cleanup_temp_file(&clip);
+    let results = result?;
+
+    let Some(best) = recommend(&results) else {
+        return Err(AppError::new(
+            "インストール済みのモデルが見つかりません",
+            "no model files under the model directory",
+        ));
+    };
+
+    print_results(&results);
+    println!();
+    if best.meets_target() {
+        println!(
+            "recommended: model={} preset={} ({} ms, meets NFR-03 final target)",
+            best.model.as_str(),
+            best.preset,
+            best.latency_ms
+        );
+    } else {
+        println!(
+            "recommended: model={} preset={} ({} ms, no setup met the {} ms NFR-03 final target)",
+            best.model.as_str(),
+            best.preset,
+            best.latency_ms,
+            TARGET_FINAL_LATENCY_MS
+        );
+    }
+
+    if apply {
+        config.model = best.model;
+        best.perf.write_to(&mut config);
+        save_config(&config).map_err(AppError::from)?;
+        println!("applied to config");
+    } else {
+        println!("re-run with --apply to save this setup");
+    }
+    Ok(())
+}
+
+fn print_results(results: &[BenchmarkResult]) {
+    println!(
+        "{:<8} {:<20} {:>10} {:>7} {:>12} {:>6}",
+        "model", "preset", "latency", "rtf", "est. 10s", "meets"
+    );
+    for result in results {
+        println!(
+            "{:<8} {:<20} {:>8}ms {:>7.3} {:>10}ms {:>6}",
+            result.model.as_str(),
+            result.preset,
+            result.latency_ms,
+            result.real_time_factor(),
+            result.estimated_final_ms(),
+            if result.meets_target() { "yes" } else { "no" }
+        );
+    }
+}
+
+async fn run_matrix(clip: &Path, language: &str) -> Result<Vec<BenchmarkResult>, AppError> {
+    let clip_ms = wav_duration(clip)?.as_millis() as u64;
+    let cpus = std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(4);
+
+    tracing::info!(
+        "benchmark: clip {clip_ms} ms, {cpus} cpus, target <= {TARGET_FINAL_LATENCY_MS} ms per {TARGET_UTTERANCE_MS} ms of speech"
+    );
+
+    let mut results = Vec::new();
+    for model in ModelSize::ALL {
+        let mut stt = SttService::new(model);
+        if !stt.model_path().exists() {
+            continue;
+        }
+        stt.options.language = language.to_string();
+
+        // Warm the page cache so the first preset is not penalised by disk I/O.
+        stt.transcribe_final(clip).await?;
+
+        for preset in presets(cpus) {
+            stt.options.perf = preset.perf;
+            let (_, latency_ms) = stt.transcribe_final(clip).await?;
+            let result = BenchmarkResult {
+                model,
+                preset: preset.name,
+                perf: preset.perf,
+                latency_ms,
+                clip_ms,
+            };
+            tracing::info!(
+                "benchmark: {} {}: {} ms (rtf {:.3})",
+                model.as_str(),
+                preset.name,
+                result.latency_ms,
+                result.real_time_factor()
+            );
+            results.push(result);
+        }
+    }
+    Ok(results)
+}
+
+/// The reference clip in the model directory, put there by
+/// `scripts/install-whisper-cli-local.sh`.
+fn reference_clip() -> Result<PathBuf, AppError> {
+    reference_clip_in(&SttService::new(ModelSize::Small).model_dir)
+}
+
+fn reference_clip_in(model_dir: &Path) -> Result<PathBuf, AppError> {
+    let path = model_dir.join(REFERENCE_CLIP_FILENAME);
+    if path.is_file() {
+        return Ok(path);
+    }
+    Err(AppError::new(
+        "ベンチマーク用音声が見つかりません。scripts/install-whisper-cli-local.sh を実行するか、--clip で音声を指定してください",
+        format!("{} does not exist", path.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(model: ModelSize, preset: &'static str, latency_ms: u64) -> BenchmarkResult {
+        BenchmarkResult {
+            model,
+            preset,
+            perf: PerfParams::default(),
+            latency_ms,
+            clip_ms: 11_000,
+        }
+    }
+
+    #[test]
+    fn reference_clip_is_where_the_installer_copies_it() {
+        let installer = include_str!("../../../scripts/install-whisper-cli-local.sh");
+        assert!(installer.contains("samples/jfk.wav"));
+        assert!(installer.contains(&format!("${{MODEL_DIR}}/{REFERENCE_CLIP_FILENAME}")));
+
+        let dir = std::env::temp_dir().join(format!("notype-bench-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        assert!(reference_clip_in(&dir).is_err());
+        std::fs::write(dir.join(REFERENCE_CLIP_FILENAME), b"RIFF").expect("clip");
+        assert_eq!(
+            reference_clip_in(&dir).expect("clip"),
+            dir.join(REFERENCE_CLIP_FILENAME)
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn target_is_scaled_to_a_ten_second_utterance() {
+        let ok = result(ModelSize::Small, "greedy", 1_650);
+        assert_eq!(ok.estimated_final_ms(), 1_500);
+        assert!(ok.meets_target());
+        assert!(!result(ModelSize::Small, "default", 1_700).meets_target());
+    }
+
+    #[test]
+    fn recommends_fastest_setup_meeting_target() {
+        let results = vec![
+            result(ModelSize::Medium, "greedy", 4_000),
+            result(ModelSize::Small, "default", 1_200),
+            result(ModelSize::Small, "greedy", 900),
+        ];
+        let best = recommend(&results).expect("recommendation");
+        assert_eq!(best.preset, "greedy");
+        assert_eq!(best.model, ModelSize::Small);
+    }
+
+    #[test]
+    fn falls_back_to_fastest_when_nothing_meets_target() {
+        let results = vec![
+            result(ModelSize::Medium, "default", 9_000),
+            result(ModelSize::Small, "default", 3_000),
+        ];
+        let best = recommend(&results).expect("recommendation");
+        assert!(!best.meets_target());
+        assert_eq!(best.model, ModelSize::Small);
+        assert!(recommend(&[]).is_none());
+    }
+
+    #[test]
+    fn presets_never_request_zero_threads() {
+        assert!(presets(1).iter().all(|p| p.perf.threads >= 1));
+    }
+}
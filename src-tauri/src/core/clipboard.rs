@@ -0,0 +1,150 @@
+use std::future::Future;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::error::AppError;
+
+const TEXT_TYPE: &str = "text/plain;charset=utf-8";
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the app gets to read a paste before the clipboard is restored.
+const RESTORE_DELAY: Duration = Duration::from_millis(300);
+
+/// The clipboard as it was before a paste. `wl-copy` offers a single type,
+/// so only the one that is put back is saved.
+#[derive(Debug, Default)]
+pub struct SavedClipboard {
+    entry: Option<(String, Vec<u8>)>,
+}
+
+impl SavedClipboard {
+    /// Puts the saved contents back, or clears the clipboard when it was
+    /// empty so the pasted text does not linger.
+    pub async fn restore(self) {
+        let result = match &self.entry {
+            Some((mime, bytes)) => copy_bytes(mime, bytes).await,
+            None => wl_copy(&["--clear"], None).await,
+        };
+        if let Err(err) = result {
+            tracing::warn!("clipboard: failed to restore: {}", err.details);
+        }
+    }
+}
+
+/// Reads the clipboard in the type `restore` can put back. An empty or
+/// unreadable clipboard saves as empty.
+pub async fn save() -> SavedClipboard {
+    let Some(types) = wl_paste(&["--list-types"]).await else {
+        return SavedClipboard::default();
+    };
+    let types = String::from_utf8_lossy(&types);
+    let Some(mime) = preferred_type(&types) else {
+        return SavedClipboard::default();
+    };
+    let entry = wl_paste(&["--no-newline", "--type", mime])
+        .await
+        .map(|bytes| (mime.to_string(), bytes));
+    SavedClipboard { entry }
+}
+
+/// Plain text wins over richer formats; without text the first MIME type,
+/// e.g. an image. X11 targets such as TARGETS or UTF8_STRING are skipped.
+fn preferred_type(types: &str) -> Option<&str> {
+    let mut mimes = types
+        .lines()
+        .map(str::trim)
+        .filter(|mime| mime.contains('/'));
+    let first = mimes.clone().next();
+    mimes.find(|mime| mime.starts_with("text/plain")).or(first)
+}
+
+/// Puts `text` on the clipboard, runs `press_paste` and then puts the
+/// previous clipboard back once the app has had time to read it.
+pub async fn paste(
+    text: &str,
+    press_paste: impl Future<Output = Result<(), AppError>>,
+) -> Result<(), AppError> {
+    let saved = save().await;
+    copy(text).await?;
+    let pasted = press_paste.await;
+    tokio::time::sleep(RESTORE_DELAY).await;
+    saved.restore().await;
+    pasted
+}
+
+async fn copy(text: &str) -> Result<(), AppError> {
+    copy_bytes(TEXT_TYPE, text.as_bytes()).await
+}
+
+async fn copy_bytes(mime: &str, bytes: &[u8]) -> Result<(), AppError> {
+    wl_copy(&["--type", mime], Some(bytes)).await
+}
+
+async fn wl_copy(args: &[&str], stdin: Option<&[u8]>) -> Result<(), AppError> {
+    let error = |detail: String| {
+        AppError::new(
+            "クリップボードへのコピーに失敗しました",
+            format!("wl-copy: {detail}"),
+        )
+    };
+    let mut child = Command::new("wl-copy")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| error(e.to_string()))?;
+    // The text goes through stdin so it never shows up in the process list.
+    if let Some(mut input) = child.stdin.take() {
+        input
+            .write_all(stdin.unwrap_or_default())
+            .await
+            .map_err(|e| error(e.to_string()))?;
+    }
+    let output = tokio::time::timeout(TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| error("timed out".to_string()))?
+        .map_err(|e| error(e.to_string()))?;
+    if !output.status.success() {
+        return Err(error(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn wl_paste(args: &[&str]) -> Option<Vec<u8>> {
+    let output = tokio::time::timeout(
+        TIMEOUT,
+        Command::new("wl-paste")
+            .args(args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    output.status.success().then_some(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_saved_over_rich_formats() {
+        assert_eq!(
+            preferred_type("text/html\nTARGETS\ntext/plain;charset=utf-8\nUTF8_STRING\n"),
+            Some("text/plain;charset=utf-8")
+        );
+        assert_eq!(
+            preferred_type("TARGETS\nimage/png\nimage/jpeg"),
+            Some("image/png")
+        );
+        assert_eq!(preferred_type("TARGETS\n"), None);
+        assert_eq!(preferred_type(""), None);
+    }
+}
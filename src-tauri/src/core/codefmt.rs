@@ -0,0 +1,490 @@
+/// Identifier casing spoken before the words it joins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Casing {
+    Camel,
+    Pascal,
+    Snake,
+    Constant,
+    Kebab,
+}
+
+const CASINGS: &[(&str, Casing)] = &[
+    ("camel case", Casing::Camel),
+    ("pascal case", Casing::Pascal),
+    ("title case", Casing::Pascal),
+    ("snake case", Casing::Snake),
+    ("constant case", Casing::Constant),
+    ("screaming snake case", Casing::Constant),
+    ("kebab case", Casing::Kebab),
+    ("dash case", Casing::Kebab),
+];
+
+/// Casing for the single next word: capitalized, or all caps when `true`.
+const WORD_CASINGS: &[(&str, bool)] = &[("cap", false), ("capital", false), ("all caps", true)];
+
+/// Says the next word as a plain word even when it is a symbol name.
+const ESCAPES: &[&str] = &["literal"];
+
+/// How a symbol is spaced against its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Word,
+    /// Spaced on both sides: `=`, `=>`, `+`.
+    Binary,
+    /// Spaced before, attached to what follows: `{`, `!`, `&`.
+    Prefix,
+    /// Attached to a preceding word or closing bracket (a call), else spaced.
+    Open,
+    /// Closing brackets, attached before: `)`, `]`.
+    Close,
+    /// Attached before, spaced after: `,`, `;`, `:`.
+    Suffix,
+    /// Attached on both sides: `.`, `::`, `_`.
+    Join,
+    Quote,
+}
+
+const SYMBOLS: &[(&str, &str, Kind)] = &[
+    ("open paren", "(", Kind::Open),
+    ("left paren", "(", Kind::Open),
+    ("close paren", ")", Kind::Close),
+    ("right paren", ")", Kind::Close),
+    ("open bracket", "[", Kind::Open),
+    ("left bracket", "[", Kind::Open),
+    ("close bracket", "]", Kind::Close),
+    ("right bracket", "]", Kind::Close),
+    ("open brace", "{", Kind::Prefix),
+    ("open curly", "{", Kind::Prefix),
+    ("left brace", "{", Kind::Prefix),
+    ("close brace", "}", Kind::Close),
+    ("close curly", "}", Kind::Close),
+    ("right brace", "}", Kind::Close),
+    ("open angle", "<", Kind::Open),
+    ("close angle", ">", Kind::Close),
+    ("arrow", "=>", Kind::Binary),
+    ("fat arrow", "=>", Kind::Binary),
+    ("thin arrow", "->", Kind::Binary),
+    ("dash arrow", "->", Kind::Binary),
+    ("equals", "=", Kind::Binary),
+    ("equal", "=", Kind::Binary),
+    ("double equals", "==", Kind::Binary),
+    ("triple equals", "===", Kind::Binary),
+    ("not equals", "!=", Kind::Binary),
+    ("less than", "<", Kind::Binary),
+    ("greater than", ">", Kind::Binary),
+    ("less than or equal", "<=", Kind::Binary),
+    ("greater than or equal", ">=", Kind::Binary),
+    ("plus", "+", Kind::Binary),
+    ("minus", "-", Kind::Binary),
+    ("times", "*", Kind::Binary),
+    ("divided by", "/", Kind::Binary),
+    ("modulo", "%", Kind::Binary),
+    ("plus equals", "+=", Kind::Binary),
+    ("minus equals", "-=", Kind::Binary),
+    ("and and", "&&", Kind::Binary),
+    ("double ampersand", "&&", Kind::Binary),
+    ("or or", "||", Kind::Binary),
+    ("double pipe", "||", Kind::Binary),
+    ("pipe", "|", Kind::Binary),
+    ("plus plus", "++", Kind::Suffix),
+    ("minus minus", "--", Kind::Suffix),
+    ("comma", ",", Kind::Suffix),
+    ("semicolon", ";", Kind::Suffix),
+    ("colon", ":", Kind::Suffix),
+    ("question mark", "?", Kind::Suffix),
+    ("dot", ".", Kind::Join),
+    ("double colon", "::", Kind::Join),
+    ("underscore", "_", Kind::Join),
+    ("slash", "/", Kind::Join),
+    ("backslash", "\\", Kind::Join),
+    ("caret", "^", Kind::Join),
+    ("new line", "\n", Kind::Join),
+    ("space", " ", Kind::Join),
+    ("tab", "\t", Kind::Join),
+    ("dash", "-", Kind::Prefix),
+    ("hyphen", "-", Kind::Prefix),
+    ("bang", "!", Kind::Prefix),
+    ("star", "*", Kind::Prefix),
+    ("ampersand", "&", Kind::Prefix),
+    ("at sign", "@", Kind::Prefix),
+    ("hash", "#", Kind::Prefix),
+    ("dollar", "$", Kind::Prefix),
+    ("tilde", "~", Kind::Prefix),
+    ("quote", "\"", Kind::Quote),
+    ("double quote", "\"", Kind::Quote),
+    ("single quote", "'", Kind::Quote),
+    ("backtick", "`", Kind::Quote),
+];
+
+const DIGITS: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+];
+
+#[derive(Debug, Clone)]
+struct Word {
+    /// As transcribed, without surrounding punctuation.
+    raw: String,
+    lower: String,
+}
+
+#[derive(Debug, Clone)]
+struct Piece {
+    text: String,
+    kind: Kind,
+    /// For quotes: whether this one closes an open quote.
+    closing: bool,
+}
+
+/// Formats a spoken transcript as code: casing commands join identifiers,
+/// symbol names become symbols and spacing follows the symbols' roles.
+///
+/// `camel case user id equals open paren close paren arrow x` becomes
+/// `userId = () => x`. Phrases are English; `literal` types the next word
+/// as is.
+pub fn format_code(text: &str) -> String {
+    let words = split_words(text);
+    let mut pieces: Vec<Piece> = Vec::new();
+    let mut open_quotes: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        if let Some(n) = ESCAPES.iter().find_map(|e| match_phrase(&words, i, e)) {
+            if let Some(word) = words.get(i + n) {
+                pieces.push(word_piece(plain_word(word)));
+            }
+            i += n + 1;
+            continue;
+        }
+        if let Some((n, casing)) = CASINGS
+            .iter()
+            .filter_map(|(phrase, casing)| Some((match_phrase(&words, i, phrase)?, *casing)))
+            .max_by_key(|(n, _)| *n)
+        {
+            let start = i + n;
+            let mut end = start;
+            while end < words.len() && is_plain(&words, end) {
+                end += 1;
+            }
+            if end > start {
+                pieces.push(word_piece(apply_casing(casing, &words[start..end])));
+            }
+            i = end;
+            continue;
+        }
+        if let Some((n, all_caps)) = WORD_CASINGS
+            .iter()
+            .filter_map(|(phrase, all_caps)| Some((match_phrase(&words, i, phrase)?, *all_caps)))
+            .max_by_key(|(n, _)| *n)
+        {
+            if let Some(word) = words.get(i + n) {
+                let word = digit_or(word);
+                pieces.push(word_piece(if all_caps {
+                    word.to_uppercase()
+                } else {
+                    capitalize(&word)
+                }));
+            }
+            i += n + 1;
+            continue;
+        }
+        if let Some((n, symbol, kind)) = match_symbol(&words, i) {
+            let closing = kind == Kind::Quote && open_quotes.last() == Some(&symbol);
+            if kind == Kind::Quote {
+                if closing {
+                    open_quotes.pop();
+                } else {
+                    open_quotes.push(symbol);
+                }
+            }
+            pieces.push(Piece {
+                text: symbol.to_string(),
+                kind,
+                closing,
+            });
+            i += n;
+            continue;
+        }
+        pieces.push(word_piece(plain_word(&words[i])));
+        i += 1;
+    }
+    join(&pieces)
+}
+
+/// Splits on whitespace and on hyphens inside words, dropping the commas
+/// and full stops whisper adds around words.
+fn split_words(text: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    for token in text.split_whitespace() {
+        let token = token.trim_matches(|c: char| ",.!?;:\"、。".contains(c));
+        let hyphenated =
+            token.contains('-') && token.chars().all(|c| c.is_alphanumeric() || c == '-');
+        let parts: Vec<&str> = if hyphenated {
+            token.split('-').collect()
+        } else {
+            vec![token]
+        };
+        for part in parts.into_iter().filter(|p| !p.is_empty()) {
+            words.push(Word {
+                raw: part.to_string(),
+                lower: part.to_lowercase(),
+            });
+        }
+    }
+    words
+}
+
+/// Words of `phrase` consumed at `i`; also accepts the phrase written as
+/// one word (`camelcase`).
+fn match_phrase(words: &[Word], i: usize, phrase: &str) -> Option<usize> {
+    let parts: Vec<&str> = phrase.split(' ').collect();
+    let spoken = words.get(i..i + parts.len());
+    if spoken.is_some_and(|s| s.iter().zip(&parts).all(|(w, p)| w.lower == *p)) {
+        return Some(parts.len());
+    }
+    (parts.len() > 1 && words.get(i)?.lower == parts.concat()).then_some(1)
+}
+
+/// Longest symbol phrase at `i`, so `double equals` beats `equals`.
+fn match_symbol(words: &[Word], i: usize) -> Option<(usize, &'static str, Kind)> {
+    SYMBOLS
+        .iter()
+        .filter_map(|(phrase, symbol, kind)| {
+            Some((match_phrase(words, i, phrase)?, *symbol, *kind))
+        })
+        .max_by_key(|(n, symbol, _)| (*n, symbol.len()))
+}
+
+/// Whether the word at `i` can be part of an identifier.
+fn is_plain(words: &[Word], i: usize) -> bool {
+    let starts_phrase = |phrase: &str| match_phrase(words, i, phrase).is_some();
+    !(ESCAPES.iter().any(|e| starts_phrase(e))
+        || CASINGS.iter().any(|(p, _)| starts_phrase(p))
+        || WORD_CASINGS.iter().any(|(p, _)| starts_phrase(p))
+        || match_symbol(words, i).is_some())
+}
+
+fn digit_or(word: &Word) -> String {
+    DIGITS
+        .iter()
+        .position(|d| *d == word.lower)
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| word.lower.clone())
+}
+
+/// Lowercases a sentence-start capital but keeps `JSON` or `getValue`.
+fn plain_word(word: &Word) -> String {
+    if word.raw.chars().skip(1).any(char::is_uppercase) {
+        return word.raw.clone();
+    }
+    digit_or(word)
+}
+
+fn word_piece(text: String) -> Piece {
+    Piece {
+        text,
+        kind: Kind::Word,
+        closing: false,
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn apply_casing(casing: Casing, words: &[Word]) -> String {
+    let parts: Vec<String> = words.iter().map(|w| w.lower.clone()).collect();
+    match casing {
+        Casing::Camel => parts
+            .iter()
+            .enumerate()
+            .map(|(n, p)| if n == 0 { p.clone() } else { capitalize(p) })
+            .collect(),
+        Casing::Pascal => parts.iter().map(|p| capitalize(p)).collect(),
+        Casing::Snake => parts.join("_"),
+        Casing::Constant => parts.join("_").to_uppercase(),
+        Casing::Kebab => parts.join("-"),
+    }
+}
+
+fn glues_right(piece: &Piece) -> bool {
+    match piece.kind {
+        Kind::Prefix | Kind::Open | Kind::Join => true,
+        Kind::Quote => !piece.closing,
+        _ => false,
+    }
+}
+
+fn space_between(prev: &Piece, next: &Piece) -> bool {
+    if glues_right(prev) {
+        return false;
+    }
+    match next.kind {
+        Kind::Open => !matches!(prev.kind, Kind::Word | Kind::Close),
+        Kind::Close | Kind::Suffix | Kind::Join => false,
+        Kind::Quote => !next.closing,
+        Kind::Word | Kind::Binary | Kind::Prefix => true,
+    }
+}
+
+fn join(pieces: &[Piece]) -> String {
+    let mut out = String::new();
+    for (n, piece) in pieces.iter().enumerate() {
+        if n > 0 && space_between(&pieces[n - 1], piece) {
+            out.push(' ');
+        }
+        out.push_str(&piece.text);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(cases: &[(&str, &str)]) {
+        for (spoken, expected) in cases {
+            assert_eq!(format_code(spoken), *expected, "spoken: {spoken:?}");
+        }
+    }
+
+    #[test]
+    fn casing_commands_join_identifiers() {
+        check(&[
+            ("camel case user id", "userId"),
+            ("snake case max retries", "max_retries"),
+            ("pascal case http client", "HttpClient"),
+            ("title case http client", "HttpClient"),
+            ("constant case max retries", "MAX_RETRIES"),
+            ("screaming snake case max retries", "MAX_RETRIES"),
+            ("kebab case main nav", "main-nav"),
+            ("snake case retry 2", "retry_2"),
+            ("camel case add one", "addOne"),
+            ("camel case user ID", "userId"),
+        ]);
+    }
+
+    #[test]
+    fn casing_stops_at_the_next_symbol_or_command() {
+        check(&[
+            (
+                "camel case user id equals snake case max retries",
+                "userId = max_retries",
+            ),
+            ("snake case a b camel case c d", "a_b cD"),
+            ("camel case is valid open paren", "isValid("),
+            ("camel case", ""),
+        ]);
+    }
+
+    #[test]
+    fn whisper_spellings_of_commands() {
+        check(&[
+            ("Camel case, user ID.", "userId"),
+            ("CamelCase user id", "userId"),
+            ("camel-case user id", "userId"),
+            ("Snake-case max retries.", "max_retries"),
+        ]);
+    }
+
+    #[test]
+    fn single_word_casing() {
+        check(&[
+            ("cap foo", "Foo"),
+            ("capital foo bar", "Foo bar"),
+            ("all caps foo bar", "FOO bar"),
+            ("all caps", ""),
+        ]);
+    }
+
+    #[test]
+    fn symbols_and_spacing() {
+        check(&[
+            ("open paren close paren", "()"),
+            ("x arrow y", "x => y"),
+            ("x thin arrow y", "x -> y"),
+            ("x equals zero", "x = 0"),
+            ("x double equals five", "x == 5"),
+            ("x triple equals y", "x === y"),
+            ("x not equals y", "x != y"),
+            ("a less than or equal b", "a <= b"),
+            ("a and and b or or c", "a && b || c"),
+            ("x plus equals one", "x += 1"),
+            ("x plus plus semicolon", "x++;"),
+            ("a comma b", "a, b"),
+            ("self dot name", "self.name"),
+            ("std double colon io double colon stdin", "std::io::stdin"),
+            ("src slash main dot rs", "src/main.rs"),
+            ("cargo test dash dash verbose", "cargo test --verbose"),
+            ("if bang done", "if !done"),
+            ("a new line b", "a\nb"),
+            ("x colon i32", "x: i32"),
+        ]);
+    }
+
+    #[test]
+    fn brackets_attach_to_calls_but_not_operators() {
+        check(&[
+            ("foo open paren x close paren", "foo(x)"),
+            ("x equals open paren a close paren", "x = (a)"),
+            ("items open bracket zero close bracket", "items[0]"),
+            (
+                "f open paren a comma open paren b close paren close paren",
+                "f(a, (b))",
+            ),
+            (
+                "fn main open paren close paren thin arrow i32 open brace",
+                "fn main() -> i32 {",
+            ),
+            ("if x double equals five open brace", "if x == 5 {"),
+            ("open brace close brace", "{}"),
+            ("vec open angle cap t close angle", "vec<T>"),
+        ]);
+    }
+
+    #[test]
+    fn quotes_pair_up() {
+        check(&[
+            (
+                "print open paren quote hello quote close paren semicolon",
+                "print(\"hello\");",
+            ),
+            ("x equals single quote a single quote", "x = 'a'"),
+            ("quote a quote comma quote b quote", "\"a\", \"b\""),
+            ("backtick code backtick", "`code`"),
+        ]);
+    }
+
+    #[test]
+    fn full_statements() {
+        check(&[
+            (
+                "const camel case add one equals open paren x close paren arrow x plus one",
+                "const addOne = (x) => x + 1",
+            ),
+            (
+                "let snake case max retries colon u32 equals three semicolon",
+                "let max_retries: u32 = 3;",
+            ),
+            (
+                "if camel case user id not equals null open brace",
+                "if userId != null {",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn plain_words_and_escapes() {
+        check(&[
+            ("Return value", "return value"),
+            ("parse JSON", "parse JSON"),
+            ("call getValue", "call getValue"),
+            ("literal plus one", "plus 1"),
+            ("literal camel case", "camel case"),
+            ("camel case literal plus", "plus"),
+            ("", ""),
+        ]);
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::config::AppConfig;
+
+/// Undo history kept across utterances.
+const HISTORY_LIMIT: usize = 32;
+
+/// Clause delimiters; a command must fill a whole clause.
+const DELIMITERS: &[char] = &['.', ',', '!', '?', '。', '、', '，', '．', '！', '？', '\n'];
+
+/// What a spoken command does instead of being typed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum CommandAction {
+    /// Presses an XKB key such as `Return` or `Tab`.
+    Key {
+        key: String,
+        #[serde(default = "one")]
+        repeat: u32,
+    },
+    /// Types `text`, e.g. a punctuation mark.
+    Insert { text: String },
+    /// Erases what was typed last.
+    DeleteLast,
+    /// Reverts the last typing or deletion.
+    Undo,
+    /// Types the command phrase that follows literally.
+    Escape,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceCommand {
+    pub phrase: String,
+    #[serde(flatten)]
+    pub action: CommandAction,
+}
+
+fn key(name: &str, repeat: u32) -> CommandAction {
+    CommandAction::Key {
+        key: name.to_string(),
+        repeat,
+    }
+}
+
+fn insert(text: &str) -> CommandAction {
+    CommandAction::Insert {
+        text: text.to_string(),
+    }
+}
+
+fn builtin_commands(language: &str) -> Vec<(&'static str, CommandAction)> {
+    match language {
+        "ja" => vec![
+            ("改行", key("Return", 1)),
+            ("段落", key("Return", 2)),
+            ("新しい段落", key("Return", 2)),
+            ("句点", insert("。")),
+            ("読点", insert("、")),
+            ("疑問符", insert("？")),
+            ("感嘆符", insert("！")),
+            ("削除", CommandAction::DeleteLast),
+            ("今のを消して", CommandAction::DeleteLast),
+            ("元に戻す", CommandAction::Undo),
+            ("取り消し", CommandAction::Undo),
+            ("そのまま", CommandAction::Escape),
+        ],
+        "en" => vec![
+            ("new line", key("Return", 1)),
+            ("new paragraph", key("Return", 2)),
+            ("period", insert(".")),
+            ("full stop", insert(".")),
+            ("comma", insert(",")),
+            ("question mark", insert("?")),
+            ("exclamation mark", insert("!")),
+            ("exclamation point", insert("!")),
+            ("colon", insert(":")),
+            ("delete that", CommandAction::DeleteLast),
+            ("scratch that", CommandAction::DeleteLast),
+            ("undo", CommandAction::Undo),
+            ("undo that", CommandAction::Undo),
+            ("literally", CommandAction::Escape),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// A final transcript split into text to type and recognized commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Text(String),
+    Command(CommandAction),
+}
+
+/// The text a list of segments leaves behind, for display and history.
+pub fn render_segments(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.clone(),
+            Segment::Command(CommandAction::Insert { text }) => text.clone(),
+            Segment::Command(CommandAction::Key { key, repeat }) if key == "Return" => {
+                "\n".repeat(*repeat as usize)
+            }
+            Segment::Command(_) => String::new(),
+        })
+        .collect()
+}
+
+pub struct CommandRecognizer {
+    commands: BTreeMap<String, Vec<(String, CommandAction)>>,
+}
+
+impl CommandRecognizer {
+    /// Built-in phrases for ja/en plus the user's `voiceCommands`; a user
+    /// phrase replaces a built-in one.
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut commands: BTreeMap<String, Vec<(String, CommandAction)>> = ["ja", "en"]
+            .iter()
+            .map(|lang| {
+                let list = builtin_commands(lang)
+                    .into_iter()
+                    .map(|(phrase, action)| (phrase.to_string(), action))
+                    .collect();
+                (lang.to_string(), list)
+            })
+            .collect();
+        for (lang, user) in &config.voice_commands {
+            let list = commands.entry(lang.clone()).or_default();
+            for command in user {
+                let phrase = normalize_phrase(&command.phrase);
+                if phrase.is_empty() {
+                    continue;
+                }
+                list.retain(|(p, _)| *p != phrase);
+                list.push((phrase, command.action.clone()));
+            }
+        }
+        Self { commands }
+    }
+
+    /// Splits `text` into clauses and turns each clause that is exactly a
+    /// command phrase into a command. Commands inside a sentence ("the trial
+    /// period ended") are left as text.
+    pub fn parse(&self, text: &str, language: Option<&str>) -> Vec<Segment> {
+        let commands: Vec<&(String, CommandAction)> = self
+            .commands
+            .iter()
+            .filter(|(lang, _)| language.is_none_or(|l| l == lang.as_str()))
+            .flat_map(|(_, list)| list.iter())
+            .collect();
+        let find = |phrase: &str| {
+            commands
+                .iter()
+                .find(|(p, _)| p == phrase)
+                .map(|(_, action)| action)
+        };
+
+        let mut segments = Vec::new();
+        // An escape word said as its own clause ("literally, period.").
+        let mut pending_escape: Option<&str> = None;
+        for clause in split_clauses(text) {
+            let action = find(&normalize_phrase(clause));
+            if let Some(escape) = pending_escape.take() {
+                if action.is_some() {
+                    push_text(&mut segments, clause);
+                    continue;
+                }
+                push_text(&mut segments, escape);
+            }
+            match action {
+                Some(CommandAction::Escape) => pending_escape = Some(clause),
+                Some(action) => push_command(&mut segments, action.clone()),
+                None => push_text(&mut segments, self.unescape(clause, &commands)),
+            }
+        }
+        if let Some(escape) = pending_escape {
+            push_text(&mut segments, escape);
+        }
+        segments
+    }
+
+    /// "literally new line." → "new line."; anything else is kept as is.
+    fn unescape<'a>(&self, clause: &'a str, commands: &[&(String, CommandAction)]) -> &'a str {
+        let trimmed = clause.trim_start();
+        for (escape, _) in commands
+            .iter()
+            .filter(|(_, action)| *action == CommandAction::Escape)
+        {
+            let Some(head) = trimmed.get(..escape.len()) else {
+                continue;
+            };
+            if !head.eq_ignore_ascii_case(escape) {
+                continue;
+            }
+            let rest = trimmed[escape.len()..]
+                .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, ',' | '、' | '，'));
+            let phrase = normalize_phrase(rest);
+            if commands
+                .iter()
+                .any(|(p, action)| *p == phrase && *action != CommandAction::Escape)
+            {
+                return rest;
+            }
+        }
+        clause
+    }
+}
+
+/// Lowercased phrase without surrounding whitespace and delimiters.
+fn normalize_phrase(text: &str) -> String {
+    text.trim_matches(|c: char| c.is_whitespace() || DELIMITERS.contains(&c))
+        .to_lowercase()
+}
+
+/// Clauses end after a delimiter and any whitespace; nothing is dropped, so
+/// joining the clauses gives back `text`.
+fn split_clauses(text: &str) -> Vec<&str> {
+    let mut clauses = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !DELIMITERS.contains(&c) {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some((j, next)) = chars.peek().copied() {
+            if !next.is_whitespace() && !DELIMITERS.contains(&next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            chars.next();
+        }
+        clauses.push(&text[start..end]);
+        start = end;
+    }
+    if start < text.len() {
+        clauses.push(&text[start..]);
+    }
+    clauses
+}
+
+fn push_text(segments: &mut Vec<Segment>, text: &str) {
+    let after_key = matches!(
+        segments.last(),
+        Some(Segment::Command(CommandAction::Key { .. }))
+    );
+    let text = if after_key { text.trim_start() } else { text };
+    if text.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(Segment::Text(previous)) => previous.push_str(text),
+        _ => segments.push(Segment::Text(text.to_string())),
+    }
+}
+
+fn push_command(segments: &mut Vec<Segment>, action: CommandAction) {
+    if let Some(Segment::Text(previous)) = segments.last_mut() {
+        // The spoken mark replaces whatever punctuation whisper put there.
+        let keep = match action {
+            CommandAction::Insert { .. } => previous
+                .trim_end_matches(|c: char| c.is_whitespace() || DELIMITERS.contains(&c))
+                .len(),
+            _ => previous.trim_end().len(),
+        };
+        previous.truncate(keep);
+        if previous.is_empty() {
+            segments.pop();
+        }
+    }
+    segments.push(Segment::Command(action));
+}
+
+/// One injector operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditStep {
+    Type(String),
+    Key { key: String, repeat: u32 },
+    Erase(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Edit {
+    Typed,
+    Deleted(String),
+}
+
+/// Remembers what notype typed, chunk by chunk, so "delete that" and "undo"
+/// know how many characters to erase or re-type.
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    typed: Vec<String>,
+    edits: Vec<Edit>,
+}
+
+impl EditHistory {
+    /// Turns segments into injector steps and records them.
+    pub fn plan(&mut self, segments: &[Segment]) -> Vec<EditStep> {
+        let mut steps = Vec::new();
+        for segment in segments {
+            match segment {
+                Segment::Text(text) | Segment::Command(CommandAction::Insert { text }) => {
+                    steps.push(EditStep::Type(text.clone()));
+                    self.record(text.clone());
+                }
+                Segment::Command(CommandAction::Key { key, repeat }) => {
+                    steps.push(EditStep::Key {
+                        key: key.clone(),
+                        repeat: *repeat,
+                    });
+                    // Only Return leaves a character behind to erase later.
+                    if key == "Return" {
+                        self.record("\n".repeat(*repeat as usize));
+                    }
+                }
+                Segment::Command(CommandAction::DeleteLast) => {
+                    if let Some(text) = self.typed.pop() {
+                        steps.push(EditStep::Erase(text.clone()));
+                        self.edits.push(Edit::Deleted(text));
+                    }
+                }
+                Segment::Command(CommandAction::Undo) => match self.edits.pop() {
+                    Some(Edit::Typed) => {
+                        if let Some(text) = self.typed.pop() {
+                            steps.push(EditStep::Erase(text));
+                        }
+                    }
+                    Some(Edit::Deleted(text)) => {
+                        steps.push(EditStep::Type(text.clone()));
+                        self.typed.push(text);
+                    }
+                    None => {}
+                },
+                Segment::Command(CommandAction::Escape) => {}
+            }
+        }
+        steps
+    }
+
+    /// Drops the chunks making up `text`, which was erased outside of a plan.
+    pub fn forget(&mut self, text: &str) {
+        let mut rest = text;
+        while !rest.is_empty() {
+            let Some(last) = self.typed.last() else {
+                break;
+            };
+            let Some(before) = rest.strip_suffix(last.as_str()) else {
+                break;
+            };
+            rest = before;
+            self.typed.pop();
+        }
+        self.edits.clear();
+    }
+
+    fn record(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.typed.push(text);
+        self.edits.push(Edit::Typed);
+        if self.typed.len() > HISTORY_LIMIT {
+            self.typed.remove(0);
+        }
+        if self.edits.len() > HISTORY_LIMIT {
+            self.edits.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recognizer() -> CommandRecognizer {
+        CommandRecognizer::from_config(&AppConfig::default())
+    }
+
+    fn text(s: &str) -> Segment {
+        Segment::Text(s.to_string())
+    }
+
+    #[test]
+    fn commands_fill_whole_clauses() {
+        let cases = [
+            (
+                "今日は晴れ。改行。明日は雨。",
+                Some("ja"),
+                vec![
+                    text("今日は晴れ。"),
+                    Segment::Command(key("Return", 1)),
+                    text("明日は雨。"),
+                ],
+            ),
+            (
+                "Hello world, period.",
+                Some("en"),
+                vec![text("Hello world"), Segment::Command(insert("."))],
+            ),
+            (
+                "The trial period ended.",
+                Some("en"),
+                vec![text("The trial period ended.")],
+            ),
+            (
+                "改行コードを直す",
+                Some("ja"),
+                vec![text("改行コードを直す")],
+            ),
+            (
+                "Delete that.",
+                None,
+                vec![Segment::Command(CommandAction::DeleteLast)],
+            ),
+            ("New paragraph.", Some("ja"), vec![text("New paragraph.")]),
+            ("3.5 GB", Some("en"), vec![text("3.5 GB")]),
+        ];
+        for (input, language, expected) in cases {
+            assert_eq!(
+                recognizer().parse(input, language),
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn escape_word_types_the_phrase() {
+        assert_eq!(
+            recognizer().parse("literally new line.", Some("en")),
+            vec![text("new line.")]
+        );
+        assert_eq!(
+            recognizer().parse("そのまま、改行。", Some("ja")),
+            vec![text("改行。")]
+        );
+        assert_eq!(
+            recognizer().parse("literally, period. Done", Some("en")),
+            vec![text("period. Done")]
+        );
+        assert_eq!(
+            recognizer().parse("そのままでいい。", Some("ja")),
+            vec![text("そのままでいい。")]
+        );
+    }
+
+    #[test]
+    fn user_commands_extend_and_override() {
+        let config = AppConfig {
+            voice_commands: BTreeMap::from([(
+                "en".to_string(),
+                vec![
+                    VoiceCommand {
+                        phrase: "Tab key".to_string(),
+                        action: key("Tab", 1),
+                    },
+                    VoiceCommand {
+                        phrase: "period".to_string(),
+                        action: insert("。"),
+                    },
+                ],
+            )]),
+            ..AppConfig::default()
+        };
+        let recognizer = CommandRecognizer::from_config(&config);
+        assert_eq!(
+            recognizer.parse("tab key. period", Some("en")),
+            vec![
+                Segment::Command(key("Tab", 1)),
+                Segment::Command(insert("。"))
+            ]
+        );
+
+        let parsed: VoiceCommand =
+            serde_json::from_str(r#"{"phrase":"署名欄","action":"key","key":"Tab"}"#)
+                .expect("parse");
+        assert_eq!(parsed.action, key("Tab", 1));
+    }
+
+    #[test]
+    fn history_deletes_and_undoes() {
+        let mut history = EditHistory::default();
+        assert_eq!(
+            history.plan(&[text("one"), Segment::Command(key("Return", 1))]),
+            vec![
+                EditStep::Type("one".to_string()),
+                EditStep::Key {
+                    key: "Return".to_string(),
+                    repeat: 1
+                }
+            ]
+        );
+        assert_eq!(
+            history.plan(&[text("two"), Segment::Command(CommandAction::DeleteLast)]),
+            vec![
+                EditStep::Type("two".to_string()),
+                EditStep::Erase("two".to_string())
+            ]
+        );
+        // Undo brings the deletion back, a second undo removes the typing.
+        assert_eq!(
+            history.plan(&[
+                Segment::Command(CommandAction::Undo),
+                Segment::Command(CommandAction::Undo)
+            ]),
+            vec![
+                EditStep::Type("two".to_string()),
+                EditStep::Erase("two".to_string())
+            ]
+        );
+        assert_eq!(
+            history.plan(&[Segment::Command(CommandAction::DeleteLast)]),
+            vec![EditStep::Erase("\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn forget_drops_replaced_chunks() {
+        let mut history = EditHistory::default();
+        history.plan(&[text("a"), Segment::Command(key("Return", 1)), text("b")]);
+        history.forget("\nb");
+        assert_eq!(
+            history.plan(&[Segment::Command(CommandAction::DeleteLast)]),
+            vec![EditStep::Erase("a".to_string())]
+        );
+        assert_eq!(
+            render_segments(&[text("a"), Segment::Command(key("Return", 2)), text("b")]),
+            "a\n\nb"
+        );
+    }
+}
@@ -1,28 +1,121 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
+use super::commands::VoiceCommand;
+use super::inject_backend::BackendKind;
+use super::macros::VoiceMacro;
+use super::pipeline::PipelineStage;
+use super::redact::RedactionPattern;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct AppConfig {
     pub max_record_seconds: u32,
     pub model: ModelSize,
+    /// Spoken language code for whisper, or "auto" to detect it per utterance.
+    pub language: String,
+    /// Default for whisper's translate-to-English task.
+    pub translate: bool,
     pub auto_type: bool,
+    /// Injection backends tried in order when one fails; empty picks a chain
+    /// for the detected session (Wayland or X11).
+    pub injection_backends: Vec<BackendKind>,
+    /// Paste final results through the clipboard instead of typing them;
+    /// `auto` pastes those of at least `paste_min_chars` characters.
+    pub paste_mode: PasteMode,
+    /// Chord that pastes in the focused app, e.g. `ctrl+shift+v` in terminals.
+    pub paste_chord: String,
+    pub paste_min_chars: usize,
     pub text_cleanup: bool,
+    /// Adds 、 and 。 to Japanese transcripts from pauses and sentence endings.
+    pub auto_punctuation: bool,
+    /// Puts each sentence on its own line.
+    pub sentence_newline: bool,
+    pub filler_removal_enabled: bool,
+    /// Spoken and kanji numbers written in digits, per category.
+    pub normalize_numbers: bool,
+    pub normalize_dates: bool,
+    pub normalize_times: bool,
+    pub normalize_percentages: bool,
+    pub normalize_units: bool,
+    /// Spoken editing commands (改行, "delete that", ...) instead of text.
+    pub voice_commands_enabled: bool,
+    /// User additions to the built-in command phrases, keyed by language code.
+    pub voice_commands: BTreeMap<String, Vec<VoiceCommand>>,
+    /// Phrases that press key chords or start commands when spoken on their own.
+    pub macros: Vec<VoiceMacro>,
+    /// Programs a macro may start; anything else is refused.
+    pub macro_allowed_commands: Vec<String>,
+    /// Report what a macro would do instead of doing it.
+    pub macro_dry_run: bool,
+    /// Formats every dictation as code; app profiles and the
+    /// `ToggleRecordingCode` D-Bus method turn it on per session.
+    pub code_mode: bool,
+    /// Settings applied while a given application has focus.
+    pub app_profiles: Vec<AppProfile>,
+    /// Order of the text stages after the hallucination filter; a stage
+    /// missing here never runs, one listed here still obeys its own toggle.
+    pub pipeline_stages: Vec<PipelineStage>,
+    /// User additions to the built-in filler words, keyed by language code.
+    pub filler_words: BTreeMap<String, Vec<String>>,
+    /// Preferences used by `text_cleanup`.
+    pub alnum_width: WidthPreference,
+    pub punctuation_width: WidthPreference,
+    pub japanese_punctuation: JapanesePunctuation,
+    pub cjk_latin_spacing: CjkLatinSpacing,
     pub llm_postprocess_enabled: bool,
     pub llm_provider: String,
     pub llm_api_base_url: String,
+    /// Only held in memory. Never written to config.json; a key read from an
+    /// older config or sent by the settings UI is moved to the keyring.
+    #[serde(skip_serializing)]
     pub llm_api_key: String,
+    /// Where the API key is stored, e.g. `keyring:llm-api-key`.
+    pub llm_api_key_ref: String,
     pub llm_model: String,
+    /// Raw text is typed when the LLM has not answered within this budget;
+    /// when streaming, it is the longest wait between two pieces.
+    pub llm_timeout_ms: u64,
+    /// Default prompt template name from templates.json.
+    pub llm_template: String,
+    /// Type the LLM's answer as it streams in instead of waiting for all of
+    /// it. Only used with `auto_type`.
+    pub llm_streaming: bool,
+    /// Replace emails, phone numbers, tokens and the entries below with
+    /// placeholders before text is sent to the LLM.
+    pub redaction_enabled: bool,
+    /// Names (customers, projects) redacted as written, case ignored.
+    pub redaction_terms: Vec<String>,
+    pub redaction_patterns: Vec<RedactionPattern>,
     pub realtime_enabled: bool,
     pub partial_autotype_mode: PartialAutotypeMode,
+    pub hallucination_filter_enabled: bool,
+    /// User additions to the built-in phantom phrases, keyed by language code.
+    pub phantom_phrases: BTreeMap<String, Vec<String>>,
+    pub no_speech_threshold: f32,
+    pub repetition_threshold: u32,
+    /// Domain terms passed to whisper as part of the initial prompt.
+    pub custom_vocabulary: Vec<String>,
+    pub initial_prompt: String,
+    pub use_previous_context: bool,
+    /// whisper-cli decoding knobs; 0 keeps whisper-cli's own default.
+    pub whisper_threads: u32,
+    pub whisper_beam_size: u32,
+    pub whisper_best_of: u32,
+    pub whisper_no_fallback: bool,
+    pub whisper_audio_ctx: u32,
+    /// Keep the last recording in the private runtime dir for re-transcription.
+    /// Off by default so no audio outlives its session.
+    pub keep_last_recording: bool,
     #[serde(default)]
     pub pill_position: Option<PillPosition>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum ModelSize {
     Small,
@@ -35,6 +128,21 @@ impl Default for ModelSize {
     }
 }
 
+impl ModelSize {
+    pub const ALL: [ModelSize; 2] = [ModelSize::Small, ModelSize::Medium];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Medium => "medium",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.as_str() == value)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PartialAutotypeMode {
@@ -47,6 +155,79 @@ impl Default for PartialAutotypeMode {
     }
 }
 
+/// Half- or full-width form to normalize to; `Keep` leaves text as transcribed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WidthPreference {
+    Half,
+    Full,
+    Keep,
+}
+
+impl Default for WidthPreference {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// Japanese comma/period pair: `Touten` is 、。 and `Comma` is ，．.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JapanesePunctuation {
+    Touten,
+    Comma,
+    Keep,
+}
+
+impl Default for JapanesePunctuation {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// Spacing between Japanese and Latin letters or digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CjkLatinSpacing {
+    None,
+    Space,
+    Keep,
+}
+
+impl Default for CjkLatinSpacing {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PasteMode {
+    Never,
+    Auto,
+    Always,
+}
+
+impl Default for PasteMode {
+    fn default() -> Self {
+        Self::Never
+    }
+}
+
+/// Per-application settings, matched against the focused app id or WM class
+/// (`code`, `Code`, `jetbrains-idea`) without regard to case.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppProfile {
+    pub app: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_mode: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paste_mode: Option<PasteMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paste_chord: Option<String>,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct PillPosition {
@@ -59,33 +240,111 @@ impl Default for AppConfig {
         Self {
             max_record_seconds: 60,
             model: ModelSize::Small,
+            language: "ja".to_string(),
+            translate: false,
             auto_type: true,
+            injection_backends: Vec::new(),
+            paste_mode: PasteMode::Never,
+            paste_chord: "ctrl+v".to_string(),
+            paste_min_chars: 200,
             text_cleanup: true,
+            // Stages that change what gets typed are opt-in, so an existing
+            // config.json keeps typing what it typed before.
+            auto_punctuation: false,
+            sentence_newline: false,
+            filler_removal_enabled: false,
+            normalize_numbers: false,
+            normalize_dates: false,
+            normalize_times: false,
+            normalize_percentages: false,
+            normalize_units: false,
+            voice_commands_enabled: false,
+            voice_commands: BTreeMap::new(),
+            macros: Vec::new(),
+            macro_allowed_commands: Vec::new(),
+            macro_dry_run: false,
+            code_mode: false,
+            app_profiles: Vec::new(),
+            pipeline_stages: PipelineStage::DEFAULT_ORDER.to_vec(),
+            filler_words: BTreeMap::new(),
+            alnum_width: WidthPreference::Keep,
+            punctuation_width: WidthPreference::Keep,
+            japanese_punctuation: JapanesePunctuation::Keep,
+            cjk_latin_spacing: CjkLatinSpacing::Keep,
             llm_postprocess_enabled: false,
             llm_provider: "".to_string(),
             llm_api_base_url: "".to_string(),
             llm_api_key: "".to_string(),
+            llm_api_key_ref: "".to_string(),
             llm_model: "".to_string(),
+            llm_timeout_ms: 5_000,
+            llm_template: "cleanup".to_string(),
+            llm_streaming: true,
+            redaction_enabled: true,
+            redaction_terms: Vec::new(),
+            redaction_patterns: Vec::new(),
             // Recovery default: prioritize stable final transcription first.
             realtime_enabled: false,
             partial_autotype_mode: PartialAutotypeMode::Replace,
+            hallucination_filter_enabled: true,
+            phantom_phrases: BTreeMap::new(),
+            no_speech_threshold: 0.6,
+            repetition_threshold: 4,
+            custom_vocabulary: Vec::new(),
+            initial_prompt: "".to_string(),
+            use_previous_context: false,
+            whisper_threads: 0,
+            whisper_beam_size: 0,
+            whisper_best_of: 0,
+            whisper_no_fallback: false,
+            whisper_audio_ctx: 0,
+            keep_last_recording: false,
             pill_position: None,
         }
     }
 }
 
-fn config_path() -> anyhow::Result<PathBuf> {
+impl AppConfig {
+    /// Applies the profile for the focused `app`, if there is one.
+    pub fn apply_app_profile(&mut self, app: Option<&str>) {
+        let Some(app) = app else {
+            return;
+        };
+        let Some(profile) = self
+            .app_profiles
+            .iter()
+            .find(|p| p.app.trim().eq_ignore_ascii_case(app.trim()))
+        else {
+            return;
+        };
+        if let Some(code_mode) = profile.code_mode {
+            self.code_mode = code_mode;
+        }
+        if let Some(paste_mode) = profile.paste_mode {
+            self.paste_mode = paste_mode;
+        }
+        if let Some(paste_chord) = &profile.paste_chord {
+            self.paste_chord = paste_chord.clone();
+        }
+    }
+}
+
+pub fn config_dir() -> anyhow::Result<PathBuf> {
     if let Ok(dir) = std::env::var("NOTYPE_CONFIG_DIR") {
         let dir = PathBuf::from(dir);
         fs::create_dir_all(&dir)?;
-        return Ok(dir.join("config.json"));
+        return Ok(dir);
     }
 
     let dirs = ProjectDirs::from("dev", "notype", "notype")
         .ok_or_else(|| anyhow::anyhow!("could not resolve config dir"))?;
     let dir = dirs.config_dir();
     fs::create_dir_all(dir)?;
-    Ok(dir.join("config.json"))
+    Ok(dir.to_path_buf())
+}
+
+fn config_path() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("config.json"))
 }
 
 #[cfg(test)]
@@ -127,6 +386,37 @@ mod tests {
         assert!(!cfg.realtime_enabled);
     }
 
+    #[test]
+    fn app_profile_matches_focused_app() {
+        let mut cfg = AppConfig {
+            app_profiles: vec![
+                AppProfile {
+                    app: "Code".to_string(),
+                    code_mode: Some(true),
+                    paste_mode: None,
+                    paste_chord: None,
+                },
+                AppProfile {
+                    app: "kitty".to_string(),
+                    code_mode: None,
+                    paste_mode: Some(PasteMode::Always),
+                    paste_chord: Some("ctrl+shift+v".to_string()),
+                },
+            ],
+            ..AppConfig::default()
+        };
+        cfg.apply_app_profile(Some("firefox"));
+        assert!(!cfg.code_mode);
+        cfg.apply_app_profile(None);
+        assert!(!cfg.code_mode);
+        cfg.apply_app_profile(Some("code"));
+        assert!(cfg.code_mode);
+        assert_eq!(cfg.paste_mode, PasteMode::Never);
+        cfg.apply_app_profile(Some("kitty"));
+        assert_eq!(cfg.paste_mode, PasteMode::Always);
+        assert_eq!(cfg.paste_chord, "ctrl+shift+v");
+    }
+
     #[test]
     fn broken_config_recovers_to_default() {
         let _guard_lock = TEST_ENV_LOCK.lock().expect("lock");
@@ -0,0 +1,389 @@
+use std::path::Path;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::config::config_dir;
+use super::error::AppError;
+
+const DICTIONARY_FILENAME: &str = "dictionary.json";
+pub const DICTIONARY_VERSION: u32 = 1;
+
+/// User replacement rules and snippets, stored as `dictionary.json` next to
+/// the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Dictionary {
+    pub version: u32,
+    pub replacements: Vec<Replacement>,
+    pub snippets: Vec<Snippet>,
+}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Self {
+            version: DICTIONARY_VERSION,
+            replacements: Vec::new(),
+            snippets: Vec::new(),
+        }
+    }
+}
+
+/// `from` is matched literally unless `regex` is set; regex replacements may
+/// refer to groups as `$1` / `${name}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub regex: bool,
+}
+
+/// Expands when the whole utterance is `trigger`. `text` may use `{date}`,
+/// `{time}` and `{clipboard}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub trigger: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnippetVars {
+    pub date: String,
+    pub time: String,
+    pub clipboard: String,
+}
+
+pub struct CompiledDictionary {
+    literals: Vec<(String, String)>,
+    regexes: Vec<(Regex, String)>,
+    snippets: Vec<(String, String)>,
+}
+
+impl CompiledDictionary {
+    /// Fails on the first invalid regex; used when saving.
+    pub fn compile(dictionary: &Dictionary) -> Result<Self, AppError> {
+        Self::build(dictionary, true)
+    }
+
+    /// Skips invalid rules with a warning so one typo never disables the rest.
+    pub fn compile_lenient(dictionary: &Dictionary) -> Self {
+        Self::build(dictionary, false).unwrap_or_else(|_| Self {
+            literals: Vec::new(),
+            regexes: Vec::new(),
+            snippets: Vec::new(),
+        })
+    }
+
+    fn build(dictionary: &Dictionary, strict: bool) -> Result<Self, AppError> {
+        let mut literals = Vec::new();
+        let mut regexes = Vec::new();
+        for rule in &dictionary.replacements {
+            if rule.from.is_empty() {
+                continue;
+            }
+            if !rule.regex {
+                literals.push((rule.from.clone(), rule.to.clone()));
+                continue;
+            }
+            match Regex::new(&rule.from) {
+                Ok(re) => regexes.push((re, rule.to.clone())),
+                Err(err) if strict => {
+                    return Err(AppError::new(
+                        "辞書の正規表現が不正です",
+                        format!("{}: {err}", rule.from),
+                    ))
+                }
+                Err(err) => tracing::warn!("dictionary: skipping regex {}: {err}", rule.from),
+            }
+        }
+
+        let snippets = dictionary
+            .snippets
+            .iter()
+            .map(|s| (normalize_trigger(&s.trigger), s.text.clone()))
+            .filter(|(trigger, _)| !trigger.is_empty())
+            .collect();
+        Ok(Self {
+            literals,
+            regexes,
+            snippets,
+        })
+    }
+
+    /// The snippet body when the whole utterance is one of the triggers.
+    pub fn snippet_for(&self, text: &str) -> Option<&str> {
+        let spoken = normalize_trigger(text);
+        self.snippets
+            .iter()
+            .find(|(trigger, _)| *trigger == spoken)
+            .map(|(_, body)| body.as_str())
+    }
+
+    /// Literal rules first, in a single left-to-right pass where the longest
+    /// match wins (earlier rule on ties) and replaced text is never rescanned.
+    /// Regex rules then run in file order, each on the previous result.
+    pub fn apply(&self, text: &str) -> String {
+        let mut out = self.apply_literals(text);
+        for (re, to) in &self.regexes {
+            out = re.replace_all(&out, to.as_str()).into_owned();
+        }
+        out
+    }
+
+    fn apply_literals(&self, text: &str) -> String {
+        if self.literals.is_empty() {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let best = self
+                .literals
+                .iter()
+                .filter(|(from, _)| rest.starts_with(from.as_str()))
+                .fold(None::<&(String, String)>, |best, rule| match best {
+                    Some(b) if b.0.len() >= rule.0.len() => Some(b),
+                    _ => Some(rule),
+                });
+            match best {
+                Some((from, to)) => {
+                    out.push_str(to);
+                    i += from.len();
+                }
+                None => {
+                    let c = rest.chars().next().expect("non-empty rest");
+                    out.push(c);
+                    i += c.len_utf8();
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Trigger comparison ignores case, surrounding whitespace and trailing
+/// punctuation whisper tends to add.
+pub fn normalize_trigger(text: &str) -> String {
+    text.trim()
+        .trim_end_matches(|c: char| c.is_whitespace() || "。、．，.,!?！？".contains(c))
+        .to_lowercase()
+}
+
+pub fn render_snippet(body: &str, vars: &SnippetVars) -> String {
+    body.replace("{date}", &vars.date)
+        .replace("{time}", &vars.time)
+        .replace("{clipboard}", &vars.clipboard)
+}
+
+/// Collects only the variables the snippet uses; the clipboard is read with
+/// `wl-paste` and left empty when unavailable.
+pub async fn snippet_vars(body: &str) -> SnippetVars {
+    let now = chrono::Local::now();
+    let clipboard = if body.contains("{clipboard}") {
+        read_clipboard().await.unwrap_or_default()
+    } else {
+        String::new()
+    };
+    SnippetVars {
+        date: now.format("%Y-%m-%d").to_string(),
+        time: now.format("%H:%M").to_string(),
+        clipboard,
+    }
+}
+
+async fn read_clipboard() -> Option<String> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(1),
+        Command::new("wl-paste")
+            .arg("--no-newline")
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn dictionary_path() -> Result<std::path::PathBuf, AppError> {
+    Ok(config_dir()
+        .map_err(|e| AppError::new("設定ディレクトリを解決できません", e.to_string()))?
+        .join(DICTIONARY_FILENAME))
+}
+
+pub fn load_dictionary_from(path: &Path) -> Result<Dictionary, AppError> {
+    if !path.exists() {
+        return Ok(Dictionary::default());
+    }
+    let raw = std::fs::read_to_string(path).map_err(|e| dictionary_error(path, e))?;
+    let dictionary: Dictionary =
+        serde_json::from_str(&raw).map_err(|e| dictionary_error(path, e))?;
+    if dictionary.version > DICTIONARY_VERSION {
+        return Err(dictionary_error(
+            path,
+            format!("unsupported version {}", dictionary.version),
+        ));
+    }
+    Ok(dictionary)
+}
+
+pub fn save_dictionary_to(path: &Path, dictionary: &Dictionary) -> Result<(), AppError> {
+    CompiledDictionary::compile(dictionary)?;
+    let dictionary = Dictionary {
+        version: DICTIONARY_VERSION,
+        ..dictionary.clone()
+    };
+    let raw = serde_json::to_string_pretty(&dictionary).map_err(|e| dictionary_error(path, e))?;
+    std::fs::write(path, raw).map_err(|e| dictionary_error(path, e))
+}
+
+pub fn load_dictionary() -> Result<Dictionary, AppError> {
+    load_dictionary_from(&dictionary_path()?)
+}
+
+pub fn save_dictionary(dictionary: &Dictionary) -> Result<(), AppError> {
+    save_dictionary_to(&dictionary_path()?, dictionary)
+}
+
+fn dictionary_error(path: &Path, err: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        "辞書ファイルの読み書きに失敗しました",
+        format!("{}: {err}", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(from: &str, to: &str) -> Replacement {
+        Replacement {
+            from: from.to_string(),
+            to: to.to_string(),
+            regex: false,
+        }
+    }
+
+    fn regex(from: &str, to: &str) -> Replacement {
+        Replacement {
+            regex: true,
+            ..literal(from, to)
+        }
+    }
+
+    fn compiled(replacements: Vec<Replacement>) -> CompiledDictionary {
+        CompiledDictionary::compile(&Dictionary {
+            replacements,
+            ..Dictionary::default()
+        })
+        .expect("compile")
+    }
+
+    #[test]
+    fn longest_literal_wins_over_rule_order() {
+        let dict = compiled(vec![
+            literal("東京", "Tokyo"),
+            literal("東京都", "Tokyo-to"),
+        ]);
+        assert_eq!(dict.apply("東京都と東京"), "Tokyo-toとTokyo");
+    }
+
+    #[test]
+    fn earlier_literal_wins_on_equal_length() {
+        let dict = compiled(vec![literal("ab", "first"), literal("ab", "second")]);
+        assert_eq!(dict.apply("ab"), "first");
+    }
+
+    #[test]
+    fn literal_output_is_not_rescanned() {
+        let dict = compiled(vec![literal("a", "b"), literal("b", "c")]);
+        assert_eq!(dict.apply("ab"), "bc");
+    }
+
+    #[test]
+    fn overlapping_literals_consume_left_to_right() {
+        let dict = compiled(vec![literal("ab", "X"), literal("bc", "Y")]);
+        assert_eq!(dict.apply("abc"), "Xc");
+    }
+
+    #[test]
+    fn regexes_run_after_literals_in_file_order() {
+        let dict = compiled(vec![
+            regex(r"(\d+)時", "${1}:00"),
+            literal("ノータイプ", "notype"),
+            regex(r"notype", "Notype"),
+        ]);
+        assert_eq!(dict.apply("ノータイプは10時から"), "Notypeは10:00から");
+    }
+
+    #[test]
+    fn invalid_regex_is_rejected_strictly_and_skipped_leniently() {
+        let dictionary = Dictionary {
+            replacements: vec![regex("(", "x"), literal("a", "b")],
+            ..Dictionary::default()
+        };
+        assert!(CompiledDictionary::compile(&dictionary).is_err());
+        assert_eq!(
+            CompiledDictionary::compile_lenient(&dictionary).apply("a("),
+            "b("
+        );
+    }
+
+    #[test]
+    fn snippet_matches_whole_utterance_only() {
+        let dict = CompiledDictionary::compile(&Dictionary {
+            snippets: vec![Snippet {
+                trigger: "署名".to_string(),
+                text: "山田太郎 {date}".to_string(),
+            }],
+            ..Dictionary::default()
+        })
+        .expect("compile");
+        assert_eq!(dict.snippet_for(" 署名。"), Some("山田太郎 {date}"));
+        assert_eq!(dict.snippet_for("署名をお願いします"), None);
+    }
+
+    #[test]
+    fn snippet_variables_are_rendered() {
+        let vars = SnippetVars {
+            date: "2026-10-18".to_string(),
+            time: "09:30".to_string(),
+            clipboard: "copied".to_string(),
+        };
+        assert_eq!(
+            render_snippet("{date} {time} {clipboard} {other}", &vars),
+            "2026-10-18 09:30 copied {other}"
+        );
+    }
+
+    #[test]
+    fn file_roundtrip_and_version_check() {
+        let dir = std::env::temp_dir().join(format!("notype-dict-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        let path = dir.join(DICTIONARY_FILENAME);
+
+        assert_eq!(
+            load_dictionary_from(&path).expect("missing"),
+            Dictionary::default()
+        );
+        let dictionary = Dictionary {
+            replacements: vec![literal("a", "b")],
+            ..Dictionary::default()
+        };
+        save_dictionary_to(&path, &dictionary).expect("save");
+        assert_eq!(load_dictionary_from(&path).expect("load"), dictionary);
+
+        std::fs::write(&path, r#"{"version": 2}"#).expect("write");
+        assert!(load_dictionary_from(&path).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,689 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::FileExt;
+
+use tokio::io::{AsyncWriteExt, Interest};
+use tokio::net::UnixStream;
+
+use super::error::AppError;
+use super::xkb::Keymap;
+
+const HEADER_LEN: usize = 16;
+const HANDSHAKE: u64 = 0;
+const CONTEXT_SENDER: u32 = 2;
+const KEYBOARD_INTERFACE: &str = "ei_keyboard";
+const KEYMAP_XKB: u32 = 1;
+/// Far larger than any real keymap; guards against a bogus size.
+const MAX_KEYMAP_LEN: u32 = 4 << 20;
+
+/// Interfaces and versions notype speaks; everything else is ignored.
+const INTERFACES: &[(&str, u32)] = &[
+    ("ei_connection", 1),
+    ("ei_callback", 1),
+    ("ei_pingpong", 1),
+    ("ei_seat", 1),
+    ("ei_device", 1),
+    (KEYBOARD_INTERFACE, 1),
+];
+
+/// Request opcodes (client to server), per interface.
+mod request {
+    pub const HANDSHAKE_VERSION: u32 = 0;
+    pub const HANDSHAKE_FINISH: u32 = 1;
+    pub const HANDSHAKE_CONTEXT_TYPE: u32 = 2;
+    pub const HANDSHAKE_NAME: u32 = 3;
+    pub const HANDSHAKE_INTERFACE_VERSION: u32 = 4;
+    pub const PINGPONG_DONE: u32 = 0;
+    pub const SEAT_BIND: u32 = 1;
+    pub const DEVICE_START_EMULATING: u32 = 1;
+    pub const DEVICE_STOP_EMULATING: u32 = 2;
+    pub const DEVICE_FRAME: u32 = 3;
+    pub const KEYBOARD_KEY: u32 = 1;
+}
+
+/// Event opcodes (server to client), per interface.
+mod event {
+    pub const HANDSHAKE_VERSION: u32 = 0;
+    pub const HANDSHAKE_CONNECTION: u32 = 2;
+    pub const CONNECTION_DISCONNECTED: u32 = 0;
+    pub const CONNECTION_SEAT: u32 = 1;
+    pub const CONNECTION_PING: u32 = 3;
+    pub const SEAT_CAPABILITY: u32 = 2;
+    pub const SEAT_DONE: u32 = 3;
+    pub const SEAT_DEVICE: u32 = 4;
+    pub const DEVICE_DESTROYED: u32 = 0;
+    pub const DEVICE_INTERFACE: u32 = 5;
+    pub const DEVICE_RESUMED: u32 = 7;
+    pub const DEVICE_PAUSED: u32 = 8;
+    pub const KEYBOARD_KEYMAP: u32 = 1;
+    pub const KEYBOARD_MODIFIERS: u32 = 3;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Object {
+    Handshake,
+    Connection,
+    Seat,
+    Device,
+    Keyboard,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arg<'a> {
+    U32(u32),
+    U64(u64),
+    Str(&'a str),
+}
+
+/// One wire message: object id, length and opcode, then the arguments in
+/// native byte order. Strings carry their length including the NUL and are
+/// padded to four bytes.
+fn encode(object: u64, opcode: u32, args: &[Arg<'_>]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for arg in args {
+        match arg {
+            Arg::U32(value) => body.extend(value.to_ne_bytes()),
+            Arg::U64(value) => body.extend(value.to_ne_bytes()),
+            Arg::Str(value) => {
+                body.extend((value.len() as u32 + 1).to_ne_bytes());
+                body.extend(value.as_bytes());
+                body.push(0);
+                body.resize(body.len().next_multiple_of(4), 0);
+            }
+        }
+    }
+    let mut message = Vec::with_capacity(HEADER_LEN + body.len());
+    message.extend(object.to_ne_bytes());
+    message.extend(((HEADER_LEN + body.len()) as u32).to_ne_bytes());
+    message.extend(opcode.to_ne_bytes());
+    message.extend(body);
+    message
+}
+
+#[derive(Debug)]
+struct Message {
+    object: u64,
+    opcode: u32,
+    body: Vec<u8>,
+    pos: usize,
+}
+
+impl Message {
+    /// Splits the first complete message off `buffer`.
+    fn take(buffer: &mut Vec<u8>) -> Result<Option<Self>, AppError> {
+        if buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_ne_bytes(buffer[8..12].try_into().expect("4 bytes")) as usize;
+        if len < HEADER_LEN || !len.is_multiple_of(4) {
+            return Err(protocol_error(format!("bad message length {len}")));
+        }
+        if buffer.len() < len {
+            return Ok(None);
+        }
+        let message: Vec<u8> = buffer.drain(..len).collect();
+        Ok(Some(Self {
+            object: u64::from_ne_bytes(message[..8].try_into().expect("8 bytes")),
+            opcode: u32::from_ne_bytes(message[12..16].try_into().expect("4 bytes")),
+            body: message[HEADER_LEN..].to_vec(),
+            pos: 0,
+        }))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], AppError> {
+        let end = self.pos + len;
+        let bytes = self
+            .body
+            .get(self.pos..end)
+            .ok_or_else(|| protocol_error("truncated message"))?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, AppError> {
+        Ok(u32::from_ne_bytes(
+            self.bytes(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64, AppError> {
+        Ok(u64::from_ne_bytes(
+            self.bytes(8)?.try_into().expect("8 bytes"),
+        ))
+    }
+
+    fn string(&mut self) -> Result<String, AppError> {
+        let len = self.u32()? as usize;
+        if len == 0 {
+            return Ok(String::new());
+        }
+        let padded = len.next_multiple_of(4);
+        let bytes = self.bytes(padded)?;
+        Ok(String::from_utf8_lossy(&bytes[..len - 1]).to_string())
+    }
+}
+
+/// Keyboard emulation over a libei socket, e.g. from the RemoteDesktop
+/// portal's `ConnectToEIS`. Keys are evdev key codes, so what they produce
+/// depends on the compositor's keymap, which the server hands over with the
+/// keyboard.
+pub struct EiKeyboard {
+    stream: UnixStream,
+    inbox: Vec<u8>,
+    /// Descriptors passed along with the bytes in `inbox`, in order.
+    fds: VecDeque<OwnedFd>,
+    keymap: Option<Keymap>,
+    objects: HashMap<u64, Object>,
+    /// Device id to the id of its keyboard interface.
+    keyboards: HashMap<u64, u64>,
+    keyboard_mask: u64,
+    active: Option<u64>,
+    serial: u32,
+    sequence: u32,
+}
+
+impl EiKeyboard {
+    /// Runs the handshake and waits until the server resumes a keyboard.
+    pub async fn connect(stream: UnixStream) -> Result<Self, AppError> {
+        let mut client = Self {
+            stream,
+            inbox: Vec::new(),
+            fds: VecDeque::new(),
+            keymap: None,
+            objects: HashMap::from([(HANDSHAKE, Object::Handshake)]),
+            keyboards: HashMap::new(),
+            keyboard_mask: 0,
+            active: None,
+            serial: 0,
+            sequence: 0,
+        };
+        while client.active.is_none() {
+            let message = client.next().await?;
+            client.handle(message).await?;
+        }
+        Ok(client)
+    }
+
+    /// The layout of the active keyboard, if the server sent an XKB keymap.
+    pub fn keymap(&self) -> Option<&Keymap> {
+        self.keymap.as_ref()
+    }
+
+    /// Sends `(key, pressed)` events, one frame each, as a single emulation
+    /// sequence.
+    pub async fn send_keys(&mut self, keys: &[(u32, bool)]) -> Result<(), AppError> {
+        self.drain().await?;
+        let device = self.active.ok_or_else(|| {
+            AppError::new(
+                "入力ポータルのキーボードが一時停止しています",
+                "ei: no resumed keyboard device",
+            )
+        })?;
+        let keyboard = self.keyboards[&device];
+        self.sequence += 1;
+        let serial = self.serial;
+
+        let mut out = encode(
+            device,
+            request::DEVICE_START_EMULATING,
+            &[Arg::U32(serial), Arg::U32(self.sequence)],
+        );
+        for (key, pressed) in keys {
+            out.extend(encode(
+                keyboard,
+                request::KEYBOARD_KEY,
+                &[Arg::U32(*key), Arg::U32(u32::from(*pressed))],
+            ));
+            out.extend(encode(
+                device,
+                request::DEVICE_FRAME,
+                &[Arg::U32(serial), Arg::U64(monotonic_us())],
+            ));
+        }
+        out.extend(encode(
+            device,
+            request::DEVICE_STOP_EMULATING,
+            &[Arg::U32(serial)],
+        ));
+        self.write(&out).await
+    }
+
+    async fn handle(&mut self, mut message: Message) -> Result<(), AppError> {
+        let Some(object) = self.objects.get(&message.object).copied() else {
+            // Callbacks and interfaces we did not ask for.
+            return Ok(());
+        };
+        match (object, message.opcode) {
+            (Object::Handshake, event::HANDSHAKE_VERSION) => self.handshake().await?,
+            (Object::Handshake, event::HANDSHAKE_CONNECTION) => {
+                self.serial = message.u32()?;
+                self.objects.insert(message.u64()?, Object::Connection);
+            }
+            (Object::Connection, event::CONNECTION_DISCONNECTED) => {
+                let _serial = message.u32()?;
+                let reason = message.u32()?;
+                let explanation = message.string()?;
+                return Err(protocol_error(format!(
+                    "disconnected (reason {reason}): {explanation}"
+                )));
+            }
+            (Object::Connection, event::CONNECTION_SEAT) => {
+                self.objects.insert(message.u64()?, Object::Seat);
+            }
+            (Object::Connection, event::CONNECTION_PING) => {
+                let ping = message.u64()?;
+                self.write(&encode(ping, request::PINGPONG_DONE, &[Arg::U64(0)]))
+                    .await?;
+            }
+            (Object::Seat, event::SEAT_CAPABILITY) => {
+                let mask = message.u64()?;
+                if message.string()? == KEYBOARD_INTERFACE {
+                    self.keyboard_mask = mask;
+                }
+            }
+            (Object::Seat, event::SEAT_DONE) => {
+                if self.keyboard_mask == 0 {
+                    return Err(protocol_error("seat has no keyboard capability"));
+                }
+                let bind = encode(
+                    message.object,
+                    request::SEAT_BIND,
+                    &[Arg::U64(self.keyboard_mask)],
+                );
+                self.write(&bind).await?;
+            }
+            (Object::Seat, event::SEAT_DEVICE) => {
+                self.objects.insert(message.u64()?, Object::Device);
+            }
+            (Object::Device, event::DEVICE_INTERFACE) => {
+                let id = message.u64()?;
+                if message.string()? == KEYBOARD_INTERFACE {
+                    self.objects.insert(id, Object::Keyboard);
+                    self.keyboards.insert(message.object, id);
+                }
+            }
+            (Object::Device, event::DEVICE_RESUMED) => {
+                self.serial = message.u32()?;
+                if self.keyboards.contains_key(&message.object) {
+                    self.active = Some(message.object);
+                }
+            }
+            (Object::Device, event::DEVICE_PAUSED | event::DEVICE_DESTROYED) => {
+                self.serial = message.u32()?;
+                if self.active == Some(message.object) {
+                    self.active = None;
+                }
+            }
+            (Object::Keyboard, event::KEYBOARD_KEYMAP) => {
+                let keymap_type = message.u32()?;
+                let size = message.u32()?;
+                let fd = self
+                    .fds
+                    .pop_front()
+                    .ok_or_else(|| protocol_error("keymap without a file descriptor"))?;
+                // Anything but a readable XKB keymap leaves no layout, so
+                // text is refused rather than typed as if it were US.
+                self.keymap = if keymap_type == KEYMAP_XKB {
+                    Some(read_keymap(fd, size)?).filter(|keymap| !keymap.is_empty())
+                } else {
+                    None
+                };
+            }
+            (Object::Keyboard, event::KEYBOARD_MODIFIERS) => {
+                self.serial = message.u32()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn handshake(&mut self) -> Result<(), AppError> {
+        let mut out = encode(HANDSHAKE, request::HANDSHAKE_VERSION, &[Arg::U32(1)]);
+        out.extend(encode(
+            HANDSHAKE,
+            request::HANDSHAKE_CONTEXT_TYPE,
+            &[Arg::U32(CONTEXT_SENDER)],
+        ));
+        out.extend(encode(
+            HANDSHAKE,
+            request::HANDSHAKE_NAME,
+            &[Arg::Str("notype")],
+        ));
+        for (name, version) in INTERFACES {
+            out.extend(encode(
+                HANDSHAKE,
+                request::HANDSHAKE_INTERFACE_VERSION,
+                &[Arg::Str(name), Arg::U32(*version)],
+            ));
+        }
+        out.extend(encode(HANDSHAKE, request::HANDSHAKE_FINISH, &[]));
+        self.write(&out).await
+    }
+
+    async fn next(&mut self) -> Result<Message, AppError> {
+        loop {
+            if let Some(message) = Message::take(&mut self.inbox)? {
+                return Ok(message);
+            }
+            self.stream
+                .readable()
+                .await
+                .map_err(|e| protocol_error(e.to_string()))?;
+            self.receive()?;
+        }
+    }
+
+    /// Handles whatever arrived since the last call (pings, pauses) without
+    /// waiting for more.
+    async fn drain(&mut self) -> Result<(), AppError> {
+        while self.receive()? {}
+        while let Some(message) = Message::take(&mut self.inbox)? {
+            self.handle(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Reads what is ready into `inbox` and `fds`; false if nothing was.
+    fn receive(&mut self) -> Result<bool, AppError> {
+        let mut chunk = [0u8; 4096];
+        let fd = self.stream.as_raw_fd();
+        let fds = &mut self.fds;
+        match self
+            .stream
+            .try_io(Interest::READABLE, || recv_with_fds(fd, &mut chunk, fds))
+        {
+            Ok(0) => Err(protocol_error("connection closed")),
+            Ok(read) => {
+                self.inbox.extend_from_slice(&chunk[..read]);
+                Ok(true)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(protocol_error(err.to_string())),
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> Result<(), AppError> {
+        self.stream
+            .write_all(bytes)
+            .await
+            .map_err(|e| protocol_error(e.to_string()))
+    }
+}
+
+/// Maximum descriptors accepted with one read; libei sends one at a time.
+const MAX_FDS: usize = 4;
+
+/// `recvmsg` keeping any `SCM_RIGHTS` descriptors, which a plain read would
+/// close.
+fn recv_with_fds(
+    socket: RawFd,
+    buffer: &mut [u8],
+    fds: &mut VecDeque<OwnedFd>,
+) -> std::io::Result<usize> {
+    // SAFETY: CMSG_SPACE only computes a size.
+    let space = unsafe { libc::CMSG_SPACE((MAX_FDS * std::mem::size_of::<RawFd>()) as u32) };
+    let mut control = vec![0u8; space as usize];
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr().cast(),
+        iov_len: buffer.len(),
+    };
+    // SAFETY: an all-zero msghdr is valid; the pointers set below outlive
+    // the call.
+    let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+    header.msg_iov = &mut iov;
+    header.msg_iovlen = 1;
+    header.msg_control = control.as_mut_ptr().cast();
+    header.msg_controllen = control.len() as _;
+    // SAFETY: `header` describes valid buffers for the whole call.
+    let read = unsafe { libc::recvmsg(socket, &mut header, libc::MSG_CMSG_CLOEXEC) };
+    if read < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: the kernel filled `control` and set `msg_controllen`; the
+    // CMSG macros stay within it.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+                let len = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for i in 0..len / std::mem::size_of::<RawFd>() {
+                    fds.push_back(OwnedFd::from_raw_fd(data.add(i).read_unaligned()));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+        }
+    }
+    Ok(read as usize)
+}
+
+/// Reads an XKB keymap of `size` bytes from the start of `fd`, usually a
+/// memfd the server filled, so its offset may be anywhere.
+fn read_keymap(fd: OwnedFd, size: u32) -> Result<Keymap, AppError> {
+    if size > MAX_KEYMAP_LEN {
+        return Err(protocol_error(format!("keymap of {size} bytes")));
+    }
+    let mut text = vec![0u8; size as usize];
+    File::from(fd)
+        .read_exact_at(&mut text, 0)
+        .map_err(|e| protocol_error(format!("keymap: {e}")))?;
+    let text = String::from_utf8_lossy(&text);
+    Ok(Keymap::parse(text.trim_end_matches('\0')))
+}
+
+/// `CLOCK_MONOTONIC` in microseconds, the clock libei timestamps use.
+fn monotonic_us() -> u64 {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid, writable timespec.
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000 + now.tv_nsec as u64 / 1_000
+}
+
+fn protocol_error(detail: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        "入力ポータルとの通信に失敗しました",
+        format!("ei: {detail}"),
+    )
+}
+
+/// A scripted EIS server offering one keyboard with the JIS test keymap;
+/// returns the keys it got.
+#[cfg(test)]
+pub(crate) async fn mock_server(mut stream: UnixStream) -> Vec<(u32, bool)> {
+    const CONNECTION: u64 = 0xff00_0000_0000_0000;
+    const SEAT: u64 = CONNECTION + 1;
+    const DEVICE: u64 = CONNECTION + 2;
+    const KEYBOARD: u64 = CONNECTION + 3;
+    const PING: u64 = CONNECTION + 4;
+    const KEYBOARD_MASK: u64 = 4;
+
+    let mut inbox = Vec::new();
+    let mut out = encode(HANDSHAKE, event::HANDSHAKE_VERSION, &[Arg::U32(1)]);
+    stream.write_all(&out).await.expect("write");
+    let mut context = None;
+    loop {
+        let mut message = read_message(&mut stream, &mut inbox)
+            .await
+            .expect("handshake");
+        match message.opcode {
+            request::HANDSHAKE_CONTEXT_TYPE => context = Some(message.u32().expect("context")),
+            request::HANDSHAKE_FINISH => break,
+            _ => {}
+        }
+    }
+    assert_eq!(context, Some(CONTEXT_SENDER));
+
+    out = encode(
+        HANDSHAKE,
+        event::HANDSHAKE_CONNECTION,
+        &[Arg::U32(1), Arg::U64(CONNECTION), Arg::U32(1)],
+    );
+    out.extend(encode(
+        CONNECTION,
+        event::CONNECTION_SEAT,
+        &[Arg::U64(SEAT), Arg::U32(1)],
+    ));
+    out.extend(encode(
+        SEAT,
+        event::SEAT_CAPABILITY,
+        &[Arg::U64(2), Arg::Str("ei_pointer")],
+    ));
+    out.extend(encode(
+        SEAT,
+        event::SEAT_CAPABILITY,
+        &[Arg::U64(KEYBOARD_MASK), Arg::Str(KEYBOARD_INTERFACE)],
+    ));
+    out.extend(encode(SEAT, event::SEAT_DONE, &[]));
+    stream.write_all(&out).await.expect("write");
+
+    let mut bind = read_message(&mut stream, &mut inbox).await.expect("bind");
+    assert_eq!((bind.object, bind.opcode), (SEAT, request::SEAT_BIND));
+    assert_eq!(bind.u64().expect("mask"), KEYBOARD_MASK);
+
+    out = encode(SEAT, event::SEAT_DEVICE, &[Arg::U64(DEVICE), Arg::U32(1)]);
+    out.extend(encode(
+        DEVICE,
+        event::DEVICE_INTERFACE,
+        &[
+            Arg::U64(KEYBOARD),
+            Arg::Str(KEYBOARD_INTERFACE),
+            Arg::U32(1),
+        ],
+    ));
+    stream.write_all(&out).await.expect("write");
+    send_keymap(&stream, KEYBOARD, super::xkb::JIS_SAMPLE).await;
+
+    out = encode(
+        CONNECTION,
+        event::CONNECTION_PING,
+        &[Arg::U64(PING), Arg::U32(1)],
+    );
+    out.extend(encode(DEVICE, event::DEVICE_RESUMED, &[Arg::U32(7)]));
+    stream.write_all(&out).await.expect("write");
+
+    let mut keys = Vec::new();
+    let mut ponged = false;
+    let mut emulating = false;
+    while let Some(mut message) = read_message(&mut stream, &mut inbox).await {
+        match (message.object, message.opcode) {
+            (PING, request::PINGPONG_DONE) => ponged = true,
+            (DEVICE, request::DEVICE_START_EMULATING) => {
+                assert_eq!(message.u32().expect("serial"), 7);
+                emulating = true;
+            }
+            (DEVICE, request::DEVICE_STOP_EMULATING) => emulating = false,
+            (KEYBOARD, request::KEYBOARD_KEY) => {
+                assert!(emulating, "key sent outside an emulation sequence");
+                let key = message.u32().expect("key");
+                keys.push((key, message.u32().expect("state") == 1));
+            }
+            _ => {}
+        }
+    }
+    assert!(ponged, "ping was not answered");
+    keys
+}
+
+/// Sends `ei_keyboard.keymap` with the keymap in a memfd, left at its end
+/// the way a server that just wrote it would.
+#[cfg(test)]
+async fn send_keymap(stream: &UnixStream, keyboard: u64, keymap: &str) {
+    // SAFETY: the name is NUL-terminated.
+    let memfd = unsafe { libc::memfd_create(c"keymap".as_ptr(), libc::MFD_CLOEXEC) };
+    assert!(memfd >= 0, "memfd_create");
+    // SAFETY: memfd_create returned a new descriptor we own.
+    let mut file = unsafe { File::from_raw_fd(memfd) };
+    std::io::Write::write_all(&mut file, keymap.as_bytes()).expect("keymap");
+    let size = keymap.len() as u32;
+    let message = encode(
+        keyboard,
+        event::KEYBOARD_KEYMAP,
+        &[Arg::U32(KEYMAP_XKB), Arg::U32(size)],
+    );
+
+    stream.writable().await.expect("writable");
+    let mut iov = libc::iovec {
+        iov_base: message.as_ptr() as *mut _,
+        iov_len: message.len(),
+    };
+    // SAFETY: CMSG_SPACE only computes a size.
+    let space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) };
+    let mut control = vec![0u8; space as usize];
+    // SAFETY: an all-zero msghdr is valid; the buffers outlive the call and
+    // the control buffer has room for one descriptor.
+    let sent = unsafe {
+        let mut header: libc::msghdr = std::mem::zeroed();
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr().cast();
+        header.msg_controllen = control.len() as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&header);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+        libc::CMSG_DATA(cmsg)
+            .cast::<RawFd>()
+            .write_unaligned(file.as_raw_fd());
+        libc::sendmsg(stream.as_raw_fd(), &header, 0)
+    };
+    assert_eq!(sent, message.len() as isize, "sendmsg");
+}
+
+#[cfg(test)]
+async fn read_message(stream: &mut UnixStream, inbox: &mut Vec<u8>) -> Option<Message> {
+    use tokio::io::AsyncReadExt;
+
+    loop {
+        if let Some(message) = Message::take(inbox).expect("message") {
+            return Some(message);
+        }
+        let mut chunk = [0u8; 4096];
+        match stream.read(&mut chunk).await.expect("read") {
+            0 => return None,
+            read => inbox.extend_from_slice(&chunk[..read]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_nul_terminated_and_padded() {
+        let message = encode(3, 4, &[Arg::Str("ei_seat"), Arg::U32(1)]);
+        assert_eq!(message.len(), HEADER_LEN + 4 + 8 + 4);
+        assert_eq!(&message[HEADER_LEN + 4..HEADER_LEN + 12], b"ei_seat\0");
+
+        let mut buffer = message.clone();
+        buffer.extend(&message[..5]);
+        let mut decoded = Message::take(&mut buffer)
+            .expect("valid")
+            .expect("complete");
+        assert_eq!((decoded.object, decoded.opcode), (3, 4));
+        assert_eq!(decoded.string().expect("string"), "ei_seat");
+        assert_eq!(decoded.u32().expect("u32"), 1);
+        assert_eq!(buffer.len(), 5);
+        assert!(Message::take(&mut buffer).expect("valid").is_none());
+    }
+
+    #[tokio::test]
+    async fn keys_reach_the_eis_server() {
+        let (server, client) = UnixStream::pair().expect("socket pair");
+        let server = tokio::spawn(mock_server(server));
+
+        let mut keyboard = EiKeyboard::connect(client).await.expect("connect");
+        let keymap = keyboard.keymap().expect("keymap");
+        assert_eq!(keymap.key_for('@'), Some((26, false)));
+        keyboard
+            .send_keys(&[(30, true), (30, false)])
+            .await
+            .expect("keys");
+        drop(keyboard);
+        assert_eq!(server.await.expect("server"), [(30, true), (30, false)]);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use super::config::AppConfig;
+use super::normalize::is_cjk_punct;
+
+/// Hesitation sounds removed by default. Japanese entries that are also real
+/// words (あの, その, まあ) are only removed when followed by a pause mark.
+const BUILTIN_FILLERS: &[(&str, &[&str])] = &[
+    (
+        "ja",
+        &[
+            "えーっと",
+            "えーと",
+            "えっと",
+            "ええと",
+            "えー",
+            "あのー",
+            "あの",
+            "そのー",
+            "その",
+            "うーん",
+            "うーんと",
+            "んー",
+            "まあ",
+        ],
+    ),
+    (
+        "en",
+        &["um", "umm", "uh", "uhh", "uh-huh", "er", "erm", "hmm"],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillerOutcome {
+    pub text: String,
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FillerRemover {
+    fillers: BTreeMap<String, Vec<String>>,
+}
+
+impl FillerRemover {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut fillers: BTreeMap<String, Vec<String>> = BUILTIN_FILLERS
+            .iter()
+            .map(|(lang, words)| {
+                (
+                    lang.to_string(),
+                    words.iter().map(|w| w.to_string()).collect(),
+                )
+            })
+            .collect();
+        for (lang, words) in &config.filler_words {
+            fillers
+                .entry(lang.clone())
+                .or_default()
+                .extend(words.iter().map(|w| w.trim().to_string()));
+        }
+        for words in fillers.values_mut() {
+            words.retain(|w| !w.is_empty());
+        }
+        Self { fillers }
+    }
+
+    /// Removes fillers that stand on their own: at the start of the text or
+    /// after a space or punctuation, and not followed by more of a word.
+    /// Drawn-out Japanese fillers are removed anywhere. Without a known
+    /// language every list is used.
+    pub fn apply(&self, text: &str, language: Option<&str>) -> FillerOutcome {
+        let mut fillers: Vec<&str> = self
+            .fillers
+            .iter()
+            .filter(|(lang, _)| language.is_none_or(|l| l == lang.as_str()))
+            .flat_map(|(_, words)| words.iter().map(String::as_str))
+            .collect();
+        if fillers.is_empty() {
+            return FillerOutcome {
+                text: text.to_string(),
+                removed: 0,
+            };
+        }
+        // Longest first so "えーと" is not cut down to "えー" + "と".
+        fillers.sort_by_key(|f| std::cmp::Reverse(f.chars().count()));
+
+        let mut out = String::with_capacity(text.len());
+        let mut removed = 0;
+        let mut rest = text;
+        let mut at_boundary = true;
+        let mut capitalize_next = false;
+        while let Some(c) = rest.chars().next() {
+            if let Some(len) = fillers
+                .iter()
+                .find_map(|f| match_filler(rest, f, at_boundary))
+            {
+                removed += 1;
+                capitalize_next |=
+                    rest.starts_with(|c: char| c.is_ascii_uppercase()) && ends_sentence(&out);
+                rest = skip_pause(&rest[len..], &out);
+                at_boundary = true;
+                continue;
+            }
+            if capitalize_next && c.is_alphabetic() {
+                out.extend(c.to_uppercase());
+                capitalize_next = false;
+            } else {
+                out.push(c);
+            }
+            at_boundary = is_delimiter(c);
+            rest = &rest[c.len_utf8()..];
+        }
+
+        if removed == 0 {
+            return FillerOutcome {
+                text: text.to_string(),
+                removed,
+            };
+        }
+        FillerOutcome {
+            text: tidy(&out),
+            removed,
+        }
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c.is_ascii_punctuation() || is_cjk_punct(c)
+}
+
+fn is_hesitation_mark(c: char) -> bool {
+    matches!(c, 'ー' | '～' | '〜')
+}
+
+/// Byte length of `filler` (plus any drawn-out ー) at the start of `text`,
+/// when it is not part of a longer word.
+fn match_filler(text: &str, filler: &str, at_boundary: bool) -> Option<usize> {
+    let head = text.get(..filler.len())?;
+    let ascii = filler.is_ascii();
+    if !(head == filler || (ascii && head.eq_ignore_ascii_case(filler))) {
+        return None;
+    }
+    let drawn_out = text[filler.len()..]
+        .chars()
+        .take_while(|c| is_hesitation_mark(*c))
+        .map(char::len_utf8)
+        .sum::<usize>();
+    let len = filler.len() + drawn_out;
+    let next = text[len..].chars().next();
+    let delimited = next.is_none_or(is_delimiter);
+    // A drawn-out Japanese filler (えー, あのー, えっと) cannot be a real word,
+    // so it may run straight into the next one; あの資料 stays as written.
+    let hesitant = !ascii && (drawn_out > 0 || filler.contains(['ー', 'っ']));
+    if !at_boundary && !hesitant {
+        return None;
+    }
+    let word_follows = next.is_some_and(|c| c.is_alphanumeric() && (ascii || !hesitant));
+    (delimited || (hesitant && !word_follows)).then_some(len)
+}
+
+fn ends_sentence(out: &str) -> bool {
+    out.trim_end()
+        .chars()
+        .last()
+        .is_none_or(|c| matches!(c, '.' | '!' | '?' | '。' | '！' | '？'))
+}
+
+/// Drops the comma or pause after a removed filler, and its full stop when
+/// nothing precedes it in the sentence.
+fn skip_pause<'a>(rest: &'a str, out: &str) -> &'a str {
+    let rest = rest.trim_start_matches([',', '、', '，', '…']);
+    let rest = if ends_sentence(out) {
+        rest.trim_start_matches(['.', '。', '．'])
+    } else {
+        rest
+    };
+    rest.trim_start_matches(' ')
+}
+
+/// Collapses the spaces left behind and drops a space or comma stranded
+/// before closing punctuation.
+fn tidy(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == ' ' && out.ends_with(' ') {
+            continue;
+        }
+        if matches!(c, ',' | '.' | '!' | '?' | ';' | ':') {
+            while out.ends_with(' ') {
+                out.pop();
+            }
+            if c != ',' && out.ends_with(',') {
+                out.pop();
+            }
+        }
+        out.push(c);
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remover() -> FillerRemover {
+        FillerRemover::from_config(&AppConfig::default())
+    }
+
+    #[test]
+    fn japanese_corpus() {
+        let cases = [
+            ("えーと、今日は晴れです。", "今日は晴れです。", 1),
+            (
+                "えー。あのー、資料を見てください。",
+                "資料を見てください。",
+                2,
+            ),
+            ("それで、あの、資料を", "それで、資料を", 1),
+            ("えっと資料を", "資料を", 1),
+            ("あのーー、はい", "はい", 1),
+            ("あの人は来ます", "あの人は来ます", 0),
+            ("その本です", "その本です", 0),
+            ("まあまあです", "まあまあです", 0),
+            ("ケーキを食べたい", "ケーキを食べたい", 0),
+            ("今日は、まあ、晴れ", "今日は、晴れ", 1),
+            ("今日はえーと晴れ", "今日は晴れ", 1),
+            ("うその、話", "うその、話", 0),
+        ];
+        for (input, expected, removed) in cases {
+            let outcome = remover().apply(input, Some("ja"));
+            assert_eq!(outcome.text, expected, "input: {input:?}");
+            assert_eq!(outcome.removed, removed, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn english_corpus() {
+        let cases = [
+            ("Um, I think so.", "I think so.", 1),
+            ("So, uh, we should go", "So, we should go", 1),
+            ("I was, um.", "I was.", 1),
+            ("Umbrella and humming", "Umbrella and humming", 0),
+            ("The erm output", "The output", 1),
+            ("Done. Uh, next item", "Done. Next item", 1),
+            ("UH uh hmm", "", 3),
+        ];
+        for (input, expected, removed) in cases {
+            let outcome = remover().apply(input, Some("en"));
+            assert_eq!(outcome.text, expected, "input: {input:?}");
+            assert_eq!(outcome.removed, removed, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn user_words_extend_the_language_lists() {
+        let config = AppConfig {
+            filler_words: BTreeMap::from([("en".to_string(), vec!["like".to_string()])]),
+            ..AppConfig::default()
+        };
+        let remover = FillerRemover::from_config(&config);
+        assert_eq!(
+            remover.apply("It was, like, fine", Some("en")).text,
+            "It was, fine"
+        );
+        assert_eq!(remover.apply("I likely agree", Some("en")).removed, 0);
+        // Lists for other languages are not applied.
+        assert_eq!(remover.apply("like えー", Some("ja")).text, "like");
+        assert_eq!(remover.apply("um えーと", None).removed, 2);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+
+use super::config::AppConfig;
+use super::normalize::{is_cjk, is_cjk_punct};
+use super::stt::{Transcription, WhisperSegment};
+
+/// Phrases whisper is known to produce from silence or background noise.
+const BUILTIN_PHANTOM_PHRASES: &[(&str, &[&str])] = &[
+    (
+        "ja",
+        &[
+            "ご視聴ありがとうございました",
+            "ご清聴ありがとうございました",
+            "最後までご視聴いただきありがとうございます",
+            "チャンネル登録よろしくお願いします",
+            "チャンネル登録お願いします",
+            "次回もお楽しみに",
+        ],
+    ),
+    (
+        "en",
+        &[
+            "Thank you for watching",
+            "Thanks for watching",
+            "Please subscribe",
+            "Like and subscribe",
+            "Subtitles by the Amara.org community",
+        ],
+    ),
+];
+
+/// Segments above this mean token probability survive a high no-speech score,
+/// mirroring whisper's own logprob guard.
+const CONFIDENT_TOKEN_PROB: f32 = 0.5;
+const MAX_NGRAM: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterReason {
+    PhantomPhrase(String),
+    RepetitionLoop { ngram: String, count: usize },
+    NoSpeech { probability: f32 },
+}
+
+impl FilterReason {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::PhantomPhrase(phrase) => format!("無音時の定型句を除外しました: {phrase}"),
+            Self::RepetitionLoop { ngram, count } => {
+                format!("繰り返しループを除外しました: 「{ngram}」x{count}")
+            }
+            Self::NoSpeech { probability } => {
+                format!("発話なしと判定しました (no_speech={probability:.2})")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterOutcome {
+    pub text: String,
+    pub reasons: Vec<FilterReason>,
+}
+
+impl FilterOutcome {
+    /// True when everything whisper produced was discarded by the filter.
+    pub fn is_suppressed(&self) -> bool {
+        self.text.is_empty() && !self.reasons.is_empty()
+    }
+}
+
+pub struct HallucinationFilter {
+    phantom_phrases: BTreeMap<String, Vec<String>>,
+    no_speech_threshold: f32,
+    repetition_threshold: usize,
+}
+
+impl HallucinationFilter {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut phantom_phrases: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (lang, phrases) in BUILTIN_PHANTOM_PHRASES {
+            phantom_phrases
+                .entry(lang.to_string())
+                .or_default()
+                .extend(phrases.iter().map(|p| p.to_string()));
+        }
+        for (lang, phrases) in &config.phantom_phrases {
+            phantom_phrases
+                .entry(lang.clone())
+                .or_default()
+                .extend(phrases.iter().cloned());
+        }
+
+        Self {
+            phantom_phrases,
+            no_speech_threshold: config.no_speech_threshold,
+            repetition_threshold: config.repetition_threshold.max(2) as usize,
+        }
+    }
+
+    pub fn apply(&self, transcription: &Transcription) -> FilterOutcome {
+        let phrases = self.phrases_for(transcription.output_language());
+        let mut reasons = Vec::new();
+        let mut kept = String::new();
+
+        for segment in &transcription.segments {
+            match self.segment_reason(segment, &phrases) {
+                Some(reason) => reasons.push(reason),
+                None => kept.push_str(&segment.text),
+            }
+        }
+
+        let mut text = kept.trim().to_string();
+        // A phantom phrase may also be split across several short segments.
+        if let Some(phrase) = match_phantom(&text, &phrases) {
+            reasons.push(FilterReason::PhantomPhrase(phrase));
+            text.clear();
+        }
+
+        let (text, loops) = collapse_repetitions(&text, self.repetition_threshold);
+        reasons.extend(loops);
+        FilterOutcome { text, reasons }
+    }
+
+    fn phrases_for(&self, language: Option<&str>) -> Vec<(String, String)> {
+        self.phantom_phrases
+            .iter()
+            .filter(|(lang, _)| language.is_none_or(|l| l == lang.as_str()))
+            .flat_map(|(_, phrases)| phrases.iter())
+            .map(|p| (normalize_for_match(p), p.clone()))
+            .filter(|(normalized, _)| !normalized.is_empty())
+            .collect()
+    }
+
+    fn segment_reason(
+        &self,
+        segment: &WhisperSegment,
+        phrases: &[(String, String)],
+    ) -> Option<FilterReason> {
+        if let Some(probability) = segment.no_speech_prob {
+            let confident = segment
+                .avg_token_prob
+                .is_some_and(|p| p >= CONFIDENT_TOKEN_PROB);
+            if probability >= self.no_speech_threshold && !confident {
+                return Some(FilterReason::NoSpeech { probability });
+            }
+        }
+
+        match_phantom(&segment.text, phrases).map(FilterReason::PhantomPhrase)
+    }
+}
+
+fn match_phantom(text: &str, phrases: &[(String, String)]) -> Option<String> {
+    let normalized = normalize_for_match(text);
+    if normalized.is_empty() {
+        return None;
+    }
+    phrases
+        .iter()
+        .find(|(candidate, _)| *candidate == normalized)
+        .map(|(_, original)| original.clone())
+}
+
+fn normalize_for_match(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Collapses an n-gram repeated `threshold` or more times in a row down to a
+/// single copy. CJK text is compared per character, everything else per word.
+/// Per character, only n-grams of Japanese or Chinese text count, so runs of
+/// digits (`100000000円`) and Latin letters are left alone. When the loop is
+/// the whole utterance, nothing is kept.
+fn collapse_repetitions(text: &str, threshold: usize) -> (String, Vec<FilterReason>) {
+    let by_char = text.chars().any(is_cjk);
+    let tokens: Vec<String> = if by_char {
+        text.chars().map(|c| c.to_string()).collect()
+    } else {
+        text.split_whitespace().map(str::to_string).collect()
+    };
+    let separator = if by_char { "" } else { " " };
+    let min_len = if by_char { 2 } else { 1 };
+
+    let mut kept: Vec<&str> = Vec::with_capacity(tokens.len());
+    let mut reasons = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let remaining = tokens.len() - i;
+        let mut best: Option<(usize, usize)> = None;
+        for len in min_len..=MAX_NGRAM.min(remaining / threshold) {
+            if by_char && !is_cjk_ngram(&tokens[i..i + len]) {
+                continue;
+            }
+            let mut count = 1;
+            while i + (count + 1) * len <= tokens.len()
+                && tokens[i + count * len..i + (count + 1) * len] == tokens[i..i + len]
+            {
+                count += 1;
+            }
+            if count >= threshold && best.is_none_or(|(l, c)| len * count > l * c) {
+                best = Some((len, count));
+            }
+        }
+
+        match best {
+            Some((len, count)) => {
+                kept.extend(tokens[i..i + len].iter().map(String::as_str));
+                reasons.push(FilterReason::RepetitionLoop {
+                    ngram: tokens[i..i + len].join(separator),
+                    count,
+                });
+                i += len * count;
+            }
+            None => {
+                kept.push(&tokens[i]);
+                i += 1;
+            }
+        }
+    }
+
+    if reasons.is_empty() {
+        return (text.to_string(), reasons);
+    }
+
+    let collapsed = kept.join(separator);
+    if let [FilterReason::RepetitionLoop { ngram, .. }] = reasons.as_slice() {
+        if *ngram == collapsed {
+            return (String::new(), reasons);
+        }
+    }
+    (collapsed, reasons)
+}
+
+/// CJK characters, optionally with CJK punctuation such as `、`.
+fn is_cjk_ngram(tokens: &[String]) -> bool {
+    let mut chars = tokens.iter().flat_map(|t| t.chars());
+    chars.clone().all(|c| is_cjk(c) || is_cjk_punct(c)) && chars.any(is_cjk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str) -> WhisperSegment {
+        WhisperSegment {
+            text: text.to_string(),
+            ..WhisperSegment::default()
+        }
+    }
+
+    fn transcription(language: &str, segments: Vec<WhisperSegment>) -> Transcription {
+        Transcription {
+            text: segments.iter().map(|s| s.text.as_str()).collect(),
+            segments,
+            language: Some(language.to_string()),
+            translated: false,
+        }
+    }
+
+    fn filter() -> HallucinationFilter {
+        HallucinationFilter::from_config(&AppConfig::default())
+    }
+
+    #[test]
+    fn phantom_only_utterance_is_suppressed() {
+        let outcome = filter().apply(&transcription(
+            "ja",
+            vec![segment("ご視聴ありがとうございました。")],
+        ));
+        assert!(outcome.is_suppressed());
+        assert_eq!(
+            outcome.reasons,
+            vec![FilterReason::PhantomPhrase(
+                "ご視聴ありがとうございました".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn trailing_phantom_segment_is_dropped_but_speech_kept() {
+        let outcome = filter().apply(&transcription(
+            "ja",
+            vec![
+                segment("明日の会議は十時からです。"),
+                segment("ご視聴ありがとうございました"),
+            ],
+        ));
+        assert_eq!(outcome.text, "明日の会議は十時からです。");
+        assert!(!outcome.is_suppressed());
+    }
+
+    #[test]
+    fn english_phantom_ignores_case_and_punctuation() {
+        let outcome = filter().apply(&transcription(
+            "en",
+            vec![segment(" thank you for watching!")],
+        ));
+        assert!(outcome.is_suppressed());
+    }
+
+    #[test]
+    fn user_phrases_extend_builtin_list() {
+        let mut cfg = AppConfig::default();
+        cfg.phantom_phrases
+            .insert("ja".to_string(), vec!["おやすみなさい".to_string()]);
+        let outcome = HallucinationFilter::from_config(&cfg)
+            .apply(&transcription("ja", vec![segment("おやすみなさい")]));
+        assert!(outcome.is_suppressed());
+    }
+
+    #[test]
+    fn repetition_loop_inside_speech_collapses_to_one_copy() {
+        let outcome = filter().apply(&transcription(
+            "ja",
+            vec![segment(
+                "了解です。よろしくよろしくよろしくよろしくよろしく",
+            )],
+        ));
+        assert_eq!(outcome.text, "了解です。よろしく");
+        assert_eq!(
+            outcome.reasons,
+            vec![FilterReason::RepetitionLoop {
+                ngram: "よろしく".to_string(),
+                count: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn repetition_loop_covering_everything_is_suppressed() {
+        let outcome = filter().apply(&transcription(
+            "en",
+            vec![segment("okay okay okay okay okay okay")],
+        ));
+        assert!(outcome.is_suppressed());
+    }
+
+    #[test]
+    fn digit_runs_in_japanese_text_are_kept() {
+        let outcome = filter().apply(&transcription(
+            "ja",
+            vec![segment("予算は100000000円です。abababab")],
+        ));
+        assert_eq!(outcome.text, "予算は100000000円です。abababab");
+        assert!(outcome.reasons.is_empty());
+    }
+
+    #[test]
+    fn short_repeats_below_threshold_are_kept() {
+        let outcome = filter().apply(&transcription("en", vec![segment("no no no way")]));
+        assert_eq!(outcome.text, "no no no way");
+        assert!(outcome.reasons.is_empty());
+    }
+
+    #[test]
+    fn no_speech_segment_is_dropped_unless_tokens_are_confident() {
+        let mut silent = segment("えー");
+        silent.no_speech_prob = Some(0.9);
+        silent.avg_token_prob = Some(0.2);
+        let mut confident = segment("はい");
+        confident.no_speech_prob = Some(0.9);
+        confident.avg_token_prob = Some(0.8);
+
+        let outcome = filter().apply(&transcription("ja", vec![silent, confident]));
+        assert_eq!(outcome.text, "はい");
+        assert_eq!(
+            outcome.reasons,
+            vec![FilterReason::NoSpeech { probability: 0.9 }]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::process::Command;
+
+/// Best-effort name of the focused application (app id / WM class).
+/// Wayland has no common API for this, so each compositor's own IPC is tried;
+/// GNOME Shell exposes nothing to third parties and yields `None`.
+pub async fn focused_app() -> Option<String> {
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        if let Some(out) = run("hyprctl", &["activewindow", "-j"]).await {
+            return parse_hyprctl(&out);
+        }
+    }
+    if std::env::var_os("SWAYSOCK").is_some() {
+        if let Some(out) = run("swaymsg", &["-t", "get_tree", "-r"]).await {
+            return parse_sway_tree(&out);
+        }
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        if let Some(out) = run("xdotool", &["getactivewindow", "getwindowclassname"]).await {
+            let class = out.trim();
+            if !class.is_empty() {
+                return Some(class.to_string());
+            }
+        }
+    }
+    None
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = tokio::time::timeout(
+        Duration::from_millis(500),
+        Command::new(program).args(args).kill_on_drop(true).output(),
+    )
+    .await
+    .ok()?
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn non_empty(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+fn parse_hyprctl(raw: &str) -> Option<String> {
+    let window: Value = serde_json::from_str(raw).ok()?;
+    non_empty(&window["class"]).or_else(|| non_empty(&window["initialClass"]))
+}
+
+fn parse_sway_tree(raw: &str) -> Option<String> {
+    let tree: Value = serde_json::from_str(raw).ok()?;
+    find_focused(&tree)
+}
+
+fn find_focused(node: &Value) -> Option<String> {
+    if node["focused"].as_bool() == Some(true) {
+        // Native Wayland clients have app_id; XWayland ones only a class.
+        if let Some(app) =
+            non_empty(&node["app_id"]).or_else(|| non_empty(&node["window_properties"]["class"]))
+        {
+            return Some(app);
+        }
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node[key].as_array())
+        .flatten()
+        .find_map(find_focused)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hyprctl_prefers_class() {
+        assert_eq!(
+            parse_hyprctl(r#"{"class":"firefox","initialClass":"Firefox"}"#),
+            Some("firefox".to_string())
+        );
+        assert_eq!(
+            parse_hyprctl(r#"{"class":"","initialClass":"code"}"#),
+            Some("code".to_string())
+        );
+        assert_eq!(parse_hyprctl("{}"), None);
+    }
+
+    #[test]
+    fn sway_finds_focused_leaf_including_xwayland() {
+        let tree = r#"{"focused":false,"nodes":[
+            {"focused":false,"nodes":[{"focused":false,"app_id":"foot"}]},
+            {"focused":false,"floating_nodes":[
+                {"focused":true,"app_id":null,"window_properties":{"class":"Slack"}}
+            ]}
+        ]}"#;
+        assert_eq!(parse_sway_tree(tree), Some("Slack".to_string()));
+        assert_eq!(parse_sway_tree(r#"{"focused":true,"nodes":[]}"#), None);
+    }
+}
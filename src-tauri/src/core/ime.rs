@@ -0,0 +1,629 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::sync::{watch, Mutex};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, StructureBuilder, Value};
+use zbus::ObjectServer;
+
+/// The IBus engine notype registers to commit text with.
+const IBUS_COMMIT_ENGINE: &str = "notype";
+const IBUS_FACTORY_PATH: &str = "/org/freedesktop/IBus/Factory";
+/// How long IBus may take to focus the engine once it is switched to.
+const IBUS_FOCUS_TIMEOUT: Duration = Duration::from_secs(1);
+const FCITX5_ACTIVE: i32 = 2;
+
+#[zbus::proxy(
+    interface = "org.fcitx.Fcitx.Controller1",
+    default_service = "org.fcitx.Fcitx5",
+    default_path = "/controller"
+)]
+trait Fcitx5Controller {
+    fn state(&self) -> zbus::Result<i32>;
+
+    fn activate(&self) -> zbus::Result<()>;
+
+    fn deactivate(&self) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.IBus",
+    default_service = "org.freedesktop.IBus",
+    default_path = "/org/freedesktop/IBus"
+)]
+trait IBus {
+    fn register_component(&self, component: &Value<'_>) -> zbus::Result<()>;
+
+    fn get_global_engine(&self) -> zbus::Result<OwnedValue>;
+
+    fn set_global_engine(&self, engine_name: &str) -> zbus::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImeFramework {
+    IBus,
+    Fcitx5,
+}
+
+impl ImeFramework {
+    /// From the usual IM variables; GNOME runs IBus even without them.
+    pub fn detect(gnome: bool) -> Option<Self> {
+        Self::from_env(|name| std::env::var(name).ok(), gnome)
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>, gnome: bool) -> Option<Self> {
+        for name in ["XMODIFIERS", "GTK_IM_MODULE", "QT_IM_MODULE"] {
+            let value = var(name).unwrap_or_default().to_ascii_lowercase();
+            if value.contains("fcitx") {
+                return Some(Self::Fcitx5);
+            }
+            if value.contains("ibus") {
+                return Some(Self::IBus);
+            }
+        }
+        gnome.then_some(Self::IBus)
+    }
+}
+
+/// An IME switched away from while typing. `restore` switches it back;
+/// dropped without it, as when typing is cancelled midway, it is switched
+/// back in the background.
+pub struct ParkedIme(Option<Parked>);
+
+enum Parked {
+    IBus {
+        conn: zbus::Connection,
+        engine: String,
+    },
+    Fcitx5 {
+        conn: zbus::Connection,
+    },
+}
+
+impl ParkedIme {
+    pub async fn restore(mut self) {
+        if let Some(parked) = self.0.take() {
+            parked.restore().await;
+        }
+    }
+}
+
+impl Drop for ParkedIme {
+    fn drop(&mut self) {
+        let Some(parked) = self.0.take() else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(parked.restore());
+            }
+            Err(_) => tracing::warn!("ime: no runtime left to restore the input method"),
+        }
+    }
+}
+
+impl Parked {
+    async fn restore(self) {
+        let result = match &self {
+            Self::IBus { conn, engine } => match IBusProxy::new(conn).await {
+                Ok(ibus) => ibus.set_global_engine(engine).await,
+                Err(err) => Err(err),
+            },
+            Self::Fcitx5 { conn } => match controller(conn).await {
+                Ok(controller) => controller.activate().await,
+                Err(err) => Err(err),
+            },
+        };
+        if let Err(err) = result {
+            tracing::warn!("ime: failed to restore input method: {err}");
+        }
+    }
+}
+
+/// Commits text into the focused app as an IBus engine of its own: the
+/// engine is made the global one, emits `CommitText`, and the previous
+/// engine is switched back. Committed text is not converted, so Japanese
+/// lands as is whatever the keyboard layout.
+#[derive(Default)]
+pub struct IBusCommitter {
+    bus: Mutex<Option<IBusBus>>,
+}
+
+impl IBusCommitter {
+    pub async fn commit(&self, text: &str) -> Result<(), String> {
+        let mut bus = self.bus.lock().await;
+        if bus.is_none() {
+            let address = ibus(&["address"]).await?;
+            let builder =
+                zbus::connection::Builder::address(address.as_str()).map_err(|e| e.to_string())?;
+            *bus = Some(IBusBus::connect(builder).await?);
+        }
+        let result = bus.as_ref().expect("connected").commit(text).await;
+        if result.is_err() {
+            // IBus may have restarted; connect again next time.
+            *bus = None;
+        }
+        result
+    }
+}
+
+/// A connection to IBus's own bus serving the commit engine.
+struct IBusBus {
+    conn: zbus::Connection,
+    focused: Arc<watch::Sender<Option<OwnedObjectPath>>>,
+}
+
+impl IBusBus {
+    async fn connect(builder: zbus::connection::Builder<'_>) -> Result<Self, String> {
+        let focused = Arc::new(watch::channel(None).0);
+        let factory = Factory {
+            focused: focused.clone(),
+            engines: AtomicU32::new(0),
+        };
+        let conn = builder
+            .serve_at(IBUS_FACTORY_PATH, factory)
+            .map_err(|e| e.to_string())?
+            .build()
+            .await
+            .map_err(|e| e.to_string())?;
+        IBusProxy::new(&conn)
+            .await
+            .map_err(|e| e.to_string())?
+            .register_component(&component())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self { conn, focused })
+    }
+
+    async fn commit(&self, text: &str) -> Result<(), String> {
+        let ibus = IBusProxy::new(&self.conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        let desc = ibus.get_global_engine().await.map_err(|e| e.to_string())?;
+        let previous = engine_name(&desc).ok_or("ibus: unreadable global engine")?;
+        self.focused.send_replace(None);
+        let mut focused = self.focused.subscribe();
+        ibus.set_global_engine(IBUS_COMMIT_ENGINE)
+            .await
+            .map_err(|e| e.to_string())?;
+        let parked = ParkedIme((previous != IBUS_COMMIT_ENGINE).then(|| Parked::IBus {
+            conn: self.conn.clone(),
+            engine: previous,
+        }));
+
+        let engine = tokio::time::timeout(IBUS_FOCUS_TIMEOUT, focused.wait_for(Option::is_some))
+            .await
+            .map_err(|_| "ibus: no input context took focus".to_string())?
+            .map_err(|e| e.to_string())?
+            .clone()
+            .expect("focused engine");
+        let result = self
+            .conn
+            .emit_signal(
+                None::<&str>,
+                &engine,
+                "org.freedesktop.IBus.Engine",
+                "CommitText",
+                &(ibus_text(text),),
+            )
+            .await
+            .map_err(|e| e.to_string());
+        parked.restore().await;
+        result
+    }
+}
+
+/// Creates the commit engine when IBus switches to it.
+struct Factory {
+    focused: Arc<watch::Sender<Option<OwnedObjectPath>>>,
+    engines: AtomicU32,
+}
+
+#[zbus::interface(name = "org.freedesktop.IBus.Factory")]
+impl Factory {
+    async fn create_engine(
+        &self,
+        name: &str,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> zbus::fdo::Result<OwnedObjectPath> {
+        if name != IBUS_COMMIT_ENGINE {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "unknown engine {name}"
+            )));
+        }
+        let id = self.engines.fetch_add(1, Ordering::Relaxed);
+        let path = OwnedObjectPath::try_from(format!("/org/freedesktop/IBus/Engine/{id}"))
+            .map_err(|e| zbus::fdo::Error::Failed(e.to_string()))?;
+        let engine = CommitEngine {
+            path: path.clone(),
+            focused: self.focused.clone(),
+        };
+        server.at(&path, engine).await?;
+        Ok(path)
+    }
+}
+
+/// An engine that only commits; keys pass through to the app.
+struct CommitEngine {
+    path: OwnedObjectPath,
+    focused: Arc<watch::Sender<Option<OwnedObjectPath>>>,
+}
+
+impl CommitEngine {
+    fn unfocus(&self) {
+        self.focused.send_if_modified(|focused| {
+            let ours = focused.as_ref() == Some(&self.path);
+            if ours {
+                *focused = None;
+            }
+            ours
+        });
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.IBus.Engine")]
+impl CommitEngine {
+    fn process_key_event(&self, _keyval: u32, _keycode: u32, _state: u32) -> bool {
+        false
+    }
+
+    fn focus_in(&self) {
+        self.focused.send_replace(Some(self.path.clone()));
+    }
+
+    fn focus_out(&self) {
+        self.unfocus();
+    }
+
+    fn enable(&self) {}
+
+    fn disable(&self) {
+        self.unfocus();
+    }
+
+    fn reset(&self) {}
+
+    fn set_capabilities(&self, _caps: u32) {}
+
+    fn set_cursor_location(&self, _x: i32, _y: i32, _w: i32, _h: i32) {}
+
+    fn set_surrounding_text(&self, _text: Value<'_>, _cursor: u32, _anchor: u32) {}
+}
+
+/// IBus serializes its objects as `(type name, attachments, fields...)`.
+fn serializable(type_name: &str) -> StructureBuilder<'static> {
+    StructureBuilder::new()
+        .add_field(type_name.to_string())
+        .add_field(HashMap::<String, Value<'static>>::new())
+}
+
+/// An `IBusComponent` offering the commit engine.
+fn component() -> Value<'static> {
+    // `IBusEngineDesc`: name, long name, description, language, license,
+    // author, icon, layout, rank, hotkeys, symbol, setup, layout variant,
+    // layout option, version, textdomain and icon property key.
+    let mut engine = serializable("IBusEngineDesc");
+    for field in [IBUS_COMMIT_ENGINE, "notype", "notype dictation", "ja"] {
+        engine = engine.add_field(field.to_string());
+    }
+    for field in ["", "", "", "default"] {
+        engine = engine.add_field(field.to_string());
+    }
+    engine = engine.add_field(0u32);
+    for _ in 0..8 {
+        engine = engine.add_field(String::new());
+    }
+    // Name, description, version, license, author, homepage, exec,
+    // textdomain, observed paths and engines.
+    let mut component = serializable("IBusComponent").add_field("org.notype.IBus".to_string());
+    for _ in 0..7 {
+        component = component.add_field(String::new());
+    }
+    component
+        .add_field(Vec::<Value<'static>>::new())
+        .add_field(vec![Value::from(engine.build())])
+        .build()
+        .into()
+}
+
+/// An `IBusText` without attributes.
+fn ibus_text(text: &str) -> Value<'static> {
+    let attributes = serializable("IBusAttrList")
+        .add_field(Vec::<Value<'static>>::new())
+        .build();
+    let text = serializable("IBusText")
+        .add_field(text.to_string())
+        .append_field(Value::new(Value::from(attributes)))
+        .build();
+    Value::from(text)
+}
+
+/// The name in a serialized `IBusEngineDesc`.
+fn engine_name(desc: &Value<'_>) -> Option<String> {
+    match desc {
+        Value::Value(inner) => engine_name(inner),
+        Value::Structure(desc) => match desc.fields().get(2)? {
+            Value::Str(name) => Some(name.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Switches an active Fcitx5 to direct input so synthetic keys are not
+/// turned into romaji conversions. `None` when it is not converting or
+/// could not be reached; typing then goes ahead as is.
+pub async fn park_fcitx5() -> Option<ParkedIme> {
+    let conn = zbus::Connection::session().await.ok()?;
+    park_fcitx5_on(conn).await
+}
+
+async fn park_fcitx5_on(conn: zbus::Connection) -> Option<ParkedIme> {
+    let controller = controller(&conn)
+        .await
+        .inspect_err(|err| tracing::debug!("ime: fcitx5 unavailable: {err}"))
+        .ok()?;
+    let state = controller
+        .state()
+        .await
+        .inspect_err(|err| tracing::debug!("ime: fcitx5 unavailable: {err}"))
+        .ok()?;
+    if state != FCITX5_ACTIVE {
+        return None;
+    }
+    match controller.deactivate().await {
+        Ok(()) => Some(ParkedIme(Some(Parked::Fcitx5 { conn }))),
+        Err(err) => {
+            tracing::warn!("ime: failed to deactivate fcitx5: {err}");
+            None
+        }
+    }
+}
+
+async fn controller(conn: &zbus::Connection) -> zbus::Result<Fcitx5ControllerProxy<'_>> {
+    Fcitx5ControllerProxy::builder(conn)
+        .cache_properties(zbus::CacheProperties::No)
+        .build()
+        .await
+}
+
+async fn ibus(args: &[&str]) -> Result<String, String> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(1),
+        Command::new("ibus").args(args).kill_on_drop(true).output(),
+    )
+    .await
+    .map_err(|_| "ibus timed out".to_string())?
+    .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::secret::PrivateBus;
+    use futures_util::StreamExt;
+    use std::sync::Mutex as StdMutex;
+    use zbus::interface;
+    use zbus::message::Header;
+
+    #[test]
+    fn framework_comes_from_im_variables() {
+        let detect = |vars: &[(&str, &str)], gnome| {
+            ImeFramework::from_env(
+                |name| {
+                    vars.iter()
+                        .find(|(k, _)| *k == name)
+                        .map(|(_, v)| v.to_string())
+                },
+                gnome,
+            )
+        };
+        assert_eq!(
+            detect(&[("XMODIFIERS", "@im=fcitx")], true),
+            Some(ImeFramework::Fcitx5)
+        );
+        assert_eq!(
+            detect(&[("GTK_IM_MODULE", "ibus")], false),
+            Some(ImeFramework::IBus)
+        );
+        assert_eq!(detect(&[], true), Some(ImeFramework::IBus));
+        assert_eq!(detect(&[("XMODIFIERS", "@im=none")], false), None);
+    }
+
+    struct MockController {
+        state: Arc<StdMutex<i32>>,
+    }
+
+    #[interface(name = "org.fcitx.Fcitx.Controller1")]
+    impl MockController {
+        fn state(&self) -> i32 {
+            *self.state.lock().expect("lock")
+        }
+
+        fn activate(&self) {
+            *self.state.lock().expect("lock") = FCITX5_ACTIVE;
+        }
+
+        fn deactivate(&self) {
+            *self.state.lock().expect("lock") = 1;
+        }
+    }
+
+    #[tokio::test]
+    async fn fcitx5_is_deactivated_while_typing() {
+        let state = Arc::new(StdMutex::new(FCITX5_ACTIVE));
+        let (server_stream, client_stream) = tokio::net::UnixStream::pair().expect("socket pair");
+        let server = zbus::connection::Builder::unix_stream(server_stream)
+            .server(zbus::Guid::generate())
+            .expect("server")
+            .p2p()
+            .serve_at(
+                "/controller",
+                MockController {
+                    state: state.clone(),
+                },
+            )
+            .expect("controller")
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client_stream)
+            .p2p()
+            .build();
+        let (_server, client) = tokio::try_join!(server, client).expect("p2p connection");
+
+        let parked = park_fcitx5_on(client.clone()).await.expect("parked");
+        assert_eq!(*state.lock().expect("lock"), 1);
+        // Already in direct input: nothing to park.
+        assert!(park_fcitx5_on(client.clone()).await.is_none());
+        parked.restore().await;
+        assert_eq!(*state.lock().expect("lock"), FCITX5_ACTIVE);
+
+        // Typing cancelled midway drops the parked IME instead.
+        drop(park_fcitx5_on(client).await.expect("parked"));
+        wait_until(|| *state.lock().expect("lock") == FCITX5_ACTIVE).await;
+    }
+
+    /// Just enough of the IBus daemon: it creates and focuses the engine it
+    /// is switched to, as when a text field has focus.
+    #[derive(Clone)]
+    struct MockIBus {
+        engine: Arc<StdMutex<String>>,
+        factory: Arc<StdMutex<Option<String>>>,
+        focused_field: bool,
+    }
+
+    #[interface(name = "org.freedesktop.IBus")]
+    impl MockIBus {
+        fn register_component(&self, component: Value<'_>, #[zbus(header)] header: Header<'_>) {
+            let Value::Structure(component) = component else {
+                panic!("component is not a structure");
+            };
+            let Some(Value::Array(engines)) = component.fields().last() else {
+                panic!("component has no engines");
+            };
+            assert_eq!(
+                engine_name(&engines[0]).as_deref(),
+                Some(IBUS_COMMIT_ENGINE)
+            );
+            *self.factory.lock().expect("lock") = header.sender().map(|s| s.to_string());
+        }
+
+        fn get_global_engine(&self) -> Value<'static> {
+            let name = self.engine.lock().expect("lock").clone();
+            serializable("IBusEngineDesc")
+                .add_field(name)
+                .build()
+                .into()
+        }
+
+        async fn set_global_engine(
+            &self,
+            name: &str,
+            #[zbus(connection)] conn: &zbus::Connection,
+        ) -> zbus::fdo::Result<()> {
+            *self.engine.lock().expect("lock") = name.to_string();
+            if name != IBUS_COMMIT_ENGINE || !self.focused_field {
+                return Ok(());
+            }
+            let factory = self.factory.lock().expect("lock").clone();
+            let reply = conn
+                .call_method(
+                    factory.as_deref(),
+                    IBUS_FACTORY_PATH,
+                    Some("org.freedesktop.IBus.Factory"),
+                    "CreateEngine",
+                    &(name,),
+                )
+                .await?;
+            let path: OwnedObjectPath = reply.body().deserialize()?;
+            conn.call_method(
+                factory.as_deref(),
+                &path,
+                Some("org.freedesktop.IBus.Engine"),
+                "FocusIn",
+                &(),
+            )
+            .await?;
+            Ok(())
+        }
+    }
+
+    async fn mock_ibus(bus: &PrivateBus, focused_field: bool) -> (zbus::Connection, MockIBus) {
+        let mock = MockIBus {
+            engine: Arc::new(StdMutex::new("mozc-jp".to_string())),
+            factory: Arc::default(),
+            focused_field,
+        };
+        let server = bus
+            .connect()
+            .name("org.freedesktop.IBus")
+            .expect("name")
+            .serve_at("/org/freedesktop/IBus", mock.clone())
+            .expect("ibus")
+            .build()
+            .await
+            .expect("server");
+        (server, mock)
+    }
+
+    async fn wait_until(done: impl Fn() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !done() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("timed out");
+    }
+
+    #[tokio::test]
+    async fn ibus_commits_text_through_its_own_engine() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("skipped: dbus-daemon is not installed");
+            return;
+        };
+        let (server, mock) = mock_ibus(&bus, true).await;
+        let rule = zbus::MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface("org.freedesktop.IBus.Engine")
+            .expect("interface")
+            .member("CommitText")
+            .expect("member")
+            .build();
+        let mut commits = zbus::MessageStream::for_match_rule(rule, &server, None)
+            .await
+            .expect("match rule");
+
+        let ibus = IBusBus::connect(bus.connect()).await.expect("registered");
+        ibus.commit("こんにちは。").await.expect("commit");
+        let signal = commits.next().await.expect("signal").expect("message");
+        let body = signal.body();
+        let text: Value<'_> = body.deserialize().expect("text");
+        let Value::Structure(text) = text else {
+            panic!("text is not a structure");
+        };
+        assert_eq!(text.full_signature().as_str(), "(sa{sv}sv)");
+        assert_eq!(text.fields()[0], Value::from("IBusText"));
+        assert_eq!(text.fields()[2], Value::from("こんにちは。"));
+        assert_eq!(*mock.engine.lock().expect("lock"), "mozc-jp");
+    }
+
+    #[tokio::test]
+    async fn cancelled_ibus_commit_switches_the_engine_back() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("skipped: dbus-daemon is not installed");
+            return;
+        };
+        // Nothing focuses the engine, so the commit waits until cancelled.
+        let (_server, mock) = mock_ibus(&bus, false).await;
+        let ibus = IBusBus::connect(bus.connect()).await.expect("registered");
+        let commit = tokio::time::timeout(Duration::from_millis(200), ibus.commit("テスト"));
+        assert!(commit.await.is_err());
+        wait_until(|| *mock.engine.lock().expect("lock") == "mozc-jp").await;
+    }
+}
@@ -1,8 +1,58 @@
-use tokio::process::Command;
-
+use super::clipboard;
+use super::config::{AppConfig, PasteMode};
 use super::error::AppError;
+use super::ime::ImeFramework;
+use super::inject_backend::{build_backend, BackendKind, DisplayServer, InjectionBackend};
+use super::macros::KeyChord;
+
+/// Sends text and keys through the first backend of the chain that works.
+/// The one that worked last is tried first next time.
+pub struct Injector {
+    backends: Vec<Box<dyn InjectionBackend>>,
+    preferred: usize,
+    last_used: Option<BackendKind>,
+    paste: PastePolicy,
+}
+
+/// When a final result is pasted instead of typed, and with which chord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PastePolicy {
+    mode: PasteMode,
+    chord: KeyChord,
+    min_chars: usize,
+}
 
-pub struct Injector;
+impl PastePolicy {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let chord = KeyChord::parse(&config.paste_chord).unwrap_or_else(|| {
+            tracing::warn!(
+                "inject: invalid pasteChord {:?}, using ctrl+v",
+                config.paste_chord
+            );
+            KeyChord::parse("ctrl+v").expect("valid chord")
+        });
+        Self {
+            mode: config.paste_mode,
+            chord,
+            min_chars: config.paste_min_chars,
+        }
+    }
+
+    fn applies(&self, text: &str) -> bool {
+        match self.mode {
+            PasteMode::Never => false,
+            PasteMode::Auto => text.chars().count() >= self.min_chars,
+            PasteMode::Always => !text.is_empty(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Op<'a> {
+    Text(&'a str),
+    Key(&'a str, u32),
+    Chord(&'a KeyChord),
+}
 
 #[derive(Debug, Default)]
 pub struct InjectionSession {
@@ -41,34 +91,115 @@ impl InjectionSession {
 }
 
 impl Injector {
-    pub fn new() -> Self {
-        Self
+    /// Uses `injectionBackends`, or the default chain for the detected
+    /// display server when it is empty, led by `ime` when an IME is set up.
+    pub fn from_config(config: &AppConfig) -> Self {
+        let paste = PastePolicy::from_config(config);
+        let server = DisplayServer::detect();
+        let kinds = if config.injection_backends.is_empty() {
+            let mut chain = server.default_chain();
+            if ImeFramework::detect(server == DisplayServer::Gnome).is_some() {
+                chain.insert(0, BackendKind::Ime);
+            }
+            chain
+        } else {
+            config.injection_backends.clone()
+        };
+        let names: Vec<&str> = kinds.iter().map(BackendKind::as_str).collect();
+        tracing::info!(
+            "inject: {:?} session, backends {}",
+            server,
+            names.join(" > ")
+        );
+        let mut injector = Self::with_backends(
+            kinds
+                .into_iter()
+                .map(|kind| build_backend(kind, server))
+                .collect(),
+        );
+        injector.set_paste(paste);
+        injector
+    }
+
+    pub fn with_backends(backends: Vec<Box<dyn InjectionBackend>>) -> Self {
+        Self {
+            backends,
+            preferred: 0,
+            last_used: None,
+            paste: PastePolicy::from_config(&AppConfig::default()),
+        }
     }
 
-    pub async fn clear_partial(&self, session: &mut InjectionSession) -> Result<(), AppError> {
+    /// Paste settings for the results that follow, e.g. with the focused
+    /// app's profile applied.
+    pub fn set_paste(&mut self, paste: PastePolicy) {
+        for backend in &self.backends {
+            backend.set_paste_chord(&paste.chord);
+        }
+        self.paste = paste;
+    }
+
+    /// The backend that handled the last injection, once.
+    pub fn take_last_used(&mut self) -> Option<BackendKind> {
+        self.last_used.take()
+    }
+
+    pub async fn clear_partial(&mut self, session: &mut InjectionSession) -> Result<(), AppError> {
         if session.last_partial().is_empty() {
             return Ok(());
         }
-        for _ in session.last_partial().chars() {
-            self.press_key("BackSpace").await?;
-        }
+        let partial = session.last_partial().to_string();
+        self.erase(&partial).await?;
         session.set_last_partial("");
         Ok(())
     }
 
+    /// Removes `text` that was typed earlier by sending one BackSpace per char.
+    pub async fn erase(&mut self, text: &str) -> Result<(), AppError> {
+        self.press("BackSpace", text.chars().count() as u32).await
+    }
+
+    /// Presses `key` (an XKB keysym name such as `Return`) `count` times.
+    pub async fn press(&mut self, key: &str, count: u32) -> Result<(), AppError> {
+        if count == 0 {
+            return Ok(());
+        }
+        self.run(Op::Key(key, count)).await
+    }
+
+    /// Presses `chord`'s key while holding its modifiers.
+    pub async fn press_chord(&mut self, chord: &KeyChord) -> Result<(), AppError> {
+        self.run(Op::Chord(chord)).await
+    }
+
     pub async fn type_final(
-        &self,
+        &mut self,
         session: &mut InjectionSession,
         text: &str,
     ) -> Result<(), AppError> {
         self.clear_partial(session).await?;
+        if self.paste.applies(text) {
+            match self.paste_text(text).await {
+                Ok(()) => {
+                    session.reset();
+                    return Ok(());
+                }
+                Err(err) => tracing::warn!("inject: paste failed, typing instead: {}", err.details),
+            }
+        }
         self.type_text(text).await?;
         session.reset();
         Ok(())
     }
 
+    /// Types one piece of a streamed result. Pieces are never pasted: that
+    /// would swap the clipboard for every word.
+    pub async fn type_streamed(&mut self, text: &str) -> Result<(), AppError> {
+        self.type_text(text).await
+    }
+
     pub async fn type_partial_replace(
-        &self,
+        &mut self,
         session: &mut InjectionSession,
         text: &str,
     ) -> Result<(), AppError> {
@@ -87,38 +218,194 @@ impl Injector {
         Ok(())
     }
 
-    async fn press_key(&self, key: &str) -> Result<(), AppError> {
-        let output = Command::new("wtype")
-            .arg("-k")
-            .arg(key)
-            .output()
-            .await
-            .map_err(|e| AppError::new("wtype の実行に失敗しました", e.to_string()))?;
+    /// Pastes `text` with the policy's chord, keeping the user's clipboard.
+    async fn paste_text(&mut self, text: &str) -> Result<(), AppError> {
+        let chord = self.paste.chord.clone();
+        clipboard::paste(text, self.run(Op::Chord(&chord))).await?;
+        self.last_used = Some(BackendKind::Clipboard);
+        Ok(())
+    }
 
-        if !output.status.success() {
-            return Err(AppError::new(
-                "キー注入に失敗しました",
-                String::from_utf8_lossy(&output.stderr).to_string(),
-            ));
+    async fn type_text(&mut self, text: &str) -> Result<(), AppError> {
+        if text.is_empty() {
+            return Ok(());
         }
+        self.run(Op::Text(text)).await
+    }
 
-        Ok(())
+    /// Tries the preferred backend, then the rest of the chain in order. A
+    /// backend that fails partway may leave some of its input behind.
+    async fn run(&mut self, op: Op<'_>) -> Result<(), AppError> {
+        let order: Vec<usize> = std::iter::once(self.preferred)
+            .chain((0..self.backends.len()).filter(|i| *i != self.preferred))
+            .collect();
+        let mut last_err = None;
+        for i in order {
+            let Some(backend) = self.backends.get(i) else {
+                continue;
+            };
+            let result = match op {
+                Op::Text(text) => backend.type_text(text).await,
+                Op::Key(key, count) => backend.press_key(key, count).await,
+                Op::Chord(chord) => backend.press_chord(chord).await,
+            };
+            let kind = backend.kind();
+            match result {
+                Ok(()) => {
+                    if i != self.preferred {
+                        tracing::info!("inject: switched to {}", kind.as_str());
+                        self.preferred = i;
+                    }
+                    self.last_used = Some(kind);
+                    return Ok(());
+                }
+                Err(err) => {
+                    tracing::warn!("inject: {} failed: {}", kind.as_str(), err.details);
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            AppError::new("入力方式が設定されていません", "no injection backend")
+        }))
     }
+}
 
-    async fn type_text(&self, text: &str) -> Result<(), AppError> {
-        let output = Command::new("wtype")
-            .arg(text)
-            .output()
-            .await
-            .map_err(|e| AppError::new("wtype の実行に失敗しました", e.to_string()))?;
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
 
-        if !output.status.success() {
-            return Err(AppError::new(
-                "テキスト注入に失敗しました",
-                String::from_utf8_lossy(&output.stderr).to_string(),
+    use super::*;
+
+    /// Records what it was asked to send; fails everything when `broken`.
+    struct Fake {
+        kind: BackendKind,
+        broken: bool,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Fake {
+        fn record(&self, what: String) -> Result<(), AppError> {
+            if self.broken {
+                return Err(AppError::new("failed", self.kind.as_str()));
+            }
+            self.sent.lock().unwrap().push(what);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl InjectionBackend for Fake {
+        fn kind(&self) -> BackendKind {
+            self.kind
+        }
+
+        async fn type_text(&self, text: &str) -> Result<(), AppError> {
+            self.record(format!("{}: {text}", self.kind.as_str()))
+        }
+
+        async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+            self.record(format!("{}: {key} x{count}", self.kind.as_str()))
+        }
+
+        async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+            self.record(format!("{}: chord {}", self.kind.as_str(), chord.key))
+        }
+
+        fn set_paste_chord(&self, chord: &KeyChord) {
+            let _ = self.record(format!(
+                "{}: paste with {}+{}",
+                self.kind.as_str(),
+                chord.modifiers.join("+"),
+                chord.key
             ));
         }
+    }
 
-        Ok(())
+    fn fake(kind: BackendKind, broken: bool, sent: &Arc<Mutex<Vec<String>>>) -> Box<Fake> {
+        Box::new(Fake {
+            kind,
+            broken,
+            sent: sent.clone(),
+        })
+    }
+
+    #[tokio::test]
+    async fn failing_backends_fall_through_the_chain() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut injector = Injector::with_backends(vec![
+            fake(BackendKind::Wtype, true, &sent),
+            fake(BackendKind::Ydotool, false, &sent),
+            fake(BackendKind::Clipboard, false, &sent),
+        ]);
+        let mut session = InjectionSession::new();
+
+        injector
+            .type_partial_replace(&mut session, "あい")
+            .await
+            .expect("partial");
+        assert_eq!(injector.take_last_used(), Some(BackendKind::Ydotool));
+        assert_eq!(injector.take_last_used(), None);
+        injector
+            .type_final(&mut session, "愛")
+            .await
+            .expect("final");
+        assert_eq!(
+            *sent.lock().unwrap(),
+            ["ydotool: あい", "ydotool: BackSpace x2", "ydotool: 愛"]
+        );
+    }
+
+    #[test]
+    fn paste_policy_follows_mode_and_threshold() {
+        let policy = |mode, chord: &str| {
+            PastePolicy::from_config(&AppConfig {
+                paste_mode: mode,
+                paste_chord: chord.to_string(),
+                paste_min_chars: 3,
+                ..AppConfig::default()
+            })
+        };
+        assert!(!policy(PasteMode::Never, "ctrl+v").applies("長い長い文章"));
+        assert!(!policy(PasteMode::Auto, "ctrl+v").applies("短い"));
+        assert!(policy(PasteMode::Auto, "ctrl+v").applies("三文字"));
+        assert!(policy(PasteMode::Always, "ctrl+v").applies("a"));
+        assert!(!policy(PasteMode::Always, "ctrl+v").applies(""));
+        assert_eq!(
+            policy(PasteMode::Always, "ctrl+shift+v").chord.modifiers,
+            ["ctrl", "shift"]
+        );
+        assert_eq!(
+            policy(PasteMode::Always, "hyper+v").chord,
+            KeyChord::parse("ctrl+v").expect("chord")
+        );
+
+        // The clipboard fallback pastes with the same chord.
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut injector =
+            Injector::with_backends(vec![fake(BackendKind::Clipboard, false, &sent)]);
+        injector.set_paste(policy(PasteMode::Never, "ctrl+shift+v"));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            ["clipboard: paste with ctrl+shift+v"]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_backends_failing_is_an_error() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut injector = Injector::with_backends(vec![
+            fake(BackendKind::Wtype, true, &sent),
+            fake(BackendKind::Xdotool, true, &sent),
+        ]);
+        let err = injector.press("Return", 1).await.unwrap_err();
+        assert_eq!(err.details, "xdotool");
+        assert!(injector.press("Return", 0).await.is_ok());
+        assert!(Injector::with_backends(Vec::new())
+            .press("Return", 1)
+            .await
+            .is_err());
     }
 }
@@ -0,0 +1,575 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::clipboard;
+use super::error::AppError;
+use super::ime::{self, ImeFramework};
+use super::macros::KeyChord;
+use super::portal;
+use super::xkb::Keymap;
+
+/// Backends that can be listed in `injectionBackends`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackendKind {
+    Wtype,
+    Ydotool,
+    Xdotool,
+    Portal,
+    Ime,
+    Clipboard,
+}
+
+impl BackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wtype => "wtype",
+            Self::Ydotool => "ydotool",
+            Self::Xdotool => "xdotool",
+            Self::Portal => "portal",
+            Self::Ime => "ime",
+            Self::Clipboard => "clipboard",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayServer {
+    Wayland,
+    /// GNOME's Wayland session; mutter has no virtual-keyboard protocol.
+    Gnome,
+    X11,
+}
+
+impl DisplayServer {
+    /// `WAYLAND_DISPLAY` wins over `XDG_SESSION_TYPE`, which wins over
+    /// `DISPLAY`; with none of them set Wayland is assumed. GNOME is told
+    /// apart by `XDG_CURRENT_DESKTOP`.
+    pub fn detect() -> Self {
+        Self::from_env(|name| std::env::var(name).ok())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let set = |name: &str| var(name).is_some_and(|v| !v.trim().is_empty());
+        let wayland = set("WAYLAND_DISPLAY")
+            || match var("XDG_SESSION_TYPE").as_deref().map(str::trim) {
+                Some("wayland") => true,
+                Some("x11") => false,
+                _ => !set("DISPLAY"),
+            };
+        if !wayland {
+            return Self::X11;
+        }
+        let gnome = var("XDG_CURRENT_DESKTOP")
+            .is_some_and(|desktop| desktop.split(':').any(|d| d.eq_ignore_ascii_case("gnome")));
+        if gnome {
+            Self::Gnome
+        } else {
+            Self::Wayland
+        }
+    }
+
+    /// The fallback chain used when `injectionBackends` is empty.
+    pub fn default_chain(&self) -> Vec<BackendKind> {
+        match self {
+            Self::Wayland => vec![
+                BackendKind::Wtype,
+                BackendKind::Ydotool,
+                BackendKind::Clipboard,
+            ],
+            Self::Gnome => vec![
+                BackendKind::Portal,
+                BackendKind::Ydotool,
+                BackendKind::Clipboard,
+            ],
+            Self::X11 => vec![
+                BackendKind::Xdotool,
+                BackendKind::Ydotool,
+                BackendKind::Clipboard,
+            ],
+        }
+    }
+
+    /// The backend that sends the paste chord for clipboard injection.
+    fn key_backend(&self) -> BackendKind {
+        match self {
+            Self::Wayland => BackendKind::Wtype,
+            Self::Gnome => BackendKind::Portal,
+            Self::X11 => BackendKind::Xdotool,
+        }
+    }
+}
+
+/// One way of getting text and keys into the focused app. Keys are XKB
+/// keysym names (`BackSpace`, `Return`), modifiers as in [`KeyChord`].
+#[async_trait]
+pub trait InjectionBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError>;
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError>;
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError>;
+
+    /// Chord used to paste, for backends that type through the clipboard.
+    fn set_paste_chord(&self, _chord: &KeyChord) {}
+}
+
+pub fn build_backend(kind: BackendKind, display: DisplayServer) -> Box<dyn InjectionBackend> {
+    match kind {
+        BackendKind::Wtype => Box::new(Wtype),
+        BackendKind::Ydotool => Box::new(Ydotool),
+        BackendKind::Xdotool => Box::new(Xdotool),
+        BackendKind::Portal => Box::new(Portal),
+        BackendKind::Ime => Box::new(ImeDirect {
+            framework: ImeFramework::detect(display == DisplayServer::Gnome),
+            ibus: ime::IBusCommitter::default(),
+            keys: build_backend(display.key_backend(), display),
+        }),
+        BackendKind::Clipboard => Box::new(ClipboardPaste {
+            keys: build_backend(display.key_backend(), display),
+            chord: std::sync::Mutex::new(KeyChord::parse("ctrl+v").expect("valid chord")),
+        }),
+    }
+}
+
+/// Wayland virtual-keyboard protocol; not implemented by GNOME's compositor.
+pub struct Wtype;
+
+#[async_trait]
+impl InjectionBackend for Wtype {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Wtype
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        run("wtype", &[text.to_string()], "テキスト注入に失敗しました").await
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        let args: Vec<String> = (0..count)
+            .flat_map(|_| ["-k".to_string(), key.to_string()])
+            .collect();
+        run("wtype", &args, "キー注入に失敗しました").await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        run("wtype", &chord.wtype_args(), "キー注入に失敗しました").await
+    }
+}
+
+/// uinput through the `ydotoold` daemon; works everywhere but only types
+/// US-layout ASCII, so other text is refused and left to the next backend.
+pub struct Ydotool;
+
+#[async_trait]
+impl InjectionBackend for Ydotool {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Ydotool
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        if !text.is_ascii() {
+            return Err(AppError::new(
+                "ydotool は ASCII 以外の文字を入力できません",
+                "ydotool: non-ASCII text",
+            ));
+        }
+        let args = ["type".to_string(), "--".to_string(), text.to_string()];
+        run("ydotool", &args, "テキスト注入に失敗しました").await
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        let code = evdev_code(key)?;
+        let mut args = vec!["key".to_string()];
+        for _ in 0..count {
+            args.extend([format!("{code}:1"), format!("{code}:0")]);
+        }
+        run("ydotool", &args, "キー注入に失敗しました").await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        let mut args = vec!["key".to_string()];
+        args.extend(
+            chord_events(chord, None)?
+                .into_iter()
+                .map(|(code, pressed)| format!("{code}:{}", u8::from(pressed))),
+        );
+        run("ydotool", &args, "キー注入に失敗しました").await
+    }
+}
+
+/// xdg-desktop-portal RemoteDesktop with libei, the way in on GNOME. The
+/// first use shows a permission dialog; later sessions restore the grant.
+/// Like ydotool it sends key codes, but looks them up in the keymap the
+/// compositor hands over, so it types whatever the current layout has keys
+/// for. Other text, such as Japanese, is refused and left to the next
+/// backend.
+pub struct Portal;
+
+#[async_trait]
+impl InjectionBackend for Portal {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Portal
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        portal::send_keys(|keymap| {
+            let keymap = keymap.ok_or_else(|| {
+                AppError::new(
+                    "入力ポータルからキー配列を取得できません",
+                    "portal: no XKB keymap from EIS",
+                )
+            })?;
+            text_events(text, keymap)
+        })
+        .await
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        let code = u32::from(evdev_code(key)?);
+        let events: Vec<_> = (0..count)
+            .flat_map(|_| [(code, true), (code, false)])
+            .collect();
+        portal::send_keys(|_| Ok(events)).await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        portal::send_keys(|keymap| {
+            Ok(chord_events(chord, keymap)?
+                .into_iter()
+                .map(|(code, pressed)| (u32::from(code), pressed))
+                .collect())
+        })
+        .await
+    }
+}
+
+/// Key events typing `text` on `keymap`, holding Shift where needed.
+fn text_events(text: &str, keymap: &Keymap) -> Result<Vec<(u32, bool)>, AppError> {
+    let shift = u32::from(SHIFT);
+    let mut events = Vec::new();
+    for c in text.chars() {
+        let (code, shifted) = keymap.key_for(c).ok_or_else(|| {
+            AppError::new(
+                "入力ポータルでは入力できない文字が含まれています",
+                format!("portal: no key for {c:?} in the keymap"),
+            )
+        })?;
+        let code = u32::from(code);
+        if shifted {
+            events.push((shift, true));
+        }
+        events.extend([(code, true), (code, false)]);
+        if shifted {
+            events.push((shift, false));
+        }
+    }
+    Ok(events)
+}
+
+/// XTEST; reaches X11 and XWayland windows only.
+pub struct Xdotool;
+
+#[async_trait]
+impl InjectionBackend for Xdotool {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Xdotool
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        let args = ["type", "--clearmodifiers", "--", text].map(str::to_string);
+        run("xdotool", &args, "テキスト注入に失敗しました").await
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        let args = [
+            "key",
+            "--clearmodifiers",
+            "--repeat",
+            &count.to_string(),
+            key,
+        ]
+        .map(str::to_string);
+        run("xdotool", &args, "キー注入に失敗しました").await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        let mut parts: Vec<&str> = chord.modifiers.iter().map(|m| x11_modifier(m)).collect();
+        parts.push(&chord.key);
+        let args = ["key", "--clearmodifiers", &parts.join("+")].map(str::to_string);
+        run("xdotool", &args, "キー注入に失敗しました").await
+    }
+}
+
+/// Commits text through IBus, so an active IME neither converts it nor
+/// needs it on the keyboard layout. Fcitx5 has no way to commit for another
+/// process: it is switched to direct input while the display server's key
+/// backend types, which only covers text on the layout.
+pub struct ImeDirect {
+    framework: Option<ImeFramework>,
+    ibus: ime::IBusCommitter,
+    keys: Box<dyn InjectionBackend>,
+}
+
+#[async_trait]
+impl InjectionBackend for ImeDirect {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Ime
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        if self.framework != Some(ImeFramework::Fcitx5) {
+            match self.ibus.commit(text).await {
+                Ok(()) => return Ok(()),
+                Err(err) if self.framework == Some(ImeFramework::IBus) => {
+                    return Err(AppError::new("IBus でテキストを確定できませんでした", err));
+                }
+                Err(err) => tracing::debug!("ime: ibus unavailable: {err}"),
+            }
+        }
+        let parked = ime::park_fcitx5().await;
+        let result = self.keys.type_text(text).await;
+        if let Some(parked) = parked {
+            parked.restore().await;
+        }
+        result
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        self.keys.press_key(key, count).await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        self.keys.press_chord(chord).await
+    }
+}
+
+/// Puts text on the clipboard with `wl-copy` and pastes it with Ctrl+V; keys
+/// go to the display server's key backend. The clipboard is overwritten.
+pub struct ClipboardPaste {
+    keys: Box<dyn InjectionBackend>,
+    chord: std::sync::Mutex<KeyChord>,
+}
+
+#[async_trait]
+impl InjectionBackend for ClipboardPaste {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Clipboard
+    }
+
+    async fn type_text(&self, text: &str) -> Result<(), AppError> {
+        let chord = self.chord.lock().expect("paste chord lock").clone();
+        clipboard::paste(text, self.keys.press_chord(&chord)).await
+    }
+
+    async fn press_key(&self, key: &str, count: u32) -> Result<(), AppError> {
+        self.keys.press_key(key, count).await
+    }
+
+    async fn press_chord(&self, chord: &KeyChord) -> Result<(), AppError> {
+        self.keys.press_chord(chord).await
+    }
+
+    fn set_paste_chord(&self, chord: &KeyChord) {
+        *self.chord.lock().expect("paste chord lock") = chord.clone();
+    }
+}
+
+async fn run(program: &str, args: &[String], user_message: &str) -> Result<(), AppError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| AppError::new(format!("{program} の実行に失敗しました"), e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(AppError::new(
+            user_message,
+            format!(
+                "{program}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn x11_modifier(modifier: &str) -> &str {
+    match modifier {
+        "logo" => "super",
+        "altgr" => "ISO_Level3_Shift",
+        other => other,
+    }
+}
+
+const SHIFT: u16 = 42;
+
+/// Linux input event codes for the keysyms notype sends as key codes.
+const EVDEV_CODES: &[(&str, u16)] = &[
+    ("Escape", 1),
+    ("minus", 12),
+    ("equal", 13),
+    ("BackSpace", 14),
+    ("Tab", 15),
+    ("bracketleft", 26),
+    ("bracketright", 27),
+    ("Return", 28),
+    ("ctrl", 29),
+    ("semicolon", 39),
+    ("apostrophe", 40),
+    ("grave", 41),
+    ("shift", SHIFT),
+    ("backslash", 43),
+    ("comma", 51),
+    ("period", 52),
+    ("slash", 53),
+    ("alt", 56),
+    ("space", 57),
+    ("altgr", 100),
+    ("Home", 102),
+    ("Up", 103),
+    ("Prior", 104),
+    ("Left", 105),
+    ("Right", 106),
+    ("End", 107),
+    ("Down", 108),
+    ("Next", 109),
+    ("Insert", 110),
+    ("Delete", 111),
+    ("logo", 125),
+];
+
+const LETTER_ROWS: [(&str, u16); 3] = [("qwertyuiop", 16), ("asdfghjkl", 30), ("zxcvbnm", 44)];
+
+/// Modifiers down, key down and up, modifiers up in reverse. With a keymap
+/// a character key is looked up there, so Ctrl+V pastes on Dvorak too.
+fn chord_events(chord: &KeyChord, keymap: Option<&Keymap>) -> Result<Vec<(u16, bool)>, AppError> {
+    let modifiers = chord
+        .modifiers
+        .iter()
+        .map(|m| evdev_code(m))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut chars = chord.key.chars();
+    let mapped = match (keymap, chars.next(), chars.next()) {
+        (Some(keymap), Some(c), None) => keymap.key_for(c).map(|(code, _)| code),
+        _ => None,
+    };
+    let key = match mapped {
+        Some(code) => code,
+        None => evdev_code(&chord.key)?,
+    };
+    let mut events: Vec<(u16, bool)> = modifiers.iter().map(|code| (*code, true)).collect();
+    events.extend([(key, true), (key, false)]);
+    events.extend(modifiers.iter().rev().map(|code| (*code, false)));
+    Ok(events)
+}
+
+fn evdev_code(key: &str) -> Result<u16, AppError> {
+    let unsupported = || {
+        AppError::new(
+            "キーコードで送れないキーです",
+            format!("no evdev key code for {key}"),
+        )
+    };
+    if let Some((_, code)) = EVDEV_CODES.iter().find(|(name, _)| *name == key) {
+        return Ok(*code);
+    }
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_digit() => Ok(match c {
+            '0' => 11,
+            d => d as u16 - '1' as u16 + 2,
+        }),
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            let c = c.to_ascii_lowercase();
+            LETTER_ROWS
+                .iter()
+                .find_map(|(row, start)| row.find(c).map(|i| start + i as u16))
+                .ok_or_else(unsupported)
+        }
+        _ => match key.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+            Some(n @ 1..=10) => Ok(58 + n),
+            Some(n @ 11..=12) => Ok(76 + n),
+            _ => Err(unsupported()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_server_follows_the_environment() {
+        let detect = |vars: &[(&str, &str)]| {
+            let vars: Vec<(String, String)> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            DisplayServer::from_env(|name| {
+                vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+            })
+        };
+        assert_eq!(
+            detect(&[("WAYLAND_DISPLAY", "wayland-0"), ("DISPLAY", ":0")]),
+            DisplayServer::Wayland
+        );
+        assert_eq!(
+            detect(&[("XDG_SESSION_TYPE", "x11"), ("DISPLAY", ":0")]),
+            DisplayServer::X11
+        );
+        assert_eq!(detect(&[("DISPLAY", ":1")]), DisplayServer::X11);
+        assert_eq!(detect(&[]), DisplayServer::Wayland);
+        assert_eq!(
+            detect(&[
+                ("XDG_SESSION_TYPE", "wayland"),
+                ("XDG_CURRENT_DESKTOP", "ubuntu:GNOME")
+            ]),
+            DisplayServer::Gnome
+        );
+        assert_eq!(
+            detect(&[("DISPLAY", ":0"), ("XDG_CURRENT_DESKTOP", "GNOME")]),
+            DisplayServer::X11
+        );
+        assert_eq!(DisplayServer::X11.default_chain()[0], BackendKind::Xdotool);
+        assert_eq!(DisplayServer::Gnome.default_chain()[0], BackendKind::Portal);
+    }
+
+    #[test]
+    fn keysyms_map_to_evdev_codes() {
+        let codes: Vec<u16> = ["BackSpace", "Return", "a", "Q", "m", "1", "0", "F1", "F12"]
+            .iter()
+            .map(|k| evdev_code(k).expect("code"))
+            .collect();
+        assert_eq!(codes, [14, 28, 30, 16, 50, 2, 11, 59, 88]);
+        assert!(evdev_code("Hyper_L").is_err());
+        assert!(evdev_code("F13").is_err());
+    }
+
+    #[test]
+    fn portal_keys_follow_the_keymap() {
+        let keymap = Keymap::parse(crate::core::xkb::JIS_SAMPLE);
+        let shift = u32::from(SHIFT);
+        assert_eq!(
+            text_events("@\"", &keymap).expect("events"),
+            [
+                (26, true),
+                (26, false),
+                (shift, true),
+                (3, true),
+                (3, false),
+                (shift, false)
+            ]
+        );
+        assert!(text_events("あ", &keymap).is_err());
+
+        let chord = KeyChord::parse("ctrl+q").expect("chord");
+        assert_eq!(
+            chord_events(&chord, Some(&keymap)).expect("events"),
+            [(29, true), (16, true), (16, false), (29, false)]
+        );
+    }
+}
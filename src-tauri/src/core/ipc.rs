@@ -4,7 +4,7 @@ use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::Mutex;
 use zbus::interface;
 
-use super::app::AppRuntime;
+use super::app::{AppRuntime, RetranscribeOverrides, SessionOverrides};
 use super::error::AppError;
 use super::state::ErrorEvent;
 
@@ -49,29 +49,68 @@ impl IpcController {
         }
     }
 
-    fn toggle_recording_impl(&self) {
-        tracing::info!("ToggleRecording: request received");
+    fn toggle_recording_impl(&self, overrides: SessionOverrides) {
+        tracing::info!("ToggleRecording: request received overrides={overrides:?}");
         let runtime = self.runtime.clone();
         let app = self.app.clone();
         tauri::async_runtime::spawn(async move {
-            match runtime.toggle_recording(app.clone()).await {
+            match runtime.toggle_recording_with(app.clone(), overrides).await {
                 Ok(state) => {
                     tracing::info!("ToggleRecording: done state={state:?}");
                 }
                 Err(err) => {
-                    let _ = app.emit(
-                        "notype://error",
-                        ErrorEvent {
-                            user_message: err.user_message.clone(),
-                            details: err.details.clone(),
-                        },
-                    );
                     tracing::warn!("ToggleRecording: failed to toggle recording: {err}");
+                    emit_ipc_error(&app, err);
                 }
             }
         });
     }
 
+    fn cancel_impl(&self) {
+        tracing::info!("Cancel: request received");
+        let runtime = self.runtime.clone();
+        let app = self.app.clone();
+        tauri::async_runtime::spawn(async move {
+            match runtime.cancel(&app).await {
+                Ok(state) => tracing::info!("Cancel: done state={state:?}"),
+                Err(err) => tracing::warn!("Cancel: failed to cancel session: {err}"),
+            }
+        });
+    }
+
+    fn retranscribe_last_impl(&self, model: String, language: String) {
+        tracing::info!("RetranscribeLast: request received model={model:?} language={language:?}");
+        let runtime = self.runtime.clone();
+        let app = self.app.clone();
+        tauri::async_runtime::spawn(async move {
+            let result = match RetranscribeOverrides::parse(&model, &language) {
+                Ok(overrides) => runtime.retranscribe_last(app.clone(), overrides).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                tracing::warn!("RetranscribeLast: failed: {err}");
+                emit_ipc_error(&app, err);
+            }
+        });
+    }
+
+    fn undo_edit_impl(&self) {
+        tracing::info!("UndoEditSelection: request received");
+        let runtime = self.runtime.clone();
+        let app = self.app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = runtime.undo_edit().await {
+                tracing::warn!("UndoEditSelection: failed: {err}");
+                emit_ipc_error(&app, err);
+            }
+        });
+    }
+
+    fn emit_error(&self, err: AppError) {
+        tracing::warn!("IPC request rejected: {err}");
+        emit_ipc_error(&self.app, err);
+    }
+
     fn quit_impl(&self) {
         tracing::info!("Quit: exiting application by IPC request");
         self.app.exit(0);
@@ -104,7 +143,69 @@ impl IpcService {
 
     #[zbus(name = "ToggleRecording")]
     async fn toggle_recording(&self) {
-        self.controller.lock().await.toggle_recording_impl();
+        self.controller
+            .lock()
+            .await
+            .toggle_recording_impl(SessionOverrides::default());
+    }
+
+    #[zbus(name = "ToggleRecordingTranslate")]
+    async fn toggle_recording_translate(&self) {
+        self.controller
+            .lock()
+            .await
+            .toggle_recording_impl(SessionOverrides::translate());
+    }
+
+    /// Like `ToggleRecording`, but formats this session's text as code.
+    #[zbus(name = "ToggleRecordingCode")]
+    async fn toggle_recording_code(&self) {
+        self.controller
+            .lock()
+            .await
+            .toggle_recording_impl(SessionOverrides::code());
+    }
+
+    /// Starts or stops recording a spoken instruction (e.g. "make this more
+    /// polite") that the LLM applies to the selected text, typing the result
+    /// over the selection.
+    #[zbus(name = "ToggleEditSelection")]
+    async fn toggle_edit_selection(&self) {
+        self.controller
+            .lock()
+            .await
+            .toggle_recording_impl(SessionOverrides::edit_selection());
+    }
+
+    /// Puts back the text the last `ToggleEditSelection` replaced.
+    #[zbus(name = "UndoEditSelection")]
+    async fn undo_edit_selection(&self) {
+        self.controller.lock().await.undo_edit_impl();
+    }
+
+    /// Like `ToggleRecording`, but post-processes this session with the named
+    /// prompt template. An unknown name is reported and nothing starts.
+    #[zbus(name = "ToggleRecordingWithTemplate")]
+    async fn toggle_recording_with_template(&self, name: String) {
+        let controller = self.controller.lock().await;
+        match SessionOverrides::with_template(&name) {
+            Ok(overrides) => controller.toggle_recording_impl(overrides),
+            Err(err) => controller.emit_error(err),
+        }
+    }
+
+    #[zbus(name = "Cancel")]
+    async fn cancel(&self) {
+        self.controller.lock().await.cancel_impl();
+    }
+
+    /// Empty `model` / `language` keep the configured values.
+    #[zbus(name = "RetranscribeLast")]
+    async fn retranscribe_last(&self, model: String, language: String) {
+        self.controller
+            .lock()
+            .await
+            .retranscribe_last_impl(model, language);
     }
 
     #[zbus(name = "Quit")]
@@ -113,7 +214,24 @@ impl IpcService {
     }
 }
 
+fn emit_ipc_error(app: &AppHandle, err: AppError) {
+    let _ = app.emit(
+        "notype://error",
+        ErrorEvent {
+            user_message: err.user_message,
+            details: err.details,
+        },
+    );
+}
+
 pub async fn try_call_existing(method: &str) -> Result<bool, AppError> {
+    try_call_existing_with(method, &()).await
+}
+
+pub async fn try_call_existing_with<B>(method: &str, body: &B) -> Result<bool, AppError>
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
     let conn = match zbus::Connection::session().await {
         Ok(c) => c,
         Err(_) => return Ok(false),
@@ -125,7 +243,7 @@ pub async fn try_call_existing(method: &str) -> Result<bool, AppError> {
             OBJECT_PATH,
             Some(INTERFACE_NAME),
             method,
-            &(),
+            body,
         )
         .await;
 
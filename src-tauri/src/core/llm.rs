@@ -0,0 +1,777 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::config::AppConfig;
+use super::error::AppError;
+use super::redact::{record_audit, Redaction, Redactor};
+use super::selection::edit_prompt;
+use super::stream::{ChunkBuffer, SseDecoder};
+use super::templates::{PromptTemplate, PromptVars, RenderedPrompt};
+
+const OPENAI_DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const PLACEHOLDER_GUARD: &str =
+    "\n\nKeep placeholders such as [EMAIL_1] or [NAME_2] exactly as they are.";
+const GEMINI_DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Receives streamed text as it arrives.
+pub type OnDelta<'a> = dyn FnMut(&str) + Send + 'a;
+
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn complete(&self, system: &str, user: &str) -> Result<String, AppError>;
+
+    /// Like `complete`, but passes the text to `on_delta` piece by piece as
+    /// it arrives. Fails when nothing arrives for `idle` or the stream ends
+    /// without the provider's end marker.
+    async fn stream(
+        &self,
+        system: &str,
+        user: &str,
+        idle: Duration,
+        on_delta: &mut OnDelta<'_>,
+    ) -> Result<String, AppError>;
+}
+
+/// `/chat/completions` as served by OpenAI, llama.cpp's server, Ollama and
+/// most other local runtimes.
+pub struct OpenAiCompatible {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiCompatible {
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_or_default(base_url, OPENAI_DEFAULT_BASE_URL),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn complete(&self, system: &str, user: &str) -> Result<String, AppError> {
+        let response = send_json(self.request(system, user, false)).await?;
+        response["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| unexpected_response(&response))
+    }
+
+    async fn stream(
+        &self,
+        system: &str,
+        user: &str,
+        idle: Duration,
+        on_delta: &mut OnDelta<'_>,
+    ) -> Result<String, AppError> {
+        let response = send(self.request(system, user, true)).await?;
+        read_sse(response, idle, on_delta, |event: &Value| {
+            let choice = &event["choices"][0];
+            (
+                choice["delta"]["content"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                choice["finish_reason"].is_string(),
+            )
+        })
+        .await
+    }
+}
+
+impl OpenAiCompatible {
+    fn request(&self, system: &str, user: &str, stream: bool) -> reqwest::RequestBuilder {
+        let body = json!({
+            "model": self.model,
+            "temperature": 0,
+            "stream": stream,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": user },
+            ],
+        });
+        let request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("content-type", "application/json")
+            .body(body.to_string());
+        // Local servers usually run without a key.
+        if self.api_key.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.api_key)
+        }
+    }
+}
+
+/// Gemini `generateContent` REST API.
+pub struct Gemini {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl Gemini {
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_or_default(base_url, GEMINI_DEFAULT_BASE_URL),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for Gemini {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    async fn complete(&self, system: &str, user: &str) -> Result<String, AppError> {
+        let response = send_json(self.request(system, user, "generateContent")).await?;
+        let parts = response["candidates"][0]["content"]["parts"]
+            .as_array()
+            .ok_or_else(|| unexpected_response(&response))?;
+        Ok(parts.iter().filter_map(|p| p["text"].as_str()).collect())
+    }
+
+    async fn stream(
+        &self,
+        system: &str,
+        user: &str,
+        idle: Duration,
+        on_delta: &mut OnDelta<'_>,
+    ) -> Result<String, AppError> {
+        let response = send(self.request(system, user, "streamGenerateContent?alt=sse")).await?;
+        read_sse(response, idle, on_delta, |event: &Value| {
+            let candidate = &event["candidates"][0];
+            let text = candidate["content"]["parts"]
+                .as_array()
+                .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect())
+                .unwrap_or_default();
+            (text, candidate["finishReason"].is_string())
+        })
+        .await
+    }
+}
+
+impl Gemini {
+    fn request(&self, system: &str, user: &str, method: &str) -> reqwest::RequestBuilder {
+        let body = json!({
+            "systemInstruction": { "parts": [{ "text": system }] },
+            "contents": [{ "role": "user", "parts": [{ "text": user }] }],
+            "generationConfig": { "temperature": 0 },
+        });
+        self.client
+            .post(format!("{}/models/{}:{method}", self.base_url, self.model))
+            .header("content-type", "application/json")
+            .header("x-goog-api-key", &self.api_key)
+            .body(body.to_string())
+    }
+}
+
+pub fn provider_from_config(config: &AppConfig) -> Result<Box<dyn LlmProvider>, AppError> {
+    let base_url = config.llm_api_base_url.trim();
+    let api_key = config.llm_api_key.trim();
+    let model = config.llm_model.trim();
+    if model.is_empty() {
+        return Err(AppError::new(
+            "LLM のモデル名が設定されていません",
+            "llm_model is empty",
+        ));
+    }
+
+    match config.llm_provider.trim().to_ascii_lowercase().as_str() {
+        "" | "openai" | "openai-compatible" | "ollama" | "llamacpp" | "llama.cpp" => {
+            Ok(Box::new(OpenAiCompatible::new(base_url, api_key, model)))
+        }
+        "gemini" => Ok(Box::new(Gemini::new(base_url, api_key, model))),
+        other => Err(AppError::new(
+            "未対応の LLM プロバイダです",
+            format!("unknown llm_provider: {other}"),
+        )),
+    }
+}
+
+/// Runs LLM post-processing with `template` when enabled. Any error, timeout
+/// or empty answer falls back to the raw transcript so dictation never gets
+/// lost.
+pub async fn postprocess_or_raw(
+    config: &AppConfig,
+    template: &PromptTemplate,
+    vars: PromptVars,
+) -> String {
+    let raw = vars.transcript.clone();
+    let Some(request) = Outbound::prepare(config, template, vars) else {
+        return raw;
+    };
+    let timeout = Duration::from_millis(config.llm_timeout_ms.max(1));
+    let started = Instant::now();
+    let prompt = &request.prompt;
+    let result = tokio::time::timeout(
+        timeout,
+        request.provider.complete(&prompt.system, &prompt.user),
+    )
+    .await
+    .unwrap_or_else(|_| Err(timed_out(timeout)));
+    request.accept(result, started, raw)
+}
+
+/// Like [`postprocess_or_raw`], but streams the answer: it is sent to
+/// `chunks` in typeable pieces as it arrives, with redacted entities already
+/// put back, and `llm_timeout_ms` applies between pieces. A result other
+/// than what was sent (the raw transcript after a failure midway) means the
+/// streamed text has to be replaced.
+pub async fn postprocess_streaming(
+    config: &AppConfig,
+    template: &PromptTemplate,
+    vars: PromptVars,
+    chunks: &UnboundedSender<String>,
+) -> String {
+    let raw = vars.transcript.clone();
+    let Some(request) = Outbound::prepare(config, template, vars) else {
+        return raw;
+    };
+    let timeout = Duration::from_millis(config.llm_timeout_ms.max(1));
+    let started = Instant::now();
+    let mut buffer = ChunkBuffer::default();
+    let redaction = &request.redaction;
+    let mut forward = |delta: &str| {
+        if let Some(chunk) = buffer.push(delta) {
+            let _ = chunks.send(redaction.fill(&chunk));
+        }
+    };
+    let prompt = &request.prompt;
+    let result = request
+        .provider
+        .stream(&prompt.system, &prompt.user, timeout, &mut forward)
+        .await;
+    if result.is_ok() {
+        if let Some(chunk) = buffer.finish() {
+            let _ = chunks.send(redaction.fill(&chunk));
+        }
+    }
+    request.accept(result, started, raw)
+}
+
+/// Applies a spoken `instruction` to `selection` and returns the rewritten
+/// text. Both are redacted like transcripts. Errors are returned rather than
+/// falling back, as there is nothing sensible to type instead.
+pub async fn rewrite_selection(
+    config: &AppConfig,
+    instruction: &str,
+    selection: &str,
+) -> Result<String, AppError> {
+    ensure_llm_enabled(config)?;
+    let provider = provider_from_config(config)?;
+    let mut redaction = Redaction::default();
+    let (instruction, selection) = if config.redaction_enabled {
+        let redactor = Redactor::from_config(config);
+        let redacted = (
+            redactor.redact(instruction, &mut redaction),
+            redactor.redact(selection, &mut redaction),
+        );
+        record_audit(provider.name(), &redaction);
+        redacted
+    } else {
+        (instruction.to_string(), selection.to_string())
+    };
+
+    let mut prompt = edit_prompt(&instruction, &selection);
+    if !redaction.is_empty() {
+        prompt.system.push_str(PLACEHOLDER_GUARD);
+    }
+    let timeout = Duration::from_millis(config.llm_timeout_ms.max(1));
+    let edited = tokio::time::timeout(timeout, provider.complete(&prompt.system, &prompt.user))
+        .await
+        .unwrap_or_else(|_| Err(timed_out(timeout)))?;
+    let edited = edited.trim();
+    if edited.is_empty() {
+        return Err(AppError::new(
+            "LLM が空の応答を返しました",
+            format!("{} returned empty text", provider.name()),
+        ));
+    }
+    // An instruction may legitimately drop an entity ("remove the email"),
+    // so unlike transcripts a missing placeholder is not an error.
+    Ok(redaction.fill(edited))
+}
+
+/// Nothing leaves the machine unless the user turned the LLM on (NFR-07).
+pub fn ensure_llm_enabled(config: &AppConfig) -> Result<(), AppError> {
+    if config.llm_postprocess_enabled {
+        return Ok(());
+    }
+    Err(AppError::new(
+        "LLM 後処理が無効です。設定で有効にしてから使ってください",
+        "llm_postprocess_enabled is off",
+    ))
+}
+
+/// A post-processing request ready to send.
+struct Outbound {
+    provider: Box<dyn LlmProvider>,
+    prompt: RenderedPrompt,
+    redaction: Redaction,
+    /// The transcript as sent, placeholders included.
+    sent: String,
+}
+
+impl Outbound {
+    /// `None` when post-processing is off or the provider is unusable.
+    fn prepare(
+        config: &AppConfig,
+        template: &PromptTemplate,
+        mut vars: PromptVars,
+    ) -> Option<Self> {
+        if !config.llm_postprocess_enabled || vars.transcript.trim().is_empty() {
+            return None;
+        }
+
+        let provider = match provider_from_config(config) {
+            Ok(provider) => provider,
+            Err(err) => {
+                tracing::warn!("llm: provider unavailable, using raw text: {}", err.details);
+                return None;
+            }
+        };
+
+        // Nothing identifying leaves the machine: entities become placeholders
+        // and are put back into the response.
+        let mut redaction = Redaction::default();
+        if config.redaction_enabled {
+            let redactor = Redactor::from_config(config);
+            vars.transcript = redactor.redact(&vars.transcript, &mut redaction);
+            vars.previous = redactor.redact(&vars.previous, &mut redaction);
+            record_audit(provider.name(), &redaction);
+        }
+
+        let mut prompt = template.render(&vars);
+        if !redaction.is_empty() {
+            prompt.system.push_str(PLACEHOLDER_GUARD);
+        }
+        Some(Self {
+            provider,
+            prompt,
+            redaction,
+            sent: vars.transcript,
+        })
+    }
+
+    fn accept(&self, result: Result<String, AppError>, started: Instant, raw: String) -> String {
+        let name = self.provider.name();
+        match result {
+            Ok(processed) if !processed.trim().is_empty() => {
+                match self.redaction.restore(processed.trim(), &self.sent) {
+                    Some(restored) => {
+                        tracing::info!(
+                            "llm: {name} post-processed in {} ms",
+                            started.elapsed().as_millis()
+                        );
+                        restored
+                    }
+                    None => {
+                        tracing::warn!(
+                            "llm: {name} dropped a redaction placeholder, using raw text"
+                        );
+                        raw
+                    }
+                }
+            }
+            Ok(_) => {
+                tracing::warn!("llm: {name} returned empty text, using raw text");
+                raw
+            }
+            Err(err) => {
+                tracing::warn!("llm: {name} failed, using raw text: {}", err.details);
+                raw
+            }
+        }
+    }
+}
+
+fn timed_out(timeout: Duration) -> AppError {
+    AppError::new(
+        "LLM の応答がタイムアウトしました",
+        format!("timed out after {} ms", timeout.as_millis()),
+    )
+}
+
+fn base_or_default(base_url: &str, default: &str) -> String {
+    let base = if base_url.is_empty() {
+        default
+    } else {
+        base_url
+    };
+    base.trim_end_matches('/').to_string()
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response, AppError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| AppError::new("LLM への接続に失敗しました", e.to_string()))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(AppError::new(
+            "LLM がエラーを返しました",
+            format!("status {status}: {body}"),
+        ));
+    }
+    Ok(response)
+}
+
+async fn send_json(request: reqwest::RequestBuilder) -> Result<Value, AppError> {
+    let body = send(request)
+        .await?
+        .text()
+        .await
+        .map_err(|e| AppError::new("LLM の応答の読み込みに失敗しました", e.to_string()))?;
+    serde_json::from_str(&body)
+        .map_err(|e| AppError::new("LLM の応答を解析できませんでした", e.to_string()))
+}
+
+/// Reads a server-sent event stream. `delta` turns each JSON event into its
+/// text and whether it is the last one; OpenAI's `[DONE]` also ends it.
+async fn read_sse(
+    response: reqwest::Response,
+    idle: Duration,
+    on_delta: &mut OnDelta<'_>,
+    delta: fn(&Value) -> (String, bool),
+) -> Result<String, AppError> {
+    let mut body = response.bytes_stream();
+    let mut decoder = SseDecoder::default();
+    let mut text = String::new();
+    loop {
+        let next = tokio::time::timeout(idle, body.next())
+            .await
+            .map_err(|_| timed_out(idle))?;
+        let (events, end_of_body) = match next {
+            Some(Ok(bytes)) => (decoder.push(&bytes), false),
+            Some(Err(e)) => {
+                return Err(AppError::new(
+                    "LLM の応答の読み込みに失敗しました",
+                    e.to_string(),
+                ))
+            }
+            None => (decoder.finish().into_iter().collect(), true),
+        };
+        for data in events {
+            if data == "[DONE]" {
+                return Ok(text);
+            }
+            let event: Value = serde_json::from_str(&data)
+                .map_err(|e| AppError::new("LLM の応答を解析できませんでした", e.to_string()))?;
+            let (piece, last) = delta(&event);
+            if !piece.is_empty() {
+                on_delta(&piece);
+                text.push_str(&piece);
+            }
+            if last {
+                return Ok(text);
+            }
+        }
+        if end_of_body {
+            return Err(AppError::new(
+                "LLM の応答が途中で途切れました",
+                "stream ended without a finish marker",
+            ));
+        }
+    }
+}
+
+fn unexpected_response(response: &Value) -> AppError {
+    AppError::new(
+        "LLM の応答を解析できませんでした",
+        format!("unexpected response: {response}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::templates::builtin_templates;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    /// One-shot HTTP server that answers with `status`/`body` after `delay`
+    /// and hands back the raw request it received.
+    async fn mock_server(
+        status: u16,
+        body: &'static str,
+        delay: Duration,
+    ) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let base_url = format!("http://{}", listener.local_addr().expect("addr"));
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = socket.read(&mut buf).await.expect("read");
+                raw.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&raw).to_string();
+                if let Some(header_end) = text.find("\r\n\r\n") {
+                    let length = text[..header_end]
+                        .lines()
+                        .find_map(|l| {
+                            let (name, value) = l.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if raw.len() >= header_end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let _ = tx.send(String::from_utf8_lossy(&raw).to_string());
+
+            tokio::time::sleep(delay).await;
+            let response = format!(
+                "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+
+        (base_url, rx)
+    }
+
+    fn template() -> PromptTemplate {
+        builtin_templates().remove(0)
+    }
+
+    fn vars(transcript: &str) -> PromptVars {
+        PromptVars {
+            transcript: transcript.to_string(),
+            ..PromptVars::default()
+        }
+    }
+
+    fn config(provider: &str, base_url: &str) -> AppConfig {
+        AppConfig {
+            llm_postprocess_enabled: true,
+            llm_provider: provider.to_string(),
+            llm_api_base_url: base_url.to_string(),
+            llm_api_key: "secret".to_string(),
+            llm_model: "test-model".to_string(),
+            ..AppConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn openai_compatible_sends_chat_completion() {
+        let (base_url, request) = mock_server(
+            200,
+            r#"{"choices":[{"message":{"role":"assistant","content":" 明日は、晴れです。 "}}]}"#,
+            Duration::ZERO,
+        )
+        .await;
+
+        let text = postprocess_or_raw(
+            &config("openai", &base_url),
+            &template(),
+            vars("明日は晴れです"),
+        )
+        .await;
+        assert_eq!(text, "明日は、晴れです。");
+
+        let request = request.await.expect("request");
+        assert!(request.starts_with("POST /chat/completions "));
+        assert!(request.contains("authorization: Bearer secret"));
+        assert!(request.contains(r#""model":"test-model""#));
+        assert!(request.contains("明日は晴れです"));
+    }
+
+    #[tokio::test]
+    async fn gemini_sends_generate_content() {
+        let (base_url, request) = mock_server(
+            200,
+            r#"{"candidates":[{"content":{"parts":[{"text":"Hello, "},{"text":"world."}]}}]}"#,
+            Duration::ZERO,
+        )
+        .await;
+
+        let text = postprocess_or_raw(
+            &config("gemini", &base_url),
+            &template(),
+            vars("hello world"),
+        )
+        .await;
+        assert_eq!(text, "Hello, world.");
+
+        let request = request.await.expect("request");
+        assert!(request.starts_with("POST /models/test-model:generateContent "));
+        assert!(request.contains("x-goog-api-key: secret"));
+        assert!(request.contains("systemInstruction"));
+    }
+
+    #[tokio::test]
+    async fn error_status_falls_back_to_raw_text() {
+        let (base_url, _request) = mock_server(500, r#"{"error":"boom"}"#, Duration::ZERO).await;
+        let text =
+            postprocess_or_raw(&config("openai", &base_url), &template(), vars("raw text")).await;
+        assert_eq!(text, "raw text");
+    }
+
+    #[tokio::test]
+    async fn timeout_falls_back_to_raw_text() {
+        let (base_url, _request) = mock_server(
+            200,
+            r#"{"choices":[{"message":{"content":"late"}}]}"#,
+            Duration::from_secs(5),
+        )
+        .await;
+        let cfg = AppConfig {
+            llm_timeout_ms: 100,
+            ..config("openai", &base_url)
+        };
+        assert_eq!(
+            postprocess_or_raw(&cfg, &template(), vars("raw text")).await,
+            "raw text"
+        );
+    }
+
+    async fn stream(cfg: &AppConfig, transcript: &str) -> (String, Vec<String>) {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let text = postprocess_streaming(cfg, &template(), vars(transcript), &tx).await;
+        drop(tx);
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk);
+        }
+        (text, chunks)
+    }
+
+    #[tokio::test]
+    async fn openai_stream_arrives_in_chunks() {
+        let (base_url, request) = mock_server(
+            200,
+            concat!(
+                "data: {\"choices\":[{\"delta\":{\"content\":\" 明日は\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"、晴れ\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"です。\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ),
+            Duration::ZERO,
+        )
+        .await;
+
+        let (text, chunks) = stream(&config("openai", &base_url), "明日は晴れです").await;
+        assert_eq!(text, "明日は、晴れです。");
+        assert_eq!(chunks.concat(), text);
+        assert_eq!(chunks[0], "明日は");
+        assert!(request.await.expect("request").contains(r#""stream":true"#));
+    }
+
+    #[tokio::test]
+    async fn gemini_stream_uses_sse_endpoint() {
+        let (base_url, request) = mock_server(
+            200,
+            concat!(
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hello, \"}]}}]}\r\n\r\n",
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"world.\"}]},\"finishReason\":\"STOP\"}]}\r\n\r\n",
+            ),
+            Duration::ZERO,
+        )
+        .await;
+
+        let (text, chunks) = stream(&config("gemini", &base_url), "hello world").await;
+        assert_eq!(text, "Hello, world.");
+        assert_eq!(chunks.concat(), text);
+        let request = request.await.expect("request");
+        assert!(request.starts_with("POST /models/test-model:streamGenerateContent?alt=sse "));
+    }
+
+    #[tokio::test]
+    async fn broken_stream_falls_back_to_raw_text() {
+        let (base_url, _request) = mock_server(
+            200,
+            "data: {\"choices\":[{\"delta\":{\"content\":\"half of it \"}}]}\n\n",
+            Duration::ZERO,
+        )
+        .await;
+
+        let (text, chunks) = stream(&config("openai", &base_url), "raw text").await;
+        assert_eq!(text, "raw text");
+        assert_eq!(chunks, ["half of it"]);
+    }
+
+    #[tokio::test]
+    async fn selection_is_rewritten_by_instruction() {
+        let (base_url, request) = mock_server(
+            200,
+            r#"{"choices":[{"message":{"content":"明日お越しいただけますか。\n"}}]}"#,
+            Duration::ZERO,
+        )
+        .await;
+        let edited = rewrite_selection(&config("openai", &base_url), "もっと丁寧に", "明日来て")
+            .await
+            .expect("edited");
+        assert_eq!(edited, "明日お越しいただけますか。");
+        let request = request.await.expect("request");
+        assert!(request.contains("<instruction>"));
+        assert!(request.contains("明日来て"));
+
+        let unknown = config("no-such-provider", "http://127.0.0.1:9");
+        assert!(rewrite_selection(&unknown, "translate", "text")
+            .await
+            .is_err());
+
+        // Refused before anything is sent when the user has not opted in.
+        let (base_url, request) = mock_server(200, "{}", Duration::ZERO).await;
+        let disabled = AppConfig {
+            llm_postprocess_enabled: false,
+            ..config("openai", &base_url)
+        };
+        assert!(rewrite_selection(&disabled, "translate", "secret text")
+            .await
+            .is_err());
+        assert!(tokio::time::timeout(Duration::from_millis(100), request)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn disabled_or_misconfigured_keeps_raw_text() {
+        let disabled = AppConfig {
+            llm_postprocess_enabled: false,
+            ..config("openai", "http://127.0.0.1:9")
+        };
+        assert_eq!(
+            postprocess_or_raw(&disabled, &template(), vars("raw")).await,
+            "raw"
+        );
+
+        let unknown = config("no-such-provider", "http://127.0.0.1:9");
+        assert_eq!(
+            postprocess_or_raw(&unknown, &template(), vars("raw")).await,
+            "raw"
+        );
+    }
+}
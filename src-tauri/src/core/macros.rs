@@ -0,0 +1,264 @@
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::config::AppConfig;
+use super::dictionary::normalize_trigger;
+use super::error::AppError;
+
+/// Modifier names understood by `wtype -M`, with common aliases.
+const MODIFIERS: &[(&str, &str)] = &[
+    ("ctrl", "ctrl"),
+    ("control", "ctrl"),
+    ("shift", "shift"),
+    ("alt", "alt"),
+    ("altgr", "altgr"),
+    ("super", "logo"),
+    ("logo", "logo"),
+    ("win", "logo"),
+    ("meta", "logo"),
+];
+
+/// What a macro does when its phrase is the whole utterance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum MacroAction {
+    /// Key chords pressed in order, e.g. `["ctrl+s"]` or `["ctrl+k", "ctrl+c"]`.
+    Keys { keys: Vec<String> },
+    /// A program listed in `macroAllowedCommands`, run without a shell.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceMacro {
+    pub phrase: String,
+    #[serde(flatten)]
+    pub action: MacroAction,
+}
+
+/// One key press with held modifiers, in `wtype` terms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    pub modifiers: Vec<&'static str>,
+    pub key: String,
+}
+
+impl KeyChord {
+    /// Parses `ctrl+shift+t`; the last part is the key (a character or an
+    /// XKB keysym name such as `Return` or `F5`).
+    pub fn parse(chord: &str) -> Option<Self> {
+        let parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+        let (key, modifiers) = parts.split_last()?;
+        if key.is_empty() {
+            return None;
+        }
+        let modifiers = modifiers
+            .iter()
+            .map(|m| {
+                MODIFIERS
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(m))
+                    .map(|(_, wtype)| *wtype)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // Ctrl+S, not Ctrl+Shift+S: a lone letter is its lowercase keysym.
+        let key = if key.len() == 1 {
+            key.to_ascii_lowercase()
+        } else {
+            key.to_string()
+        };
+        Some(Self { modifiers, key })
+    }
+
+    /// Arguments for `wtype` that hold the modifiers around the key.
+    pub fn wtype_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for modifier in &self.modifiers {
+            args.extend(["-M".to_string(), modifier.to_string()]);
+        }
+        args.extend(["-k".to_string(), self.key.clone()]);
+        for modifier in self.modifiers.iter().rev() {
+            args.extend(["-m".to_string(), modifier.to_string()]);
+        }
+        args
+    }
+}
+
+/// A resolved macro step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroStep {
+    Chord(KeyChord),
+    Run { program: String, args: Vec<String> },
+}
+
+impl MacroStep {
+    /// Human-readable form for logs and the dry-run event.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Chord(chord) => {
+                let mut parts: Vec<&str> = chord.modifiers.clone();
+                parts.push(&chord.key);
+                format!("keys: {}", parts.join("+"))
+            }
+            Self::Run { program, args } => {
+                let mut line = vec![program.as_str()];
+                line.extend(args.iter().map(String::as_str));
+                format!("run: {}", line.join(" "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroTable {
+    macros: Vec<VoiceMacro>,
+    allowed_commands: Vec<String>,
+}
+
+impl MacroTable {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            macros: config.macros.clone(),
+            allowed_commands: config.macro_allowed_commands.clone(),
+        }
+    }
+
+    /// The steps of the macro whose phrase is the whole of `text` (case and
+    /// trailing punctuation ignored), or an error when it is misconfigured.
+    pub fn resolve(&self, text: &str) -> Option<Result<Vec<MacroStep>, AppError>> {
+        let spoken = normalize_trigger(text);
+        if spoken.is_empty() {
+            return None;
+        }
+        let found = self
+            .macros
+            .iter()
+            .find(|m| normalize_trigger(&m.phrase) == spoken)?;
+        Some(self.steps(found))
+    }
+
+    fn steps(&self, found: &VoiceMacro) -> Result<Vec<MacroStep>, AppError> {
+        match &found.action {
+            MacroAction::Keys { keys } => keys
+                .iter()
+                .map(|chord| {
+                    KeyChord::parse(chord).map(MacroStep::Chord).ok_or_else(|| {
+                        AppError::new(
+                            "マクロのキー指定が正しくありません",
+                            format!("{}: {chord}", found.phrase),
+                        )
+                    })
+                })
+                .collect(),
+            MacroAction::Command { program, args } => {
+                if !self.allowed_commands.iter().any(|a| a == program) {
+                    return Err(AppError::new(
+                        "許可されていないコマンドはマクロから実行できません",
+                        format!("{program} is not in macroAllowedCommands"),
+                    ));
+                }
+                Ok(vec![MacroStep::Run {
+                    program: program.clone(),
+                    args: args.clone(),
+                }])
+            }
+        }
+    }
+}
+
+/// Starts `program` detached from notype's stdio; its exit is only logged.
+pub fn spawn_command(program: &str, args: &[String]) -> Result<(), AppError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            AppError::new(
+                "マクロのコマンドを起動できませんでした",
+                format!("{program}: {e}"),
+            )
+        })?;
+    let program = program.to_string();
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) => tracing::info!("macro command {program} exited with {status}"),
+            Err(err) => tracing::warn!("macro command {program}: {err}"),
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> MacroTable {
+        MacroTable::from_config(&AppConfig {
+            macros: vec![
+                VoiceMacro {
+                    phrase: "保存".to_string(),
+                    action: MacroAction::Keys {
+                        keys: vec!["Ctrl+S".to_string()],
+                    },
+                },
+                VoiceMacro {
+                    phrase: "Run tests".to_string(),
+                    action: MacroAction::Command {
+                        program: "cargo".to_string(),
+                        args: vec!["test".to_string()],
+                    },
+                },
+                VoiceMacro {
+                    phrase: "wipe".to_string(),
+                    action: MacroAction::Command {
+                        program: "rm".to_string(),
+                        args: vec!["-rf".to_string()],
+                    },
+                },
+            ],
+            macro_allowed_commands: vec!["cargo".to_string()],
+            ..AppConfig::default()
+        })
+    }
+
+    #[test]
+    fn chords_become_wtype_modifier_sequences() {
+        let chord = KeyChord::parse("ctrl+alt+T").expect("chord");
+        assert_eq!(
+            chord.wtype_args(),
+            ["-M", "ctrl", "-M", "alt", "-k", "t", "-m", "alt", "-m", "ctrl"]
+        );
+        assert_eq!(
+            KeyChord::parse("super+Return").expect("chord").key,
+            "Return"
+        );
+        assert_eq!(KeyChord::parse("hyper+s"), None);
+        assert_eq!(KeyChord::parse("ctrl+"), None);
+    }
+
+    #[test]
+    fn only_whole_utterances_match() {
+        let table = table();
+        let steps = table.resolve("保存。").expect("match").expect("valid");
+        assert_eq!(steps[0].describe(), "keys: ctrl+s");
+        let steps = table.resolve("run tests.").expect("match").expect("valid");
+        assert_eq!(steps[0].describe(), "run: cargo test");
+        assert!(table.resolve("保存して").is_none());
+        assert!(table.resolve("ファイルを保存").is_none());
+        assert!(table.resolve("").is_none());
+    }
+
+    #[test]
+    fn commands_must_be_allowed() {
+        let err = table().resolve("wipe").expect("match").unwrap_err();
+        assert!(err.details.contains("rm"));
+    }
+}
@@ -1,8 +1,33 @@
 pub mod app;
 pub mod audio;
+pub mod benchmark;
+pub mod clipboard;
+pub mod codefmt;
+pub mod commands;
 pub mod config;
+pub mod dictionary;
+pub mod eis;
 pub mod error;
+pub mod filler;
+pub mod filter;
+pub mod focus;
+pub mod ime;
 pub mod inject;
+pub mod inject_backend;
 pub mod ipc;
+pub mod llm;
+pub mod macros;
+pub mod normalize;
+pub mod numbers;
+pub mod pipeline;
+pub mod portal;
+pub mod punctuate;
+pub mod redact;
+pub mod secret;
+pub mod selection;
 pub mod state;
+pub mod stream;
 pub mod stt;
+pub mod templates;
+pub mod vocab;
+pub mod xkb;
@@ -0,0 +1,327 @@
+use super::config::{AppConfig, CjkLatinSpacing, JapanesePunctuation, WidthPreference};
+
+/// Offset between ASCII and the Unicode full-width forms (U+FF01..U+FF5E).
+const FULLWIDTH_OFFSET: u32 = 0xFEE0;
+
+/// ASCII punctuation with a full-width counterpart used in Japanese text.
+const WIDTH_PUNCTUATION: &[(char, char)] = &[
+    ('!', '！'),
+    ('?', '？'),
+    (':', '：'),
+    (';', '；'),
+    ('(', '（'),
+    (')', '）'),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    pub alnum_width: WidthPreference,
+    pub punctuation_width: WidthPreference,
+    pub japanese_punctuation: JapanesePunctuation,
+    pub cjk_latin_spacing: CjkLatinSpacing,
+}
+
+impl NormalizeOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            alnum_width: config.alnum_width,
+            punctuation_width: config.punctuation_width,
+            japanese_punctuation: config.japanese_punctuation,
+            cjk_latin_spacing: config.cjk_latin_spacing,
+        }
+    }
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self::from_config(&AppConfig::default())
+    }
+}
+
+/// Kana, kanji, 々 and half-width katakana. Punctuation is not included.
+pub fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3005 | 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0xFF66..=0xFF9F)
+}
+
+/// CJK symbols and full-width punctuation (、。「」！？（） and friends).
+pub fn is_cjk_punct(c: char) -> bool {
+    !is_cjk(c)
+        && matches!(c as u32,
+            0x3001..=0x303F | 0xFF01..=0xFF0F | 0xFF1A..=0xFF20 | 0xFF3B..=0xFF40 | 0xFF5B..=0xFF65)
+}
+
+fn is_wide(c: char) -> bool {
+    is_cjk(c) || is_cjk_punct(c)
+}
+
+fn is_latin(c: char) -> bool {
+    c.is_ascii_alphanumeric() || to_half_alnum(c).is_some()
+}
+
+/// Joins non-empty trimmed lines. Lines meeting at Japanese text are joined
+/// directly; anything else gets a single space.
+pub fn join_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let (Some(a), Some(b)) = (out.chars().last(), line.chars().next()) {
+            if !is_wide(a) && !is_wide(b) {
+                out.push(' ');
+            }
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Cleans up a final transcript: joins lines, normalizes character widths
+/// and Japanese punctuation, collapses duplicate punctuation and fixes
+/// spacing around Japanese text.
+pub fn normalize_text(text: &str, options: &NormalizeOptions) -> String {
+    let joined = join_lines(text);
+    let has_cjk = joined.chars().any(is_cjk);
+    let chars: Vec<char> = joined
+        .chars()
+        .map(|c| if c == '\u{3000}' { ' ' } else { c })
+        .map(|c| convert_alnum(c, options.alnum_width, has_cjk))
+        .collect();
+    let chars = convert_punctuation(&chars, options);
+    let chars = collapse_punctuation(&chars);
+    apply_spacing(&chars, options.cjk_latin_spacing)
+        .trim()
+        .to_string()
+}
+
+fn to_half_alnum(c: char) -> Option<char> {
+    matches!(c, '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ')
+        .then(|| char::from_u32(c as u32 - FULLWIDTH_OFFSET))
+        .flatten()
+}
+
+fn convert_alnum(c: char, width: WidthPreference, has_cjk: bool) -> char {
+    match width {
+        WidthPreference::Half => to_half_alnum(c).unwrap_or(c),
+        // Full-width Latin only makes sense inside Japanese text.
+        WidthPreference::Full if has_cjk && c.is_ascii_alphanumeric() => {
+            char::from_u32(c as u32 + FULLWIDTH_OFFSET).unwrap_or(c)
+        }
+        _ => c,
+    }
+}
+
+fn neighbour(chars: &[char], i: usize, forward: bool) -> Option<char> {
+    if forward {
+        chars[i + 1..].iter().copied().find(|c| !c.is_whitespace())
+    } else {
+        chars[..i]
+            .iter()
+            .rev()
+            .copied()
+            .find(|c| !c.is_whitespace())
+    }
+}
+
+fn convert_punctuation(chars: &[char], options: &NormalizeOptions) -> Vec<char> {
+    (0..chars.len())
+        .map(|i| {
+            let c = chars[i];
+            let after_cjk = neighbour(chars, i, false).is_some_and(is_cjk);
+
+            let c = match (options.japanese_punctuation, c) {
+                (JapanesePunctuation::Touten, '，' | '､') => '、',
+                (JapanesePunctuation::Touten, '．' | '｡') => '。',
+                (JapanesePunctuation::Touten, ',') if after_cjk => '、',
+                (JapanesePunctuation::Touten, '.') if after_cjk => '。',
+                (JapanesePunctuation::Comma, '、' | '､') => '，',
+                (JapanesePunctuation::Comma, '。' | '｡') => '．',
+                (JapanesePunctuation::Comma, ',') if after_cjk => '，',
+                (JapanesePunctuation::Comma, '.') if after_cjk => '．',
+                _ => c,
+            };
+
+            match options.punctuation_width {
+                WidthPreference::Full => {
+                    // An opening bracket belongs to what follows it.
+                    let in_japanese = if c == '(' {
+                        neighbour(chars, i, true).is_some_and(is_cjk)
+                    } else {
+                        after_cjk
+                    };
+                    WIDTH_PUNCTUATION
+                        .iter()
+                        .find(|(half, _)| *half == c && in_japanese)
+                        .map_or(c, |(_, full)| *full)
+                }
+                WidthPreference::Half => WIDTH_PUNCTUATION
+                    .iter()
+                    .find(|(_, full)| *full == c)
+                    .map_or(c, |(half, _)| *half),
+                WidthPreference::Keep => c,
+            }
+        })
+        .collect()
+}
+
+fn is_period(c: char) -> bool {
+    matches!(c, '。' | '．')
+}
+
+fn is_comma_or_period(c: char) -> bool {
+    matches!(c, '、' | '，' | ',') || is_period(c)
+}
+
+/// "。。" and "、、" become one mark, and a comma next to a period gives way to
+/// the period. ASCII "..." and "!!" are left alone as intentional.
+fn collapse_punctuation(chars: &[char]) -> Vec<char> {
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    for &c in chars {
+        match out.last().copied() {
+            Some(last) if last == c && is_comma_or_period(c) => {}
+            Some(last) if is_comma_or_period(last) && is_comma_or_period(c) && last != ',' => {
+                if is_period(c) {
+                    *out.last_mut().expect("non-empty") = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn apply_spacing(chars: &[char], spacing: CjkLatinSpacing) -> String {
+    let mut out = String::with_capacity(chars.len());
+    let mut pending_space = false;
+    for &c in chars {
+        if c.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if let Some(last) = out.chars().last() {
+            let mixed = (is_cjk(last) && is_latin(c)) || (is_latin(last) && is_cjk(c));
+            let space = if is_cjk_punct(last) || is_cjk_punct(c) || (is_cjk(last) && is_cjk(c)) {
+                false
+            } else if mixed {
+                match spacing {
+                    CjkLatinSpacing::None => false,
+                    CjkLatinSpacing::Space => true,
+                    CjkLatinSpacing::Keep => pending_space,
+                }
+            } else {
+                pending_space
+            };
+            if space {
+                out.push(' ');
+            }
+        }
+        out.push(c);
+        pending_space = false;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> NormalizeOptions {
+        NormalizeOptions {
+            alnum_width: WidthPreference::Half,
+            punctuation_width: WidthPreference::Full,
+            japanese_punctuation: JapanesePunctuation::Touten,
+            cjk_latin_spacing: CjkLatinSpacing::None,
+        }
+    }
+
+    #[test]
+    fn default_corpus() {
+        let cases = [
+            ("今日は\n晴れです。", "今日は晴れです。"),
+            ("Hello\nworld", "Hello world"),
+            ("one  two\n\nthree", "one two three"),
+            ("今日は 晴れです 。", "今日は晴れです。"),
+            ("\u{3000}全角スペース\u{3000}です", "全角スペースです"),
+            ("ＡＢＣ１２３を使う", "ABC123を使う"),
+            ("Tauri を使ったアプリ", "Tauriを使ったアプリ"),
+            ("Tauri\nを使う", "Tauriを使う"),
+            ("これはnotypeです", "これはnotypeです"),
+            ("はい。。", "はい。"),
+            ("はい、、そうです", "はい、そうです"),
+            ("はい、。", "はい。"),
+            ("はい。、次", "はい。次"),
+            ("本当に?", "本当に？"),
+            ("すごい! 次は", "すごい！次は"),
+            ("メモ(重要)", "メモ（重要）"),
+            ("メモ（重要）", "メモ（重要）"),
+            ("今日は, 晴れ.", "今日は、晴れ。"),
+            ("はい，そうです．", "はい、そうです。"),
+            ("バージョン3.5です", "バージョン3.5です"),
+            ("What? Really.", "What? Really."),
+            ("Wait...", "Wait..."),
+            ("a,, b", "a, b"),
+            ("", ""),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_text(input, &options()),
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn preference_corpus() {
+        let space = NormalizeOptions {
+            cjk_latin_spacing: CjkLatinSpacing::Space,
+            ..options()
+        };
+        let keep = NormalizeOptions {
+            cjk_latin_spacing: CjkLatinSpacing::Keep,
+            ..options()
+        };
+        let comma = NormalizeOptions {
+            japanese_punctuation: JapanesePunctuation::Comma,
+            ..options()
+        };
+        let half_punct = NormalizeOptions {
+            punctuation_width: WidthPreference::Half,
+            ..options()
+        };
+        let full_alnum = NormalizeOptions {
+            alnum_width: WidthPreference::Full,
+            ..options()
+        };
+        let keep_alnum = NormalizeOptions {
+            alnum_width: WidthPreference::Keep,
+            ..options()
+        };
+
+        let cases = [
+            (space, "これはnotypeです", "これは notype です"),
+            (space, "Tauri  を使う", "Tauri を使う"),
+            (space, "今日は 晴れ", "今日は晴れ"),
+            (keep, "Tauri を使う", "Tauri を使う"),
+            (keep, "Tauriを使う", "Tauriを使う"),
+            (comma, "はい、そうです。", "はい，そうです．"),
+            (comma, "はい, そう.", "はい，そう．"),
+            (half_punct, "本当に？（笑）", "本当に?(笑)"),
+            (full_alnum, "ABCを使う", "ＡＢＣを使う"),
+            (full_alnum, "ABC only", "ABC only"),
+            (keep_alnum, "ＡＢＣを使う", "ＡＢＣを使う"),
+        ];
+        for (opts, input, expected) in cases {
+            assert_eq!(
+                normalize_text(input, &opts),
+                expected,
+                "input: {input:?} options: {opts:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn join_lines_only_spaces_latin_boundaries() {
+        assert_eq!(join_lines(" 一行目\n\n二行目 "), "一行目二行目");
+        assert_eq!(join_lines("first\nsecond"), "first second");
+        assert_eq!(join_lines("はい。\nOK"), "はい。OK");
+    }
+}
@@ -0,0 +1,1139 @@
+use super::config::AppConfig;
+use super::normalize::is_cjk;
+
+/// Which kinds of spoken or kanji numbers are rewritten in digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberOptions {
+    pub numbers: bool,
+    pub dates: bool,
+    pub times: bool,
+    pub percentages: bool,
+    pub units: bool,
+}
+
+impl NumberOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            numbers: config.normalize_numbers,
+            dates: config.normalize_dates,
+            times: config.normalize_times,
+            percentages: config.normalize_percentages,
+            units: config.normalize_units,
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.numbers || self.dates || self.times || self.percentages || self.units
+    }
+}
+
+/// Rewrites numbers in `text` in digit form. Japanese handles kanji numerals
+/// and kana readings, English handles number words; without a known
+/// language both are tried.
+pub fn normalize_numbers(text: &str, language: Option<&str>, options: &NumberOptions) -> String {
+    if !options.any() {
+        return text.to_string();
+    }
+    match language {
+        Some("ja") => normalize_japanese(text, options),
+        Some("en") => normalize_english(text, options),
+        None => normalize_english(&normalize_japanese(text, options), options),
+        Some(_) => text.to_string(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Japanese
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Numeral {
+    Digit(u64),
+    /// 十, 百, 千
+    Small(u64),
+    /// 万, 億, 兆
+    Large(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Counter {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Percent,
+    Unit,
+    Other,
+}
+
+/// Counter (pattern, kind, written form). Matched longest first.
+const JA_COUNTERS: &[(&str, Counter, &str)] = &[
+    ("年", Counter::Year, "年"),
+    ("ねん", Counter::Year, "年"),
+    ("月", Counter::Month, "月"),
+    ("がつ", Counter::Month, "月"),
+    ("日", Counter::Day, "日"),
+    ("にち", Counter::Day, "日"),
+    ("時間", Counter::Other, "時間"),
+    ("じかん", Counter::Other, "時間"),
+    ("時", Counter::Hour, "時"),
+    ("じ", Counter::Hour, "時"),
+    ("分", Counter::Minute, "分"),
+    ("ふん", Counter::Minute, "分"),
+    ("ぷん", Counter::Minute, "分"),
+    ("秒", Counter::Second, "秒"),
+    ("びょう", Counter::Second, "秒"),
+    ("パーセント", Counter::Percent, "%"),
+    ("ぱーせんと", Counter::Percent, "%"),
+    ("％", Counter::Percent, "%"),
+    ("%", Counter::Percent, "%"),
+    ("キロバイト", Counter::Unit, "KB"),
+    ("メガバイト", Counter::Unit, "MB"),
+    ("ギガバイト", Counter::Unit, "GB"),
+    ("テラバイト", Counter::Unit, "TB"),
+    ("キロメートル", Counter::Unit, "km"),
+    ("センチメートル", Counter::Unit, "cm"),
+    ("センチ", Counter::Unit, "cm"),
+    ("ミリメートル", Counter::Unit, "mm"),
+    ("メートル", Counter::Unit, "m"),
+    ("キログラム", Counter::Unit, "kg"),
+    ("グラム", Counter::Unit, "g"),
+    ("ミリ秒", Counter::Unit, "ms"),
+    ("ギガヘルツ", Counter::Unit, "GHz"),
+    ("メガヘルツ", Counter::Unit, "MHz"),
+    ("ヘルツ", Counter::Unit, "Hz"),
+    ("個", Counter::Other, "個"),
+    ("件", Counter::Other, "件"),
+    ("回", Counter::Other, "回"),
+    ("台", Counter::Other, "台"),
+    ("枚", Counter::Other, "枚"),
+    ("冊", Counter::Other, "冊"),
+    ("円", Counter::Other, "円"),
+    ("歳", Counter::Other, "歳"),
+    ("倍", Counter::Other, "倍"),
+];
+
+/// Kana readings of numerals, including the sound changes before counters.
+const KANA_NUMERALS: &[(&str, Numeral)] = &[
+    ("ぜろ", Numeral::Digit(0)),
+    ("れい", Numeral::Digit(0)),
+    ("いち", Numeral::Digit(1)),
+    ("いっ", Numeral::Digit(1)),
+    ("に", Numeral::Digit(2)),
+    ("さん", Numeral::Digit(3)),
+    ("よん", Numeral::Digit(4)),
+    ("よ", Numeral::Digit(4)),
+    ("し", Numeral::Digit(4)),
+    ("ご", Numeral::Digit(5)),
+    ("ろく", Numeral::Digit(6)),
+    ("ろっ", Numeral::Digit(6)),
+    ("なな", Numeral::Digit(7)),
+    ("しち", Numeral::Digit(7)),
+    ("はち", Numeral::Digit(8)),
+    ("はっ", Numeral::Digit(8)),
+    ("きゅう", Numeral::Digit(9)),
+    ("く", Numeral::Digit(9)),
+    ("じゅう", Numeral::Small(10)),
+    ("じゅっ", Numeral::Small(10)),
+    ("じっ", Numeral::Small(10)),
+    ("ひゃく", Numeral::Small(100)),
+    ("びゃく", Numeral::Small(100)),
+    ("ぴゃく", Numeral::Small(100)),
+    ("せん", Numeral::Small(1000)),
+    ("ぜん", Numeral::Small(1000)),
+    ("まん", Numeral::Large(10_000)),
+    ("おく", Numeral::Large(100_000_000)),
+];
+
+/// Single kanji numeral + counter pairs that are usually words: 一時的,
+/// 一日中, 十分 (enough), 五分五分. Only converted next to a date or time.
+const JA_IDIOMS: &[&str] = &["一時", "一日", "一分", "五分", "十分"];
+
+/// Words spelled with numerals, some of them before a counter (三日月,
+/// 九分九厘), which are never converted.
+const JA_WORDS: &[&str] = &[
+    "三日月",
+    "三日坊主",
+    "二十日鼠",
+    "五月雨",
+    "五月晴",
+    "五月蝿",
+    "一個人",
+    "八百屋",
+    "八百長",
+    "五十音",
+    "四六時中",
+    "九分九厘",
+    "十中八九",
+    "一石二鳥",
+    "一期一会",
+    "千差万別",
+    "一部始終",
+];
+
+const AM_PM_PREFIXES: &[&str] = &["午前", "午後", "朝", "夜", "夕方"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Digits,
+    Kanji,
+    Kana,
+}
+
+#[derive(Debug, Clone)]
+struct JaNumber {
+    digits: String,
+    len: usize,
+    source: Source,
+    /// Read as a number but left as written (二五日 is neither 25 nor a
+    /// range anyone says).
+    literal: bool,
+}
+
+impl JaNumber {
+    /// The value, or the upper end of a range such as `2、3`.
+    fn value(&self) -> Option<u64> {
+        self.digits.rsplit('、').next()?.parse().ok()
+    }
+}
+
+fn kanji_numeral(c: char) -> Option<Numeral> {
+    Some(match c {
+        '〇' | '零' => Numeral::Digit(0),
+        '一' => Numeral::Digit(1),
+        '二' => Numeral::Digit(2),
+        '三' => Numeral::Digit(3),
+        '四' => Numeral::Digit(4),
+        '五' => Numeral::Digit(5),
+        '六' => Numeral::Digit(6),
+        '七' => Numeral::Digit(7),
+        '八' => Numeral::Digit(8),
+        '九' => Numeral::Digit(9),
+        '十' => Numeral::Small(10),
+        '百' => Numeral::Small(100),
+        '千' => Numeral::Small(1000),
+        '万' => Numeral::Large(10_000),
+        '億' => Numeral::Large(100_000_000),
+        '兆' => Numeral::Large(1_000_000_000_000),
+        _ => return None,
+    })
+}
+
+/// 二〇二六 reads digit by digit; 二千二十六 is positional.
+fn numeral_value(numerals: &[Numeral]) -> Option<String> {
+    if numerals.is_empty() {
+        return None;
+    }
+    if numerals.iter().all(|n| matches!(n, Numeral::Digit(_))) {
+        return Some(
+            numerals
+                .iter()
+                .map(|n| match n {
+                    Numeral::Digit(d) => char::from_digit(*d as u32, 10).unwrap_or('0'),
+                    _ => '0',
+                })
+                .collect(),
+        );
+    }
+    let (mut total, mut section, mut current) = (0u64, 0u64, None::<u64>);
+    for numeral in numerals {
+        match *numeral {
+            Numeral::Digit(d) => {
+                if current.is_some() {
+                    return None;
+                }
+                current = Some(d);
+            }
+            Numeral::Small(unit) => {
+                section += current.take().unwrap_or(1) * unit;
+            }
+            Numeral::Large(unit) => {
+                let value = section + current.take().unwrap_or(0);
+                if value == 0 {
+                    return None;
+                }
+                total += value * unit;
+                section = 0;
+            }
+        }
+    }
+    Some((total + section + current.unwrap_or(0)).to_string())
+}
+
+fn match_counter(text: &str) -> Option<(usize, Counter, &'static str)> {
+    JA_COUNTERS
+        .iter()
+        .filter(|(pattern, _, _)| text.starts_with(pattern))
+        .max_by_key(|(pattern, _, _)| pattern.len())
+        .map(|(pattern, kind, written)| (pattern.len(), *kind, *written))
+}
+
+fn read_ascii_digits(text: &str) -> Option<JaNumber> {
+    let bytes = text.as_bytes();
+    let mut len = 0;
+    while len < bytes.len()
+        && (bytes[len].is_ascii_digit()
+            || (bytes[len] == b'.'
+                && len > 0
+                && bytes.get(len + 1).is_some_and(u8::is_ascii_digit)))
+    {
+        len += 1;
+    }
+    (len > 0).then(|| JaNumber {
+        digits: text[..len].to_string(),
+        len,
+        source: Source::Digits,
+        literal: false,
+    })
+}
+
+/// Two adjacent single digits are an estimate, not a number: 二三日 is
+/// "two or three days" (2、3日). Only neighbours such as 二三 or 五六 are
+/// said that way.
+fn numeral_range(numerals: &[Numeral]) -> Option<Result<String, ()>> {
+    let [Numeral::Digit(low), Numeral::Digit(high)] = numerals else {
+        return None;
+    };
+    if *low == 0 || *high == 0 {
+        return None;
+    }
+    Some(if *high == low + 1 {
+        Ok(format!("{low}、{high}"))
+    } else {
+        Err(())
+    })
+}
+
+fn read_kanji(text: &str) -> Option<JaNumber> {
+    let numerals: Vec<Numeral> = text.chars().map_while(kanji_numeral).collect();
+    let mut len = numerals.len() * '一'.len_utf8();
+    let mut literal = false;
+    let mut digits = match numeral_range(&numerals) {
+        Some(Ok(range)) => range,
+        Some(Err(())) => {
+            literal = true;
+            String::new()
+        }
+        None => numeral_value(&numerals)?,
+    };
+    if literal || digits.contains('、') {
+        return Some(JaNumber {
+            digits,
+            len,
+            source: Source::Kanji,
+            literal,
+        });
+    }
+
+    // 三点五 → 3.5
+    if let Some(after) = text[len..].strip_prefix('点') {
+        let fraction: String = after
+            .chars()
+            .map_while(kanji_numeral)
+            .map_while(|n| match n {
+                Numeral::Digit(d) => char::from_digit(d as u32, 10),
+                _ => None,
+            })
+            .collect();
+        if !fraction.is_empty() {
+            len += '点'.len_utf8() + fraction.len() * '一'.len_utf8();
+            digits = format!("{digits}.{fraction}");
+        }
+    }
+    Some(JaNumber {
+        digits,
+        len,
+        source: Source::Kanji,
+        literal,
+    })
+}
+
+/// Kana readings are only taken when a counter follows, since に, し, ご and
+/// く are also ordinary kana.
+fn read_kana(text: &str) -> Option<JaNumber> {
+    let mut numerals = Vec::new();
+    let mut len = 0;
+    loop {
+        let rest = &text[len..];
+        let counter_len = match_counter(rest).map_or(0, |(l, _, _)| l);
+        let Some((pattern, numeral)) = KANA_NUMERALS
+            .iter()
+            .filter(|(pattern, _)| rest.starts_with(pattern))
+            .max_by_key(|(pattern, _)| pattern.len())
+        else {
+            break;
+        };
+        if pattern.len() <= counter_len {
+            break;
+        }
+        numerals.push(*numeral);
+        len += pattern.len();
+    }
+    let (_, counter, _) = match_counter(&text[len..])?;
+    // A lone one-kana reading (に, ご, く) is too ambiguous, except in months.
+    let chars = text[..len].chars().count();
+    if chars < 2 && counter != Counter::Month {
+        return None;
+    }
+    Some(JaNumber {
+        digits: numeral_value(&numerals)?,
+        len,
+        source: Source::Kana,
+        literal: false,
+    })
+}
+
+fn is_small_kana(c: char) -> bool {
+    "ゃゅょぁぃぅぇぉっ".contains(c)
+}
+
+fn read_japanese_number(
+    text: &str,
+    previous: Option<char>,
+    follows_number: bool,
+) -> Option<JaNumber> {
+    let first = text.chars().next()?;
+    if first.is_ascii_digit() {
+        return if previous.is_some_and(|c| c.is_ascii_alphanumeric() || c == '.') {
+            None
+        } else {
+            read_ascii_digits(text)
+        };
+    }
+    if kanji_numeral(first).is_some() {
+        return read_kanji(text);
+    }
+    // After a kanji or katakana the kana belong to that word (田中さん),
+    // unless that was a converted counter (2026年じゅうがつ).
+    if !follows_number
+        && previous.is_some_and(|c| is_cjk(c) && !('\u{3040}'..='\u{309F}').contains(&c))
+    {
+        return None;
+    }
+    read_kana(text)
+}
+
+fn normalize_japanese(text: &str, options: &NumberOptions) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    // The counter converted last and where it ended, for 十月一日 / 十時十分.
+    let mut previous: Option<(Counter, usize)> = None;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+        if let Some(word) = JA_WORDS.iter().find(|w| rest.starts_with(*w)) {
+            out.push_str(word);
+            i += word.len();
+            continue;
+        }
+        let follows_number = previous.is_some_and(|(_, end)| end == i);
+        let Some(number) = read_japanese_number(rest, out.chars().last(), follows_number) else {
+            out.push(c);
+            i += c.len_utf8();
+            continue;
+        };
+        if number.literal {
+            out.push_str(&rest[..number.len]);
+            i += number.len;
+            continue;
+        }
+        let after = &rest[number.len..];
+        let counter = match_counter(after).filter(|(len, _, _)| {
+            !(number.source == Source::Kana && after[*len..].starts_with(is_small_kana))
+        });
+        let context = previous.filter(|(_, end)| *end == i).map(|(kind, _)| kind);
+
+        if let Some((counter_len, kind, written)) = counter {
+            let idiom = number.source == Source::Kanji
+                && JA_IDIOMS.contains(&&rest[..number.len + counter_len]);
+            let converted = convert_counter(&number, kind, options, || {
+                !idiom
+                    || match kind {
+                        Counter::Day => context == Some(Counter::Month),
+                        Counter::Minute => context == Some(Counter::Hour),
+                        Counter::Hour => AM_PM_PREFIXES.iter().any(|p| out.ends_with(p)),
+                        _ => true,
+                    }
+            });
+            if converted {
+                out.push_str(&number.digits);
+                out.push_str(written);
+                i += number.len + counter_len;
+                previous = Some((kind, i));
+                continue;
+            }
+            if number.source == Source::Digits {
+                previous = Some((kind, i + number.len + counter_len));
+            }
+        }
+        // Without a counter a kanji numeral is as likely a word (八百屋,
+        // 五十音) as a number, so it stays as written.
+
+        if number.source == Source::Kana {
+            out.push(c);
+            i += c.len_utf8();
+        } else {
+            out.push_str(&rest[..number.len]);
+            i += number.len;
+        }
+    }
+    out
+}
+
+fn convert_counter(
+    number: &JaNumber,
+    kind: Counter,
+    options: &NumberOptions,
+    unambiguous: impl FnOnce() -> bool,
+) -> bool {
+    let spoken = number.source != Source::Digits;
+    let value = number.value();
+    let in_range = |range: std::ops::RangeInclusive<u64>| value.is_some_and(|v| range.contains(&v));
+    let allowed = match kind {
+        Counter::Year => options.dates && spoken && value.is_some(),
+        Counter::Month => options.dates && spoken && in_range(1..=12),
+        Counter::Day => options.dates && spoken && in_range(1..=31),
+        Counter::Hour => options.times && spoken && in_range(0..=24),
+        Counter::Minute | Counter::Second => options.times && spoken && in_range(0..=59),
+        Counter::Percent => options.percentages,
+        Counter::Unit => options.units,
+        Counter::Other => options.numbers && spoken,
+    };
+    allowed && unambiguous()
+}
+
+// ---------------------------------------------------------------------------
+// English
+
+const ONES: &[&str] = &[
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: &[(&str, u64)] = &[
+    ("twenty", 20),
+    ("thirty", 30),
+    ("forty", 40),
+    ("fifty", 50),
+    ("sixty", 60),
+    ("seventy", 70),
+    ("eighty", 80),
+    ("ninety", 90),
+];
+
+const SCALES: &[(&str, u64)] = &[
+    ("thousand", 1_000),
+    ("million", 1_000_000),
+    ("billion", 1_000_000_000),
+];
+
+const ORDINALS: &[(&str, u64)] = &[
+    ("first", 1),
+    ("second", 2),
+    ("third", 3),
+    ("fourth", 4),
+    ("fifth", 5),
+    ("sixth", 6),
+    ("seventh", 7),
+    ("eighth", 8),
+    ("ninth", 9),
+    ("tenth", 10),
+    ("eleventh", 11),
+    ("twelfth", 12),
+    ("thirteenth", 13),
+    ("fourteenth", 14),
+    ("fifteenth", 15),
+    ("sixteenth", 16),
+    ("seventeenth", 17),
+    ("eighteenth", 18),
+    ("nineteenth", 19),
+    ("twentieth", 20),
+    ("thirtieth", 30),
+];
+
+const MONTHS: &[&str] = &[
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Singular unit words and their symbols; a trailing "s" is ignored.
+const EN_UNITS: &[(&str, &str)] = &[
+    ("kilobyte", "KB"),
+    ("megabyte", "MB"),
+    ("gigabyte", "GB"),
+    ("terabyte", "TB"),
+    ("kilometer", "km"),
+    ("kilometre", "km"),
+    ("meter", "m"),
+    ("metre", "m"),
+    ("centimeter", "cm"),
+    ("centimetre", "cm"),
+    ("millimeter", "mm"),
+    ("millimetre", "mm"),
+    ("kilogram", "kg"),
+    ("gram", "g"),
+    ("millisecond", "ms"),
+    ("hertz", "Hz"),
+    ("kilohertz", "kHz"),
+    ("megahertz", "MHz"),
+    ("gigahertz", "GHz"),
+];
+
+const MERIDIEMS: &[&str] = &["am", "pm", "a.m", "p.m"];
+
+#[derive(Debug, Clone, Copy)]
+struct Word<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+}
+
+/// ASCII words; `-`, `.`, `,` and `'` join them only between letters or
+/// digits (twenty-six, 3.5, 2,000, o'clock, p.m).
+fn split_words(text: &str) -> Vec<Word<'_>> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut words = Vec::new();
+    let mut k = 0;
+    while k < chars.len() {
+        if !chars[k].1.is_ascii_alphanumeric() {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        k += 1;
+        while k < chars.len() {
+            let c = chars[k].1;
+            let joiner = matches!(c, '-' | '.' | ',' | '\'')
+                && chars[k - 1].1.is_ascii_alphanumeric()
+                && chars
+                    .get(k + 1)
+                    .is_some_and(|(_, n)| n.is_ascii_alphanumeric())
+                && (c != ',' || chars[k - 1].1.is_ascii_digit());
+            if c.is_ascii_alphanumeric() || joiner {
+                k += 1;
+            } else {
+                break;
+            }
+        }
+        let (start_byte, end_byte) = (chars[start].0, chars.get(k).map_or(text.len(), |(i, _)| *i));
+        words.push(Word {
+            start: start_byte,
+            end: end_byte,
+            text: &text[start_byte..end_byte],
+        });
+    }
+    words
+}
+
+struct EnText<'a> {
+    text: &'a str,
+    words: Vec<Word<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    None,
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+    And,
+}
+
+#[derive(Debug, Clone)]
+struct EnNumber {
+    text: String,
+    value: Option<u64>,
+    end: usize,
+    spoken: bool,
+    decimal: bool,
+}
+
+impl<'a> EnText<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            words: split_words(text),
+        }
+    }
+
+    /// The lowercased word at `i` when only whitespace separates it from the
+    /// previous word.
+    fn next_word(&self, i: usize) -> Option<String> {
+        let word = self.words.get(i)?;
+        if i > 0
+            && !self.text[self.words[i - 1].end..word.start]
+                .trim()
+                .is_empty()
+        {
+            return None;
+        }
+        Some(word.text.to_ascii_lowercase())
+    }
+
+    fn number(&self, i: usize) -> Option<EnNumber> {
+        let word = self.words.get(i)?;
+        if word.text.starts_with(|c: char| c.is_ascii_digit())
+            && word
+                .text
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+        {
+            return Some(EnNumber {
+                text: word.text.to_string(),
+                value: word.text.replace(',', "").parse().ok(),
+                end: i + 1,
+                spoken: false,
+                decimal: word.text.contains('.'),
+            });
+        }
+
+        let (value, end) = self.spoken_integer(i)?;
+        let mut fraction = String::new();
+        let mut j = end + 1;
+        if self.next_word(end).as_deref() == Some("point") {
+            while let Some(d) = self.next_word(j).as_deref().and_then(single_digit) {
+                fraction.push(d);
+                j += 1;
+            }
+        }
+        Some(if fraction.is_empty() {
+            EnNumber {
+                text: value.to_string(),
+                value: Some(value),
+                end,
+                spoken: true,
+                decimal: false,
+            }
+        } else {
+            EnNumber {
+                text: format!("{value}.{fraction}"),
+                value: None,
+                end: j,
+                spoken: true,
+                decimal: true,
+            }
+        })
+    }
+
+    fn ordinal(&self, i: usize) -> Option<(u64, usize)> {
+        let word = self.next_word(i)?;
+        if let Some(n) = ordinal_value(&word) {
+            return Some((n, i + 1));
+        }
+        if let Some((tens, unit)) = word.split_once('-') {
+            let n = tens_value(tens)? + ordinal_value(unit).filter(|n| *n < 10)?;
+            return Some((n, i + 1));
+        }
+        if let Some(tens) = tens_value(&word) {
+            let unit = self.next_word(i + 1).as_deref().and_then(ordinal_value);
+            return unit.filter(|n| *n < 10).map(|n| (tens + n, i + 2));
+        }
+        // 18th, 2nd
+        let digits = word.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let suffix = &word[digits.len()..];
+        if matches!(suffix, "st" | "nd" | "rd" | "th") {
+            return digits.parse().ok().map(|n| (n, i + 1));
+        }
+        None
+    }
+
+    /// "October eighteenth twenty twenty six" → "October 18, 2026".
+    fn date(&self, i: usize) -> Option<(String, usize)> {
+        let word = self.words.get(i)?;
+        let month = MONTHS.iter().find(|m| **m == word.text)?;
+        let (day, end) = self.ordinal(i + 1).or_else(|| {
+            self.next_word(i + 1)?;
+            let number = self.number(i + 1)?;
+            (!number.decimal).then_some((number.value?, number.end))
+        })?;
+        if !(1..=31).contains(&day) {
+            return None;
+        }
+        let mut out = format!("{month} {day}");
+        let mut end = end;
+        if let Some((year, year_end)) = self.year(end) {
+            out = format!("{out}, {year}");
+            end = year_end;
+        }
+        Some((out, end))
+    }
+
+    fn year(&self, i: usize) -> Option<(u64, usize)> {
+        let word = self.words.get(i)?;
+        let gap = self.text[self.words[i - 1].end..word.start].trim();
+        if !(gap.is_empty() || gap == ",") {
+            return None;
+        }
+        if word.text.len() == 4 && word.text.chars().all(|c| c.is_ascii_digit()) {
+            return word.text.parse().ok().map(|y| (y, i + 1));
+        }
+        let (first, first_end) = self.spoken_integer(i)?;
+        if first >= 1000 {
+            return Some((first, first_end));
+        }
+        // Pairs: "twenty twenty six", "nineteen oh five".
+        if !(10..=99).contains(&first) {
+            return None;
+        }
+        if self.next_word(first_end).as_deref() == Some("oh") {
+            let d = self
+                .next_word(first_end + 1)
+                .as_deref()
+                .and_then(single_digit)?;
+            return Some((first * 100 + d.to_digit(10)? as u64, first_end + 2));
+        }
+        let (second, end) = self.spoken_integer(first_end)?;
+        (second <= 99).then_some((first * 100 + second, end))
+    }
+
+    /// Number words from `i` on ("two thousand and twenty-six"); a trailing
+    /// "and" is not consumed.
+    fn spoken_integer(&self, i: usize) -> Option<(u64, usize)> {
+        let mut state = (0u64, 0u64, Last::None);
+        let mut end = i;
+        let mut j = i;
+        loop {
+            let word = if j == i {
+                self.words.get(j).map(|w| w.text.to_ascii_lowercase())
+            } else {
+                self.next_word(j)
+            };
+            let Some(word) = word else { break };
+            let mut next = state;
+            if !word.split('-').all(|part| step(&mut next, part)) {
+                break;
+            }
+            state = next;
+            j += 1;
+            if state.2 != Last::And {
+                end = j;
+            }
+        }
+        (end > i).then_some((state.0 + state.1, end))
+    }
+
+    /// "three thirty pm" → "3:30 pm", "ten o'clock" → "10:00".
+    fn time(&self, i: usize) -> Option<(String, usize)> {
+        let hour = self.number(i)?;
+        let h = hour
+            .value
+            .filter(|h| !hour.decimal && (1..=12).contains(h))?;
+        let j = hour.end;
+        let next = self.next_word(j)?;
+        if next == "o'clock" {
+            return Some((format!("{h}:00"), j + 1));
+        }
+        if MERIDIEMS.contains(&next.as_str()) {
+            return hour
+                .spoken
+                .then(|| (format!("{h} {}", self.words[j].text), j + 1));
+        }
+        let (minute, k) = if next == "oh" {
+            let d = self.next_word(j + 1).as_deref().and_then(single_digit)?;
+            (format!("0{d}"), j + 2)
+        } else {
+            let (m, k) = self.spoken_integer(j)?;
+            if !(10..=59).contains(&m) {
+                return None;
+            }
+            (m.to_string(), k)
+        };
+        let meridiem = self.next_word(k)?;
+        MERIDIEMS
+            .contains(&meridiem.as_str())
+            .then(|| (format!("{h}:{minute} {}", self.words[k].text), k + 1))
+    }
+}
+
+fn step(state: &mut (u64, u64, Last), word: &str) -> bool {
+    let (total, current, last) = state;
+    if let Some(n) = ONES.iter().position(|w| *w == word) {
+        let n = n as u64;
+        let allowed = if n < 10 {
+            matches!(
+                last,
+                Last::None | Last::Tens | Last::Hundred | Last::Scale | Last::And
+            )
+        } else {
+            matches!(last, Last::None | Last::Hundred | Last::Scale | Last::And)
+        };
+        if allowed {
+            *current += n;
+            *last = if n < 10 { Last::Unit } else { Last::Teen };
+        }
+        return allowed;
+    }
+    if let Some(n) = tens_value(word) {
+        let allowed = matches!(last, Last::None | Last::Hundred | Last::Scale | Last::And);
+        if allowed {
+            *current += n;
+            *last = Last::Tens;
+        }
+        return allowed;
+    }
+    if word == "hundred" {
+        let allowed = matches!(last, Last::Unit | Last::Teen | Last::Tens);
+        if allowed {
+            *current *= 100;
+            *last = Last::Hundred;
+        }
+        return allowed;
+    }
+    if let Some((_, scale)) = SCALES.iter().find(|(w, _)| *w == word) {
+        let allowed = matches!(last, Last::Unit | Last::Teen | Last::Tens | Last::Hundred);
+        if allowed {
+            *total += *current * scale;
+            *current = 0;
+            *last = Last::Scale;
+        }
+        return allowed;
+    }
+    if word == "and" && matches!(last, Last::Hundred | Last::Scale) {
+        *last = Last::And;
+        return true;
+    }
+    false
+}
+
+fn tens_value(word: &str) -> Option<u64> {
+    TENS.iter().find(|(w, _)| *w == word).map(|(_, n)| *n)
+}
+
+fn ordinal_value(word: &str) -> Option<u64> {
+    ORDINALS.iter().find(|(w, _)| *w == word).map(|(_, n)| *n)
+}
+
+fn single_digit(word: &str) -> Option<char> {
+    if word == "oh" {
+        return Some('0');
+    }
+    ONES[..10]
+        .iter()
+        .position(|w| *w == word)
+        .and_then(|n| char::from_digit(n as u32, 10))
+}
+
+fn unit_symbol(word: &str) -> Option<&'static str> {
+    let singular = word.strip_suffix('s').unwrap_or(word);
+    EN_UNITS
+        .iter()
+        .find(|(w, _)| *w == word || *w == singular)
+        .map(|(_, symbol)| *symbol)
+}
+
+fn normalize_english(text: &str, options: &NumberOptions) -> String {
+    let en = EnText::new(text);
+    let mut replacements: Vec<(usize, usize, String)> = Vec::new();
+    let mut i = 0;
+    while i < en.words.len() {
+        let found = options
+            .dates
+            .then(|| en.date(i))
+            .flatten()
+            .or_else(|| options.times.then(|| en.time(i)).flatten());
+        if let Some((replacement, end)) = found {
+            replacements.push((en.words[i].start, en.words[end - 1].end, replacement));
+            i = end;
+            continue;
+        }
+
+        let Some(number) = en.number(i) else {
+            i += 1;
+            continue;
+        };
+        let next = en.next_word(number.end);
+        let replacement = match next.as_deref() {
+            Some("percent") if options.percentages => Some((format!("{}%", number.text), 1)),
+            Some("per")
+                if options.percentages
+                    && en.next_word(number.end + 1).as_deref() == Some("cent") =>
+            {
+                Some((format!("{}%", number.text), 2))
+            }
+            Some(word) if options.units && unit_symbol(word).is_some() => Some((
+                format!("{} {}", number.text, unit_symbol(word).unwrap_or_default()),
+                1,
+            )),
+            // Small numbers stay spelled out ("one of them", "five apples").
+            _ if options.numbers
+                && number.spoken
+                && (number.decimal
+                    || number.value.is_some_and(|v| v >= 10)
+                    || number.end - i >= 2) =>
+            {
+                Some((number.text.clone(), 0))
+            }
+            _ => None,
+        };
+        match replacement {
+            Some((text, extra)) => {
+                let end = number.end + extra;
+                replacements.push((en.words[i].start, en.words[end - 1].end, text));
+                i = end;
+            }
+            None => i = number.end,
+        }
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, end, replacement) in replacements {
+        out.push_str(&text[cursor..start]);
+        out.push_str(&replacement);
+        cursor = end;
+    }
+    out.push_str(&text[cursor..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> NumberOptions {
+        NumberOptions {
+            numbers: true,
+            dates: true,
+            times: true,
+            percentages: true,
+            units: true,
+        }
+    }
+
+    #[test]
+    fn japanese_corpus() {
+        let cases = [
+            ("にせんにじゅうろくねんじゅうがつ", "2026年10月"),
+            ("二〇二六年十月十八日", "2026年10月18日"),
+            ("十月一日に", "10月1日に"),
+            ("午後三時半", "午後3時半"),
+            ("十時三十分に集合", "10時30分に集合"),
+            ("さんじかん", "3時間"),
+            ("五十パーセント", "50%"),
+            ("三点五ギガバイト", "3.5GB"),
+            ("3.5ギガバイト", "3.5GB"),
+            ("三個と二千三百円", "3個と2300円"),
+            ("二千三百", "二千三百"),
+            ("八百屋で", "八百屋で"),
+            ("五十音順", "五十音順"),
+            ("三日月が出た", "三日月が出た"),
+            ("三日に", "3日に"),
+            ("九分九厘", "九分九厘"),
+            ("二三日かかる", "2、3日かかる"),
+            ("五六個", "5、6個"),
+            ("二五日", "二五日"),
+            ("十分です", "十分です"),
+            ("一時的に", "一時的に"),
+            ("一日中", "一日中"),
+            ("万一の場合", "万一の場合"),
+            ("一緒に統一する", "一緒に統一する"),
+            ("田中さんじゃない", "田中さんじゃない"),
+            ("ごじゅうにん", "ごじゅうにん"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_numbers(input, Some("ja"), &all()),
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn english_corpus() {
+        let cases = [
+            ("three point five gigabytes", "3.5 GB"),
+            ("The file is 3.5 gigabytes.", "The file is 3.5 GB."),
+            ("fifty percent of users", "50% of users"),
+            ("It costs twenty-six dollars", "It costs 26 dollars"),
+            ("two thousand and twenty", "2020"),
+            ("one hundred and five", "105"),
+            ("October eighteenth twenty twenty six", "October 18, 2026"),
+            ("on March 3rd, 2025", "on March 3, 2025"),
+            ("Meet at three thirty pm.", "Meet at 3:30 pm."),
+            ("at ten o'clock", "at 10:00"),
+            ("at seven p.m.", "at 7 p.m."),
+            ("ten oh five am", "10:05 am"),
+            ("one of them has five apples", "one of them has five apples"),
+            ("May I go", "May I go"),
+            ("one two three", "one two three"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                normalize_numbers(input, Some("en"), &all()),
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn categories_are_toggled_separately() {
+        let only_units = NumberOptions {
+            units: true,
+            ..NumberOptions::from_config(&AppConfig {
+                normalize_numbers: false,
+                normalize_dates: false,
+                normalize_times: false,
+                normalize_percentages: false,
+                normalize_units: false,
+                ..AppConfig::default()
+            })
+        };
+        assert_eq!(
+            normalize_numbers("十月に三点五ギガバイト", Some("ja"), &only_units),
+            "十月に3.5GB"
+        );
+        assert_eq!(
+            normalize_numbers("twenty percent of five gigabytes", Some("en"), &only_units),
+            "twenty percent of 5 GB"
+        );
+        let none = NumberOptions {
+            units: false,
+            ..only_units
+        };
+        assert_eq!(normalize_numbers("二千年", None, &none), "二千年");
+    }
+
+    #[test]
+    fn unknown_language_tries_both() {
+        assert_eq!(
+            normalize_numbers("二千年 and twenty percent", None, &all()),
+            "2000年 and 20%"
+        );
+        assert_eq!(
+            normalize_numbers("twenty percent", Some("de"), &all()),
+            "twenty percent"
+        );
+    }
+}
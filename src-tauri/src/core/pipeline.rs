@@ -0,0 +1,701 @@
+use std::time::Instant;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::codefmt::format_code;
+use super::commands::{render_segments, CommandAction, CommandRecognizer, Segment};
+use super::config::{load_config, AppConfig};
+use super::dictionary::{
+    load_dictionary, render_snippet, snippet_vars, CompiledDictionary, Dictionary,
+};
+use super::error::AppError;
+use super::filler::FillerRemover;
+use super::llm::{postprocess_or_raw, postprocess_streaming};
+use super::macros::{MacroStep, MacroTable};
+use super::normalize::{normalize_text, NormalizeOptions};
+use super::numbers::{normalize_numbers, NumberOptions};
+use super::punctuate::{break_sentences, punctuate_transcription, PunctuateOptions};
+use super::secret::resolve_llm_api_key;
+use super::stt::{Transcription, WhisperSegment};
+use super::templates::{template_or_default, PromptVars};
+
+/// Text stages that can be listed in `pipelineStages`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PipelineStage {
+    Punctuation,
+    Fillers,
+    Macros,
+    Code,
+    Cleanup,
+    Numbers,
+    SentenceBreaks,
+    Replacements,
+    Llm,
+    Commands,
+}
+
+impl PipelineStage {
+    pub const DEFAULT_ORDER: [PipelineStage; 10] = [
+        Self::Punctuation,
+        Self::Fillers,
+        Self::Macros,
+        Self::Code,
+        Self::Cleanup,
+        Self::Numbers,
+        Self::SentenceBreaks,
+        Self::Replacements,
+        Self::Llm,
+        Self::Commands,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Punctuation => "punctuation",
+            Self::Fillers => "fillers",
+            Self::Macros => "macros",
+            Self::Code => "code",
+            Self::Cleanup => "cleanup",
+            Self::Numbers => "numbers",
+            Self::SentenceBreaks => "sentenceBreaks",
+            Self::Replacements => "replacements",
+            Self::Llm => "llm",
+            Self::Commands => "commands",
+        }
+    }
+
+    /// Whether the stage's own setting is on. Code mode replaces the prose
+    /// stages with the code formatter.
+    fn enabled(&self, config: &AppConfig) -> bool {
+        let prose = !config.code_mode;
+        match self {
+            Self::Punctuation => config.auto_punctuation && prose,
+            Self::Fillers => config.filler_removal_enabled,
+            Self::Macros => !config.macros.is_empty(),
+            Self::Code => config.code_mode,
+            Self::Cleanup => config.text_cleanup && prose,
+            Self::Numbers => {
+                let numbers = config.normalize_numbers
+                    || config.normalize_dates
+                    || config.normalize_times
+                    || config.normalize_percentages
+                    || config.normalize_units;
+                numbers && prose
+            }
+            Self::SentenceBreaks => config.sentence_newline && prose,
+            Self::Replacements => true,
+            Self::Llm => config.llm_postprocess_enabled && prose,
+            Self::Commands => config.voice_commands_enabled && prose,
+        }
+    }
+}
+
+/// What every stage may read besides the text.
+pub struct StageContext<'a> {
+    pub config: &'a AppConfig,
+    /// Language of the text, after any translation.
+    pub language: Option<&'a str>,
+    /// whisper's segments, for pause-based punctuation.
+    pub segments: &'a [WhisperSegment],
+    /// Template context for the LLM stage; the transcript is filled in.
+    pub prompt: PromptVars,
+    /// Where the LLM stage streams its answer, when it is typed as it
+    /// arrives.
+    pub stream: Option<UnboundedSender<String>>,
+}
+
+/// Text flowing through the pipeline. Once the commands stage has split it,
+/// later stages only change the text between commands.
+#[derive(Debug)]
+pub struct PipelineText {
+    pub segments: Vec<Segment>,
+    pub fillers_removed: usize,
+    /// Set by the macros stage; ends the pipeline.
+    pub macro_steps: Option<Result<Vec<MacroStep>, AppError>>,
+    /// Set for an expanded snippet, which later stages leave as written.
+    pub verbatim: bool,
+}
+
+impl PipelineText {
+    fn new(text: String) -> Self {
+        Self {
+            segments: vec![Segment::Text(text)],
+            fillers_removed: 0,
+            macro_steps: None,
+            verbatim: false,
+        }
+    }
+
+    pub fn render(&self) -> String {
+        render_segments(&self.segments)
+    }
+
+    fn map_text(&mut self, mut f: impl FnMut(&str) -> String) {
+        for segment in &mut self.segments {
+            if let Segment::Text(text) = segment {
+                *text = f(text);
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait TextStage: Send + Sync {
+    fn stage(&self) -> PipelineStage;
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText);
+}
+
+/// Before/after text of one stage, for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageTrace {
+    pub stage: PipelineStage,
+    pub before: String,
+    pub after: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug)]
+pub struct PipelineOutput {
+    pub text: String,
+    pub segments: Vec<Segment>,
+    pub fillers_removed: usize,
+    pub macro_steps: Option<Result<Vec<MacroStep>, AppError>>,
+    pub traces: Vec<StageTrace>,
+}
+
+pub struct TextPipeline {
+    stages: Vec<Box<dyn TextStage>>,
+}
+
+impl TextPipeline {
+    /// Builds the stages listed in `pipelineStages`, reading `dictionary.json`
+    /// for the replacements stage.
+    pub fn from_config(config: &AppConfig) -> Self {
+        let dictionary =
+            CompiledDictionary::compile_lenient(&load_dictionary().unwrap_or_else(|err| {
+                tracing::warn!("dictionary: {}", err.details);
+                Dictionary::default()
+            }));
+        Self::build(config, dictionary)
+    }
+
+    /// Stages that are switched off are left out; a stage listed twice runs
+    /// at its first position.
+    pub fn build(config: &AppConfig, dictionary: CompiledDictionary) -> Self {
+        let mut dictionary = Some(dictionary);
+        let mut seen = Vec::new();
+        let mut stages: Vec<Box<dyn TextStage>> = Vec::new();
+        for stage in &config.pipeline_stages {
+            if seen.contains(stage) || !stage.enabled(config) {
+                continue;
+            }
+            seen.push(*stage);
+            let built: Box<dyn TextStage> = match stage {
+                PipelineStage::Punctuation => Box::new(PunctuationStage),
+                PipelineStage::Fillers => Box::new(FillerStage(FillerRemover::from_config(config))),
+                PipelineStage::Macros => Box::new(MacroStage(MacroTable::from_config(config))),
+                PipelineStage::Code => Box::new(CodeStage),
+                PipelineStage::Cleanup => {
+                    Box::new(CleanupStage(NormalizeOptions::from_config(config)))
+                }
+                PipelineStage::Numbers => Box::new(NumberStage(NumberOptions::from_config(config))),
+                PipelineStage::SentenceBreaks => Box::new(SentenceBreakStage),
+                PipelineStage::Replacements => match dictionary.take() {
+                    Some(dictionary) => Box::new(ReplacementStage(dictionary)),
+                    None => continue,
+                },
+                PipelineStage::Llm => Box::new(LlmStage),
+                PipelineStage::Commands => {
+                    Box::new(CommandStage(CommandRecognizer::from_config(config)))
+                }
+            };
+            stages.push(built);
+        }
+        Self { stages }
+    }
+
+    pub fn stages(&self) -> Vec<PipelineStage> {
+        self.stages.iter().map(|s| s.stage()).collect()
+    }
+
+    pub async fn run(&self, ctx: &StageContext<'_>, text: String) -> PipelineOutput {
+        let mut text = PipelineText::new(text);
+        let mut traces = Vec::new();
+        for stage in &self.stages {
+            let before = text.render();
+            let started = Instant::now();
+            stage.apply(ctx, &mut text).await;
+            traces.push(StageTrace {
+                stage: stage.stage(),
+                before,
+                after: text.render(),
+                elapsed_ms: started.elapsed().as_millis() as u64,
+            });
+            if text.macro_steps.is_some() || text.verbatim {
+                break;
+            }
+        }
+        PipelineOutput {
+            text: text.render(),
+            segments: text.segments,
+            fillers_removed: text.fillers_removed,
+            macro_steps: text.macro_steps,
+            traces,
+        }
+    }
+}
+
+struct PunctuationStage;
+
+#[async_trait]
+impl TextStage for PunctuationStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Punctuation
+    }
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText) {
+        let whisper_text: String = ctx.segments.iter().map(|s| s.text.as_str()).collect();
+        let language = ctx.language.map(str::to_string);
+        text.map_text(|t| {
+            // Pauses are only known while the text is still whisper's own.
+            let transcription = if whisper_text.trim() == t.trim() {
+                Transcription {
+                    text: t.to_string(),
+                    segments: ctx.segments.to_vec(),
+                    language: language.clone(),
+                    translated: false,
+                }
+            } else {
+                Transcription {
+                    language: language.clone(),
+                    ..Transcription::from_text(t.to_string())
+                }
+            };
+            punctuate_transcription(transcription, &PunctuateOptions::default()).text
+        });
+    }
+}
+
+struct FillerStage(FillerRemover);
+
+#[async_trait]
+impl TextStage for FillerStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Fillers
+    }
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText) {
+        let mut removed = 0;
+        text.map_text(|t| {
+            let outcome = self.0.apply(t, ctx.language);
+            removed += outcome.removed;
+            outcome.text
+        });
+        text.fillers_removed += removed;
+    }
+}
+
+struct MacroStage(MacroTable);
+
+#[async_trait]
+impl TextStage for MacroStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Macros
+    }
+
+    async fn apply(&self, _ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.macro_steps = self.0.resolve(&text.render());
+    }
+}
+
+struct CodeStage;
+
+#[async_trait]
+impl TextStage for CodeStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Code
+    }
+
+    async fn apply(&self, _ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.map_text(format_code);
+    }
+}
+
+struct CleanupStage(NormalizeOptions);
+
+#[async_trait]
+impl TextStage for CleanupStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Cleanup
+    }
+
+    async fn apply(&self, _ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.map_text(|t| normalize_text(t, &self.0));
+    }
+}
+
+struct NumberStage(NumberOptions);
+
+#[async_trait]
+impl TextStage for NumberStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Numbers
+    }
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.map_text(|t| normalize_numbers(t, ctx.language, &self.0));
+    }
+}
+
+struct SentenceBreakStage;
+
+#[async_trait]
+impl TextStage for SentenceBreakStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::SentenceBreaks
+    }
+
+    async fn apply(&self, _ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.map_text(break_sentences);
+    }
+}
+
+struct ReplacementStage(CompiledDictionary);
+
+#[async_trait]
+impl TextStage for ReplacementStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Replacements
+    }
+
+    async fn apply(&self, _ctx: &StageContext<'_>, text: &mut PipelineText) {
+        if let Some(body) = self.0.snippet_for(&text.render()) {
+            // Snippets are typed as written; they bypass every later stage.
+            text.segments = vec![Segment::Text(render_snippet(
+                body,
+                &snippet_vars(body).await,
+            ))];
+            text.verbatim = true;
+            return;
+        }
+        text.map_text(|t| self.0.apply(t));
+    }
+}
+
+struct LlmStage;
+
+#[async_trait]
+impl TextStage for LlmStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Llm
+    }
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText) {
+        let template = template_or_default(&ctx.config.llm_template);
+        let language = ctx.language.unwrap_or(&ctx.config.language).to_string();
+        // Streamed text goes straight to the screen, so only a lone text
+        // segment can be streamed.
+        let single = text.segments.len() == 1;
+        for segment in &mut text.segments {
+            if let Segment::Text(t) = segment {
+                let vars = PromptVars {
+                    transcript: std::mem::take(t),
+                    language: language.clone(),
+                    ..ctx.prompt.clone()
+                };
+                *t = match &ctx.stream {
+                    Some(chunks) if single => {
+                        postprocess_streaming(ctx.config, &template, vars, chunks).await
+                    }
+                    _ => postprocess_or_raw(ctx.config, &template, vars).await,
+                };
+            }
+        }
+    }
+}
+
+struct CommandStage(CommandRecognizer);
+
+#[async_trait]
+impl TextStage for CommandStage {
+    fn stage(&self) -> PipelineStage {
+        PipelineStage::Commands
+    }
+
+    async fn apply(&self, ctx: &StageContext<'_>, text: &mut PipelineText) {
+        text.segments = std::mem::take(&mut text.segments)
+            .into_iter()
+            .flat_map(|segment| match segment {
+                Segment::Text(t) => self.0.parse(&t, ctx.language),
+                command => vec![command],
+            })
+            .collect();
+    }
+}
+
+/// `notype pipeline test "<text>" [--language ja]`: runs text through the
+/// configured stages without recording and prints what each stage did.
+/// Macros and commands are listed, not executed.
+pub async fn run_cli(args: &[String]) -> i32 {
+    match args {
+        [command, text, rest @ ..] if command == "test" => match run_test(text, rest).await {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("pipeline test failed: {err}");
+                1
+            }
+        },
+        _ => {
+            eprintln!("usage: notype pipeline test \"<text>\" [--language ja|en]");
+            2
+        }
+    }
+}
+
+async fn run_test(text: &str, args: &[String]) -> Result<(), AppError> {
+    let mut config = load_config().map_err(AppError::from)?;
+    resolve_llm_api_key(&mut config).await;
+    let language = args
+        .iter()
+        .position(|a| a == "--language")
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+        .unwrap_or_else(|| config.language.clone());
+    let language = (language != "auto").then_some(language);
+
+    let pipeline = TextPipeline::from_config(&config);
+    let stages: Vec<&str> = pipeline.stages().iter().map(|s| s.as_str()).collect();
+    println!("stages: {}", stages.join(" -> "));
+    let ctx = StageContext {
+        config: &config,
+        language: language.as_deref(),
+        segments: &[],
+        prompt: PromptVars::default(),
+        stream: None,
+    };
+    let output = pipeline.run(&ctx, text.to_string()).await;
+
+    for trace in &output.traces {
+        if trace.before == trace.after {
+            println!("{:<15} (unchanged)", trace.stage.as_str());
+        } else {
+            println!(
+                "{:<15} {:?} -> {:?} ({} ms)",
+                trace.stage.as_str(),
+                trace.before,
+                trace.after,
+                trace.elapsed_ms
+            );
+        }
+    }
+    match &output.macro_steps {
+        Some(Ok(steps)) => {
+            let steps: Vec<String> = steps.iter().map(MacroStep::describe).collect();
+            println!("macro (not run): {}", steps.join(", "));
+        }
+        Some(Err(err)) => println!("macro error: {err}"),
+        None => {}
+    }
+    for segment in &output.segments {
+        if let Segment::Command(action) = segment {
+            println!("command: {}", describe_command(action));
+        }
+    }
+    println!("result: {:?}", output.text);
+    Ok(())
+}
+
+fn describe_command(action: &CommandAction) -> String {
+    match action {
+        CommandAction::Key { key, repeat } => format!("key {key} x{repeat}"),
+        CommandAction::Insert { text } => format!("insert {text:?}"),
+        CommandAction::DeleteLast => "delete last".to_string(),
+        CommandAction::Undo => "undo".to_string(),
+        CommandAction::Escape => "escape".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::dictionary::{Replacement, Snippet};
+    use crate::core::macros::{MacroAction, VoiceMacro};
+
+    fn dictionary(replacements: &[(&str, &str)], snippets: &[(&str, &str)]) -> CompiledDictionary {
+        CompiledDictionary::compile_lenient(&Dictionary {
+            replacements: replacements
+                .iter()
+                .map(|(from, to)| Replacement {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    regex: false,
+                })
+                .collect(),
+            snippets: snippets
+                .iter()
+                .map(|(trigger, text)| Snippet {
+                    trigger: trigger.to_string(),
+                    text: text.to_string(),
+                })
+                .collect(),
+            ..Dictionary::default()
+        })
+    }
+
+    /// The stages that are off by default, switched on.
+    fn opted_in() -> AppConfig {
+        AppConfig {
+            auto_punctuation: true,
+            filler_removal_enabled: true,
+            normalize_numbers: true,
+            normalize_dates: true,
+            normalize_times: true,
+            normalize_percentages: true,
+            normalize_units: true,
+            voice_commands_enabled: true,
+            ..AppConfig::default()
+        }
+    }
+
+    async fn run(config: &AppConfig, dictionary: CompiledDictionary, text: &str) -> PipelineOutput {
+        let language = (config.language != "auto").then_some(config.language.as_str());
+        let ctx = StageContext {
+            config,
+            language,
+            segments: &[],
+            prompt: PromptVars::default(),
+            stream: None,
+        };
+        TextPipeline::build(config, dictionary)
+            .run(&ctx, text.to_string())
+            .await
+    }
+
+    #[test]
+    fn default_order_skips_switched_off_stages() {
+        let pipeline = TextPipeline::build(&AppConfig::default(), dictionary(&[], &[]));
+        assert_eq!(
+            pipeline.stages(),
+            [PipelineStage::Cleanup, PipelineStage::Replacements]
+        );
+        let pipeline = TextPipeline::build(&opted_in(), dictionary(&[], &[]));
+        assert_eq!(
+            pipeline.stages(),
+            [
+                PipelineStage::Punctuation,
+                PipelineStage::Fillers,
+                PipelineStage::Cleanup,
+                PipelineStage::Numbers,
+                PipelineStage::Replacements,
+                PipelineStage::Commands,
+            ]
+        );
+
+        let code = AppConfig {
+            code_mode: true,
+            pipeline_stages: vec![
+                PipelineStage::Code,
+                PipelineStage::Cleanup,
+                PipelineStage::Code,
+            ],
+            ..AppConfig::default()
+        };
+        let pipeline = TextPipeline::build(&code, dictionary(&[], &[]));
+        assert_eq!(pipeline.stages(), [PipelineStage::Code]);
+    }
+
+    #[tokio::test]
+    async fn traces_record_each_stage() {
+        let output = run(&opted_in(), dictionary(&[], &[]), "えーと三個ください").await;
+        assert_eq!(output.text, "3個ください。");
+        assert_eq!(output.fillers_removed, 1);
+        let fillers = &output.traces[1];
+        assert_eq!(fillers.stage, PipelineStage::Fillers);
+        assert_eq!(fillers.before, "えーと三個ください。");
+        assert_eq!(fillers.after, "三個ください。");
+        let numbers = &output.traces[3];
+        assert_eq!(
+            (numbers.before.as_str(), numbers.after.as_str()),
+            ("三個ください。", "3個ください。")
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_order_is_followed() {
+        let replace_then_fill = AppConfig {
+            language: "en".to_string(),
+            pipeline_stages: vec![PipelineStage::Replacements, PipelineStage::Fillers],
+            ..opted_in()
+        };
+        let output = run(
+            &replace_then_fill,
+            dictionary(&[("hm", "um")], &[]),
+            "hm yes",
+        )
+        .await;
+        assert_eq!(output.text, "yes");
+
+        let fill_then_replace = AppConfig {
+            pipeline_stages: vec![PipelineStage::Fillers, PipelineStage::Replacements],
+            ..replace_then_fill
+        };
+        let output = run(
+            &fill_then_replace,
+            dictionary(&[("hm", "um")], &[]),
+            "hm yes",
+        )
+        .await;
+        assert_eq!(output.text, "um yes");
+    }
+
+    #[tokio::test]
+    async fn stages_after_commands_only_touch_text() {
+        let config = AppConfig {
+            language: "en".to_string(),
+            pipeline_stages: vec![PipelineStage::Commands, PipelineStage::Replacements],
+            ..opted_in()
+        };
+        let output = run(
+            &config,
+            dictionary(&[("hello", "hi")], &[]),
+            "hello. New line. world",
+        )
+        .await;
+        assert_eq!(
+            output.segments[1],
+            Segment::Command(CommandAction::Key {
+                key: "Return".to_string(),
+                repeat: 1
+            })
+        );
+        assert!(output.text.starts_with("hi"));
+        assert!(output.text.contains('\n'));
+    }
+
+    #[tokio::test]
+    async fn snippets_and_macros_end_the_pipeline() {
+        let config = AppConfig {
+            macros: vec![VoiceMacro {
+                phrase: "保存".to_string(),
+                action: MacroAction::Keys {
+                    keys: vec!["ctrl+s".to_string()],
+                },
+            }],
+            ..opted_in()
+        };
+        let output = run(&config, dictionary(&[], &[("署名", "改行")]), "署名").await;
+        assert_eq!(output.text, "改行");
+        assert_eq!(
+            output.traces.last().unwrap().stage,
+            PipelineStage::Replacements
+        );
+        assert!(output.macro_steps.is_none());
+
+        let output = run(&config, dictionary(&[], &[]), "えー、保存").await;
+        assert!(matches!(output.macro_steps, Some(Ok(_))));
+        assert_eq!(output.traces.last().unwrap().stage, PipelineStage::Macros);
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{ObjectPath, OwnedFd, OwnedObjectPath, OwnedValue, Value};
+use zbus::{MatchRule, MessageStream};
+
+use super::config::config_dir;
+use super::eis::EiKeyboard;
+use super::error::AppError;
+use super::xkb::Keymap;
+
+const RESTORE_TOKEN_FILE: &str = "portal-restore-token";
+const DEVICE_KEYBOARD: u32 = 1;
+/// Keep the grant until the user revokes it in the system settings.
+const PERSIST_UNTIL_REVOKED: u32 = 2;
+const EIS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.RemoteDesktop",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait RemoteDesktop {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn select_devices(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    fn start(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(name = "ConnectToEIS")]
+    fn connect_to_eis(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedFd>;
+}
+
+/// Keyboard shared by every portal backend in the process, so the session
+/// (and its permission dialog) is set up once and survives config changes.
+static KEYBOARD: Lazy<Mutex<Option<PortalKeyboard>>> = Lazy::new(|| Mutex::new(None));
+
+/// Sends evdev `(key, pressed)` events through the RemoteDesktop portal,
+/// starting a session on first use. `events` builds them from the
+/// compositor's keymap, which is only known once the session is up. A
+/// failed session is dropped and set up again next time.
+pub async fn send_keys(
+    events: impl FnOnce(Option<&Keymap>) -> Result<Vec<(u32, bool)>, AppError>,
+) -> Result<(), AppError> {
+    let mut keyboard = KEYBOARD.lock().await;
+    let session = match keyboard.as_mut() {
+        Some(session) => session,
+        None => {
+            let conn = zbus::Connection::session().await.map_err(portal_error)?;
+            keyboard.insert(PortalKeyboard::connect(conn, &restore_token_path()?).await?)
+        }
+    };
+    let keys = events(session.keymap())?;
+    let result = session.send_keys(&keys).await;
+    if result.is_err() {
+        *keyboard = None;
+    }
+    result
+}
+
+/// A started RemoteDesktop session with keyboard access over libei.
+pub struct PortalKeyboard {
+    /// The session ends when this connection closes.
+    _conn: zbus::Connection,
+    ei: EiKeyboard,
+}
+
+impl PortalKeyboard {
+    /// Starts a keyboard session. With a restore token from an earlier grant
+    /// the portal skips its dialog; the new token it returns is saved to
+    /// `token_path`.
+    pub async fn connect(conn: zbus::Connection, token_path: &Path) -> Result<Self, AppError> {
+        let portal = RemoteDesktopProxy::builder(&conn)
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await
+            .map_err(portal_error)?;
+
+        let created = request(
+            &conn,
+            portal.create_session(HashMap::from([
+                ("handle_token", handle_token()),
+                ("session_handle_token", handle_token()),
+            ])),
+        )
+        .await?;
+        let session = created
+            .get("session_handle")
+            .and_then(|value| String::try_from(value.try_clone().ok()?).ok())
+            .and_then(|path| OwnedObjectPath::try_from(path).ok())
+            .ok_or_else(|| portal_error("CreateSession returned no session handle"))?;
+
+        let mut select = HashMap::from([
+            ("handle_token", handle_token()),
+            ("types", Value::from(DEVICE_KEYBOARD)),
+            ("persist_mode", Value::from(PERSIST_UNTIL_REVOKED)),
+        ]);
+        if let Some(token) = load_restore_token(token_path) {
+            select.insert("restore_token", Value::from(token));
+        }
+        request(&conn, portal.select_devices(&session, select)).await?;
+
+        let started = request(
+            &conn,
+            portal.start(
+                &session,
+                "",
+                HashMap::from([("handle_token", handle_token())]),
+            ),
+        )
+        .await?;
+        let devices = started
+            .get("devices")
+            .and_then(|value| u32::try_from(value).ok())
+            .unwrap_or(0);
+        if devices & DEVICE_KEYBOARD == 0 {
+            return Err(AppError::new(
+                "キーボード操作が許可されませんでした",
+                format!("portal granted devices {devices}"),
+            ));
+        }
+        if let Some(token) = started
+            .get("restore_token")
+            .and_then(|value| String::try_from(value.try_clone().ok()?).ok())
+        {
+            if let Err(err) = save_restore_token(token_path, &token) {
+                tracing::warn!("portal: failed to save restore token: {err}");
+            }
+        }
+
+        let fd = portal
+            .connect_to_eis(&session, HashMap::new())
+            .await
+            .map_err(portal_error)?;
+        let stream = std::os::unix::net::UnixStream::from(std::os::fd::OwnedFd::from(fd));
+        stream.set_nonblocking(true).map_err(portal_error)?;
+        let stream = tokio::net::UnixStream::from_std(stream).map_err(portal_error)?;
+        let ei = tokio::time::timeout(EIS_CONNECT_TIMEOUT, EiKeyboard::connect(stream))
+            .await
+            .map_err(|_| portal_error("no keyboard from EIS"))??;
+        tracing::info!("portal: keyboard session started");
+        Ok(Self { _conn: conn, ei })
+    }
+
+    pub fn keymap(&self) -> Option<&Keymap> {
+        self.ei.keymap()
+    }
+
+    pub async fn send_keys(&mut self, keys: &[(u32, bool)]) -> Result<(), AppError> {
+        self.ei.send_keys(keys).await
+    }
+}
+
+/// Makes a portal call and waits for the `Response` signal on the request
+/// object it returns. The signal is subscribed before the call so a fast
+/// response cannot be missed.
+async fn request(
+    conn: &zbus::Connection,
+    call: impl std::future::Future<Output = zbus::Result<OwnedObjectPath>>,
+) -> Result<HashMap<String, OwnedValue>, AppError> {
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface("org.freedesktop.portal.Request")
+        .and_then(|rule| rule.member("Response"))
+        .map_err(portal_error)?
+        .build();
+    let mut responses = MessageStream::for_match_rule(rule, conn, None)
+        .await
+        .map_err(portal_error)?;
+    let handle = call.await.map_err(portal_error)?;
+
+    while let Some(message) = responses.next().await {
+        let message = message.map_err(portal_error)?;
+        if message.header().path().map(|path| path.as_str()) != Some(handle.as_str()) {
+            continue;
+        }
+        let (response, results): (u32, HashMap<String, OwnedValue>) =
+            message.body().deserialize().map_err(portal_error)?;
+        return match response {
+            0 => Ok(results),
+            1 => Err(AppError::new(
+                "入力の許可がキャンセルされました",
+                format!("portal request {} cancelled", handle.as_str()),
+            )),
+            other => Err(portal_error(format!(
+                "request {} failed with {other}",
+                handle.as_str()
+            ))),
+        };
+    }
+    Err(portal_error(
+        "connection closed before the portal responded",
+    ))
+}
+
+fn handle_token() -> Value<'static> {
+    Value::from(format!("notype_{}", uuid::Uuid::new_v4().simple()))
+}
+
+fn restore_token_path() -> Result<PathBuf, AppError> {
+    config_dir()
+        .map(|dir| dir.join(RESTORE_TOKEN_FILE))
+        .map_err(|e| AppError::new("設定ディレクトリを解決できません", e.to_string()))
+}
+
+fn load_restore_token(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+fn save_restore_token(path: &Path, token: &str) -> std::io::Result<()> {
+    std::fs::write(path, token)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+}
+
+fn portal_error(err: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        "入力ポータル (RemoteDesktop) を利用できません",
+        format!("portal: {err}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use tokio::task::JoinHandle;
+    use zbus::interface;
+
+    const SESSION_PATH: &str = "/org/freedesktop/portal/desktop/session/1_0/notype";
+
+    #[derive(Default)]
+    struct Log {
+        restore_token: Option<String>,
+        persist_mode: Option<u32>,
+        eis: Option<JoinHandle<Vec<(u32, bool)>>>,
+    }
+
+    struct MockPortal {
+        log: Arc<StdMutex<Log>>,
+    }
+
+    /// Emits the `Response` for a request, as the real portal does once the
+    /// user has answered.
+    async fn respond(
+        conn: &zbus::Connection,
+        options: &HashMap<String, OwnedValue>,
+        results: HashMap<&str, Value<'_>>,
+    ) -> OwnedObjectPath {
+        let token = String::try_from(options["handle_token"].try_clone().expect("clone"))
+            .expect("handle token");
+        let path = OwnedObjectPath::try_from(format!(
+            "/org/freedesktop/portal/desktop/request/1_0/{token}"
+        ))
+        .expect("path");
+        conn.emit_signal(
+            None::<&str>,
+            &path,
+            "org.freedesktop.portal.Request",
+            "Response",
+            &(0u32, results),
+        )
+        .await
+        .expect("response");
+        path
+    }
+
+    #[interface(name = "org.freedesktop.portal.RemoteDesktop")]
+    impl MockPortal {
+        async fn create_session(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            options: HashMap<String, OwnedValue>,
+        ) -> OwnedObjectPath {
+            let results = HashMap::from([("session_handle", Value::from(SESSION_PATH))]);
+            respond(conn, &options, results).await
+        }
+
+        async fn select_devices(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            session_handle: OwnedObjectPath,
+            options: HashMap<String, OwnedValue>,
+        ) -> OwnedObjectPath {
+            assert_eq!(session_handle.as_str(), SESSION_PATH);
+            assert_eq!(
+                u32::try_from(&options["types"]).expect("types"),
+                DEVICE_KEYBOARD
+            );
+            {
+                let mut log = self.log.lock().expect("lock");
+                log.persist_mode = options
+                    .get("persist_mode")
+                    .and_then(|v| u32::try_from(v).ok());
+                log.restore_token = options
+                    .get("restore_token")
+                    .and_then(|v| String::try_from(v.try_clone().ok()?).ok());
+            }
+            respond(conn, &options, HashMap::new()).await
+        }
+
+        async fn start(
+            &self,
+            #[zbus(connection)] conn: &zbus::Connection,
+            _session_handle: OwnedObjectPath,
+            _parent_window: String,
+            options: HashMap<String, OwnedValue>,
+        ) -> OwnedObjectPath {
+            let results = HashMap::from([
+                ("devices", Value::from(DEVICE_KEYBOARD)),
+                ("restore_token", Value::from("token-2")),
+            ]);
+            respond(conn, &options, results).await
+        }
+
+        #[zbus(name = "ConnectToEIS")]
+        fn connect_to_eis(
+            &self,
+            _session_handle: OwnedObjectPath,
+            _options: HashMap<String, OwnedValue>,
+        ) -> OwnedFd {
+            let (server, client) = std::os::unix::net::UnixStream::pair().expect("socket pair");
+            server.set_nonblocking(true).expect("nonblocking");
+            let server = tokio::net::UnixStream::from_std(server).expect("tokio stream");
+            self.log.lock().expect("lock").eis =
+                Some(tokio::spawn(super::super::eis::mock_server(server)));
+            OwnedFd::from(std::os::fd::OwnedFd::from(client))
+        }
+    }
+
+    /// Runs a mock portal on a private peer-to-peer bus.
+    async fn mock_portal() -> (zbus::Connection, zbus::Connection, Arc<StdMutex<Log>>) {
+        let log: Arc<StdMutex<Log>> = Arc::default();
+        let (server_stream, client_stream) = tokio::net::UnixStream::pair().expect("socket pair");
+        let server = zbus::connection::Builder::unix_stream(server_stream)
+            .server(zbus::Guid::generate())
+            .expect("server")
+            .p2p()
+            .serve_at(
+                "/org/freedesktop/portal/desktop",
+                MockPortal { log: log.clone() },
+            )
+            .expect("portal")
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client_stream)
+            .p2p()
+            .build();
+        let (server, client) = tokio::try_join!(server, client).expect("p2p connection");
+        (server, client, log)
+    }
+
+    #[tokio::test]
+    async fn restores_the_grant_and_types_over_eis() {
+        let (_server, client, log) = mock_portal().await;
+        let dir = std::env::temp_dir().join(format!("notype-portal-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        let token_path = dir.join(RESTORE_TOKEN_FILE);
+        std::fs::write(&token_path, "token-1\n").expect("token");
+
+        let mut keyboard = PortalKeyboard::connect(client, &token_path)
+            .await
+            .expect("session");
+        assert_eq!(
+            keyboard.keymap().and_then(|keymap| keymap.key_for('@')),
+            Some((26, false))
+        );
+        keyboard
+            .send_keys(&[(29, true), (47, true), (47, false), (29, false)])
+            .await
+            .expect("keys");
+        drop(keyboard);
+
+        let eis = {
+            let mut log = log.lock().expect("lock");
+            assert_eq!(log.restore_token.as_deref(), Some("token-1"));
+            assert_eq!(log.persist_mode, Some(PERSIST_UNTIL_REVOKED));
+            log.eis.take().expect("ConnectToEIS called")
+        };
+        assert_eq!(
+            eis.await.expect("eis"),
+            [(29, true), (47, true), (47, false), (29, false)]
+        );
+        assert_eq!(load_restore_token(&token_path).as_deref(), Some("token-2"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
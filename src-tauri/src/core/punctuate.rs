@@ -0,0 +1,310 @@
+use super::normalize::is_cjk;
+use super::stt::Transcription;
+
+/// Silence between segments that reads as a comma.
+const COMMA_PAUSE_MS: u64 = 300;
+/// Silence between segments that reads as the end of a sentence.
+const SENTENCE_PAUSE_MS: u64 = 800;
+
+/// Polite sentence endings (です・ます調), the common case for dictation.
+const SENTENCE_ENDINGS: &[&str] = &[
+    "ましょう",
+    "ください",
+    "ました",
+    "でした",
+    "ません",
+    "です",
+    "ます",
+];
+
+/// What may follow a sentence ending without closing the sentence
+/// (ですが, ますね, くださいました, でしたら, ますように, ...), or shows the
+/// "ending" was part of a longer word (これで|すべて).
+const CONTINUATIONS: &[&str] = &[
+    "が",
+    "ら",
+    "も",
+    "よう",
+    "べ",
+    "れば",
+    "こと",
+    "ね",
+    "よ",
+    "か",
+    "し",
+    "から",
+    "けど",
+    "けれど",
+    "の",
+    "な",
+    "と",
+    "って",
+    "わ",
+    "ので",
+    "のに",
+    "で",
+    "た",
+    "ん",
+    "っ",
+    "ます",
+    "ませ",
+    "まし",
+];
+
+/// Sentence openers that begin with a continuation, e.g. ですもし: still a
+/// new sentence.
+const SENTENCE_STARTS: &[&str] = &["もし", "もう", "もっと", "もちろん", "ようやく"];
+
+/// Marks after which nothing is inserted.
+const TRAILING_MARKS: &str = "。、．，！？!?.,…」』)）";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunctuateOptions {
+    pub comma_pause_ms: u64,
+    pub sentence_pause_ms: u64,
+}
+
+impl Default for PunctuateOptions {
+    fn default() -> Self {
+        Self {
+            comma_pause_ms: COMMA_PAUSE_MS,
+            sentence_pause_ms: SENTENCE_PAUSE_MS,
+        }
+    }
+}
+
+/// Adds 、 and 。 to Japanese whisper output using segment boundaries, the
+/// pauses between them and polite sentence endings. Other languages are
+/// returned unchanged; whisper already punctuates them.
+pub fn punctuate_transcription(
+    mut transcription: Transcription,
+    options: &PunctuateOptions,
+) -> Transcription {
+    if transcription
+        .output_language()
+        .is_some_and(|lang| lang != "ja")
+    {
+        return transcription;
+    }
+
+    let count = transcription.segments.len();
+    for i in 0..count {
+        let next_start = transcription.segments.get(i + 1).map(|s| s.start_ms);
+        let segment = &mut transcription.segments[i];
+        if !segment.text.chars().any(is_cjk) {
+            continue;
+        }
+        let mut text = split_sentences(segment.text.trim_end());
+        if let Some(mark) = boundary_mark(
+            &text,
+            next_start.map(|s| s.saturating_sub(segment.end_ms)),
+            options,
+        ) {
+            text.push(mark);
+        }
+        segment.text = text;
+    }
+
+    if count > 0 {
+        transcription.text = transcription
+            .segments
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect::<String>()
+            .trim()
+            .to_string();
+    }
+    transcription
+}
+
+/// The mark closing a segment. `gap_ms` is `None` for the last segment.
+fn boundary_mark(text: &str, gap_ms: Option<u64>, options: &PunctuateOptions) -> Option<char> {
+    let last = text.chars().last()?;
+    if TRAILING_MARKS.contains(last) || (!is_cjk(last) && !last.is_alphanumeric()) {
+        return None;
+    }
+    let Some(gap_ms) = gap_ms else {
+        return Some('。');
+    };
+    let sentence_end = text.ends_with('か') || SENTENCE_ENDINGS.iter().any(|e| text.ends_with(e));
+    if sentence_end || gap_ms >= options.sentence_pause_ms {
+        Some('。')
+    } else if gap_ms >= options.comma_pause_ms {
+        Some('、')
+    } else {
+        None
+    }
+}
+
+/// Inserts 。 after a sentence ending followed by the start of a new
+/// sentence within one segment, e.g. "晴れですそして" → "晴れです。そして".
+fn split_sentences(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let ending = SENTENCE_ENDINGS
+            .iter()
+            .find(|e| rest.starts_with(*e))
+            .copied();
+        match ending {
+            Some(ending) => {
+                out.push_str(ending);
+                rest = &rest[ending.len()..];
+                let continues = CONTINUATIONS.iter().any(|c| rest.starts_with(c))
+                    && !SENTENCE_STARTS.iter().any(|s| rest.starts_with(s));
+                let next = rest.trim_start().chars().next();
+                if !continues && next.is_some_and(is_cjk) {
+                    out.push('。');
+                    rest = rest.trim_start();
+                }
+            }
+            None => {
+                let c = rest.chars().next().expect("non-empty rest");
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Puts each sentence on its own line.
+pub fn break_sentences(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        out.push(c);
+        if matches!(c, '。' | '．' | '！' | '？') {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            if chars.peek().is_some()
+                && !chars.peek().is_some_and(|c| "。．！？」』）".contains(*c))
+            {
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stt::WhisperSegment;
+
+    fn segment(start_ms: u64, end_ms: u64, text: &str) -> WhisperSegment {
+        WhisperSegment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            ..WhisperSegment::default()
+        }
+    }
+
+    fn punctuate(language: Option<&str>, segments: Vec<WhisperSegment>) -> String {
+        let transcription = Transcription {
+            text: segments
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            segments,
+            language: language.map(str::to_string),
+            translated: false,
+        };
+        punctuate_transcription(transcription, &PunctuateOptions::default()).text
+    }
+
+    #[test]
+    fn pauses_and_endings_drive_marks() {
+        let cases = [
+            (
+                vec![
+                    segment(0, 1000, "今日は"),
+                    segment(1400, 2000, "晴れです"),
+                    segment(2100, 3000, "明日は雨"),
+                ],
+                "今日は、晴れです。明日は雨。",
+            ),
+            (
+                vec![
+                    segment(0, 1000, "会議を始めます"),
+                    segment(1050, 2000, "よろしくお願いします"),
+                ],
+                "会議を始めます。よろしくお願いします。",
+            ),
+            (
+                vec![segment(0, 1000, "本当"), segment(1000, 1500, "ですか")],
+                "本当ですか。",
+            ),
+            (
+                vec![segment(0, 1000, "そして"), segment(2000, 2500, "終わり")],
+                "そして。終わり。",
+            ),
+            (
+                vec![segment(0, 1000, "東京"), segment(1100, 2000, "都庁")],
+                "東京都庁。",
+            ),
+            (
+                vec![
+                    segment(0, 1000, "はい、"),
+                    segment(2000, 2500, "そうです。"),
+                ],
+                "はい、そうです。",
+            ),
+        ];
+        for (segments, expected) in cases {
+            assert_eq!(punctuate(Some("ja"), segments), expected);
+        }
+    }
+
+    #[test]
+    fn sentence_endings_split_inside_a_segment() {
+        let cases = [
+            (
+                "今日は晴れですそして明日は雨です",
+                "今日は晴れです。そして明日は雨です。",
+            ),
+            ("晴れですが寒いです", "晴れですが寒いです。"),
+            (
+                "行きましたそれから帰りました",
+                "行きました。それから帰りました。",
+            ),
+            ("ありますね", "ありますね。"),
+            ("来てくださいました", "来てくださいました。"),
+            ("雨でしたら行きます", "雨でしたら行きます。"),
+            ("これですべてです", "これですべてです。"),
+            ("晴れますように", "晴れますように。"),
+            ("いいですもの", "いいですもの。"),
+            ("行きますことにします", "行きますことにします。"),
+            (
+                "晴れですもし雨なら休みです",
+                "晴れです。もし雨なら休みです。",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                punctuate(None, vec![segment(0, 0, input)]),
+                expected,
+                "input: {input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_languages_are_untouched() {
+        let segments = vec![segment(0, 1000, " Hello"), segment(2000, 3000, " world")];
+        assert_eq!(punctuate(Some("en"), segments.clone()), "Hello world");
+        assert_eq!(punctuate(None, segments), "Hello world");
+    }
+
+    #[test]
+    fn sentences_can_be_broken_into_lines() {
+        assert_eq!(
+            break_sentences("晴れです。 明日は？「はい。」終わり"),
+            "晴れです。\n明日は？\n「はい。」終わり"
+        );
+    }
+}
@@ -0,0 +1,791 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use directories::ProjectDirs;
+use futures_util::StreamExt;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use super::config::{config_dir, save_config, AppConfig};
+use super::error::AppError;
+
+pub const LLM_API_KEY: &str = "llm-api-key";
+
+const KEYRING_PREFIX: &str = "keyring:";
+const FILE_PREFIX: &str = "file:";
+const APPLICATION_ATTRIBUTE: &str = "notype";
+
+/// (session, parameters, value, content_type) as defined by the Secret
+/// Service spec. Only the "plain" algorithm is used: the value never leaves
+/// the session bus, which is already private to the user.
+type SecretStruct = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Service",
+    default_service = "org.freedesktop.secrets",
+    default_path = "/org/freedesktop/secrets"
+)]
+trait SecretService {
+    fn open_session(
+        &self,
+        algorithm: &str,
+        input: &Value<'_>,
+    ) -> zbus::Result<(OwnedValue, OwnedObjectPath)>;
+
+    fn search_items(
+        &self,
+        attributes: HashMap<&str, &str>,
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)>;
+
+    fn unlock(
+        &self,
+        objects: &[ObjectPath<'_>],
+    ) -> zbus::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)>;
+
+    fn read_alias(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Collection",
+    default_service = "org.freedesktop.secrets"
+)]
+trait SecretCollection {
+    fn create_item(
+        &self,
+        properties: HashMap<&str, Value<'_>>,
+        secret: &SecretStruct,
+        replace: bool,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Item",
+    default_service = "org.freedesktop.secrets"
+)]
+trait SecretItem {
+    fn get_secret(&self, session: &ObjectPath<'_>) -> zbus::Result<SecretStruct>;
+
+    fn delete(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Secret.Prompt",
+    default_service = "org.freedesktop.secrets"
+)]
+trait SecretPrompt {
+    fn prompt(&self, window_id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn completed(&self, dismissed: bool, result: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Secret storage backed by the freedesktop Secret Service (GNOME Keyring,
+/// KWallet), with an encrypted file as a last resort.
+pub struct SecretStore {
+    conn: Option<zbus::Connection>,
+    file_dir: PathBuf,
+    key_path: PathBuf,
+}
+
+impl SecretStore {
+    pub async fn connect() -> Result<Self, AppError> {
+        let file_dir = config_dir()
+            .map_err(|e| AppError::new("設定ディレクトリを解決できません", e.to_string()))?
+            .join("secrets");
+        // Kept out of the config dir so a copy of it alone cannot decrypt.
+        let key_path = ProjectDirs::from("dev", "notype", "notype")
+            .map(|dirs| dirs.data_local_dir().join("master.key"))
+            .ok_or_else(|| {
+                AppError::new(
+                    "データディレクトリを解決できません",
+                    "could not resolve data dir",
+                )
+            })?;
+        let conn = match zbus::Connection::session().await {
+            Ok(conn) => Some(conn),
+            Err(err) => {
+                tracing::warn!("secret: session bus unavailable: {err}");
+                None
+            }
+        };
+        Ok(Self {
+            conn,
+            file_dir,
+            key_path,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn with_connection(
+        conn: Option<zbus::Connection>,
+        file_dir: PathBuf,
+        key_path: PathBuf,
+    ) -> Self {
+        Self {
+            conn,
+            file_dir,
+            key_path,
+        }
+    }
+
+    /// Stores `value` under `name` and returns the reference to keep in the
+    /// config. Falls back to the encrypted file when no keyring answers.
+    pub async fn store(&self, name: &str, value: &str) -> Result<String, AppError> {
+        match self.keyring_store(name, value).await {
+            Ok(()) => {
+                // Do not leave a stale copy behind from an earlier fallback.
+                let _ = std::fs::remove_file(self.file_dir.join(format!("{name}.enc")));
+                Ok(format!("{KEYRING_PREFIX}{name}"))
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "secret: keyring unavailable, storing {name} in an encrypted file instead: {}",
+                    err.details
+                );
+                file_store(&self.file_dir, &self.key_path, name, value)?;
+                Ok(format!("{FILE_PREFIX}{name}"))
+            }
+        }
+    }
+
+    pub async fn load(&self, reference: &str) -> Result<String, AppError> {
+        if let Some(name) = reference.strip_prefix(KEYRING_PREFIX) {
+            self.keyring_load(name).await
+        } else if let Some(name) = reference.strip_prefix(FILE_PREFIX) {
+            file_load(&self.file_dir, &self.key_path, name)
+        } else {
+            Err(AppError::new(
+                "シークレットの参照が不正です",
+                format!("unknown secret reference: {reference}"),
+            ))
+        }
+    }
+
+    pub async fn delete(&self, reference: &str) -> Result<(), AppError> {
+        if let Some(name) = reference.strip_prefix(KEYRING_PREFIX) {
+            self.keyring_delete(name).await
+        } else if let Some(name) = reference.strip_prefix(FILE_PREFIX) {
+            let _ = std::fs::remove_file(self.file_dir.join(format!("{name}.enc")));
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn connection(&self) -> Result<&zbus::Connection, AppError> {
+        self.conn
+            .as_ref()
+            .ok_or_else(|| keyring_error("session bus unavailable"))
+    }
+
+    async fn service(&self) -> Result<(SecretServiceProxy<'_>, OwnedObjectPath), AppError> {
+        let service = SecretServiceProxy::builder(self.connection()?)
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await
+            .map_err(keyring_error)?;
+        let (_, session) = service
+            .open_session("plain", &Value::from(""))
+            .await
+            .map_err(keyring_error)?;
+        Ok((service, session))
+    }
+
+    async fn keyring_store(&self, name: &str, value: &str) -> Result<(), AppError> {
+        let conn = self.connection()?;
+        let (service, session) = self.service().await?;
+        let collection = service.read_alias("default").await.map_err(keyring_error)?;
+        if collection.as_str() == "/" {
+            return Err(keyring_error("no default collection"));
+        }
+        self.unlock(&service, &collection).await?;
+
+        let mut properties: HashMap<&str, Value<'_>> = HashMap::new();
+        properties.insert(
+            "org.freedesktop.Secret.Item.Label",
+            Value::from(format!("notype {name}")),
+        );
+        properties.insert(
+            "org.freedesktop.Secret.Item.Attributes",
+            Value::from(attributes(name)),
+        );
+        let secret: SecretStruct = (
+            session,
+            Vec::new(),
+            value.as_bytes().to_vec(),
+            "text/plain".to_string(),
+        );
+
+        let (_, prompt) = SecretCollectionProxy::builder(conn)
+            .path(collection)
+            .map_err(keyring_error)?
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await
+            .map_err(keyring_error)?
+            .create_item(properties, &secret, true)
+            .await
+            .map_err(keyring_error)?;
+        self.run_prompt(&prompt).await
+    }
+
+    async fn keyring_load(&self, name: &str) -> Result<String, AppError> {
+        let (service, session) = self.service().await?;
+        let item = self.find_item(&service, name).await?.ok_or_else(|| {
+            AppError::new(
+                "キーリングに API キーが見つかりません",
+                format!("no keyring item for {name}"),
+            )
+        })?;
+        let (_, _, value, _) = self
+            .item(&item)
+            .await?
+            .get_secret(&session)
+            .await
+            .map_err(keyring_error)?;
+        String::from_utf8(value).map_err(keyring_error)
+    }
+
+    async fn keyring_delete(&self, name: &str) -> Result<(), AppError> {
+        let (service, _) = self.service().await?;
+        if let Some(item) = self.find_item(&service, name).await? {
+            let prompt = self
+                .item(&item)
+                .await?
+                .delete()
+                .await
+                .map_err(keyring_error)?;
+            self.run_prompt(&prompt).await?;
+        }
+        Ok(())
+    }
+
+    async fn find_item(
+        &self,
+        service: &SecretServiceProxy<'_>,
+        name: &str,
+    ) -> Result<Option<OwnedObjectPath>, AppError> {
+        let (unlocked, locked) = service
+            .search_items(attributes(name))
+            .await
+            .map_err(keyring_error)?;
+        if let Some(item) = unlocked.into_iter().next() {
+            return Ok(Some(item));
+        }
+        match locked.into_iter().next() {
+            Some(item) => {
+                self.unlock(service, &item).await?;
+                Ok(Some(item))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn item(&self, path: &OwnedObjectPath) -> Result<SecretItemProxy<'_>, AppError> {
+        SecretItemProxy::builder(self.connection()?)
+            .path(path.clone())
+            .map_err(keyring_error)?
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await
+            .map_err(keyring_error)
+    }
+
+    async fn unlock(
+        &self,
+        service: &SecretServiceProxy<'_>,
+        object: &OwnedObjectPath,
+    ) -> Result<(), AppError> {
+        let (_, prompt) = service
+            .unlock(&[object.as_ref()])
+            .await
+            .map_err(keyring_error)?;
+        self.run_prompt(&prompt).await
+    }
+
+    /// Shows a keyring prompt (e.g. the unlock dialog) and waits for the user.
+    async fn run_prompt(&self, prompt: &OwnedObjectPath) -> Result<(), AppError> {
+        if prompt.as_str() == "/" {
+            return Ok(());
+        }
+        let proxy = SecretPromptProxy::builder(self.connection()?)
+            .path(prompt.clone())
+            .map_err(keyring_error)?
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .await
+            .map_err(keyring_error)?;
+        let mut completed = proxy.receive_completed().await.map_err(keyring_error)?;
+        proxy.prompt("").await.map_err(keyring_error)?;
+        let signal = completed
+            .next()
+            .await
+            .ok_or_else(|| keyring_error("prompt closed"))?;
+        let dismissed = signal.args().map_err(keyring_error)?.dismissed;
+        if dismissed {
+            return Err(AppError::new(
+                "キーリングのロック解除がキャンセルされました",
+                "secret service prompt dismissed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Moves a plaintext API key (from an older config.json) into the secret
+/// store, or loads the stored key into memory. The key only ever lives in
+/// `AppConfig::llm_api_key` at runtime, which is never serialized.
+pub async fn resolve_llm_api_key(config: &mut AppConfig) {
+    if config.llm_api_key.is_empty() && config.llm_api_key_ref.is_empty() {
+        return;
+    }
+    let store = match SecretStore::connect().await {
+        Ok(store) => store,
+        Err(err) => {
+            tracing::warn!("secret: store unavailable: {}", err.details);
+            return;
+        }
+    };
+
+    if !config.llm_api_key.is_empty() {
+        match store.store(LLM_API_KEY, &config.llm_api_key).await {
+            Ok(reference) => {
+                config.llm_api_key_ref = reference;
+                match save_config(config) {
+                    Ok(()) => {
+                        tracing::info!("secret: migrated plaintext API key out of config.json")
+                    }
+                    Err(err) => tracing::warn!("secret: failed to rewrite config: {err}"),
+                }
+            }
+            Err(err) => tracing::warn!("secret: failed to migrate API key: {}", err.details),
+        }
+        return;
+    }
+
+    match store.load(&config.llm_api_key_ref).await {
+        Ok(key) => config.llm_api_key = key,
+        Err(err) => tracing::warn!("secret: failed to load API key: {}", err.details),
+    }
+}
+
+/// Stores a key entered in the settings UI and returns its reference.
+pub async fn store_llm_api_key(key: &str) -> Result<String, AppError> {
+    SecretStore::connect().await?.store(LLM_API_KEY, key).await
+}
+
+pub async fn delete_secret(reference: &str) -> Result<(), AppError> {
+    SecretStore::connect().await?.delete(reference).await
+}
+
+fn attributes(name: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", APPLICATION_ATTRIBUTE), ("key", name)])
+}
+
+fn keyring_error(err: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        "キーリングにアクセスできません",
+        format!("secret service: {err}"),
+    )
+}
+
+const FILE_VERSION: &str = "v1:";
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+
+/// Fallback used only when no Secret Service is running: XChaCha20-Poly1305
+/// with the secret's name as associated data. The key file lives in the data
+/// dir, apart from the secrets, but anyone who can read both as the user can
+/// still decrypt; it is not a substitute for a keyring.
+fn file_store(dir: &Path, key_path: &Path, name: &str, value: &str) -> Result<(), AppError> {
+    let cipher = file_cipher(key_path, true)?;
+    let nonce: [u8; NONCE_LEN] = random_bytes()?;
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: value.as_bytes(),
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| file_error(dir, "encryption failed"))?;
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+
+    write_private(
+        &dir.join(format!("{name}.enc")),
+        format!("{FILE_VERSION}{}", BASE64.encode(payload)).as_bytes(),
+    )
+}
+
+fn file_load(dir: &Path, key_path: &Path, name: &str) -> Result<String, AppError> {
+    let path = dir.join(format!("{name}.enc"));
+    let raw = std::fs::read_to_string(&path).map_err(|e| file_error(&path, e))?;
+    let payload = raw
+        .trim()
+        .strip_prefix(FILE_VERSION)
+        .and_then(|b64| BASE64.decode(b64).ok())
+        .filter(|p| p.len() >= NONCE_LEN + TAG_LEN)
+        .ok_or_else(|| file_error(&path, "malformed secret file"))?;
+
+    let cipher = file_cipher(key_path, false)?;
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let plaintext = cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: name.as_bytes(),
+            },
+        )
+        .map_err(|_| file_error(&path, "integrity check failed"))?;
+    String::from_utf8(plaintext).map_err(|e| file_error(&path, e))
+}
+
+/// Loads the key file. A missing key is only generated when storing; a key
+/// that exists but cannot be read is an error, since replacing it would make
+/// every stored secret unrecoverable.
+fn file_cipher(path: &Path, create: bool) -> Result<XChaCha20Poly1305, AppError> {
+    let key = match std::fs::read(path) {
+        Ok(raw) => <[u8; 32]>::try_from(raw.as_slice())
+            .map_err(|_| file_error(path, "key file has the wrong length"))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && create => {
+            let key: [u8; 32] = random_bytes()?;
+            create_private(path, &key).map_err(|e| file_error(path, e))?;
+            key
+        }
+        Err(err) => return Err(file_error(path, err)),
+    };
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], AppError> {
+    let mut bytes = [0u8; N];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut bytes))
+        .map_err(|e| AppError::new("乱数の取得に失敗しました", e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Replaces `path` through a temporary file that is created as 0600, so the
+/// contents are never readable by others, not even briefly.
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), AppError> {
+    let tmp = path.with_extension("tmp");
+    let _ = std::fs::remove_file(&tmp);
+    create_private(&tmp, contents)
+        .and_then(|()| std::fs::rename(&tmp, path))
+        .map_err(|e| file_error(path, e))
+}
+
+fn create_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn file_error(path: &Path, err: impl std::fmt::Display) -> AppError {
+    AppError::new(
+        "暗号化ファイルのシークレットを扱えません",
+        format!("{}: {err}", path.display()),
+    )
+}
+
+#[cfg(test)]
+/// A private session bus from `dbus-daemon`, stopped on drop.
+pub(crate) struct PrivateBus {
+    daemon: std::process::Child,
+    address: String,
+}
+
+#[cfg(test)]
+impl PrivateBus {
+    /// `None` when `dbus-daemon` is not installed.
+    pub(crate) fn start() -> Option<Self> {
+        use std::io::BufRead;
+
+        let mut daemon = std::process::Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .ok()?;
+        let stdout = daemon.stdout.take().expect("stdout");
+        let mut address = String::new();
+        std::io::BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("bus address");
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub(crate) fn connect(&self) -> zbus::connection::Builder<'static> {
+        zbus::connection::Builder::address(self.address.as_str()).expect("address")
+    }
+}
+
+#[cfg(test)]
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use zbus::{interface, ObjectServer};
+
+    const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/login";
+    const SESSION_PATH: &str = "/org/freedesktop/secrets/session/1";
+
+    type Items = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    struct MockService {
+        items: Items,
+    }
+
+    #[interface(name = "org.freedesktop.Secret.Service")]
+    impl MockService {
+        fn open_session(
+            &self,
+            algorithm: &str,
+            _input: Value<'_>,
+        ) -> (OwnedValue, OwnedObjectPath) {
+            assert_eq!(algorithm, "plain");
+            (
+                OwnedValue::try_from(Value::from("")).expect("value"),
+                OwnedObjectPath::try_from(SESSION_PATH).expect("path"),
+            )
+        }
+
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            let key = attributes.get("key").cloned().unwrap_or_default();
+            let found = self.items.lock().expect("lock").contains_key(&key)
+                && attributes.get("application").map(String::as_str) == Some("notype");
+            let items = found
+                .then(|| item_path(&key))
+                .into_iter()
+                .collect::<Vec<_>>();
+            (items, Vec::new())
+        }
+
+        fn unlock(&self, objects: Vec<OwnedObjectPath>) -> (Vec<OwnedObjectPath>, OwnedObjectPath) {
+            (objects, OwnedObjectPath::try_from("/").expect("path"))
+        }
+
+        fn read_alias(&self, name: &str) -> OwnedObjectPath {
+            assert_eq!(name, "default");
+            OwnedObjectPath::try_from(COLLECTION_PATH).expect("path")
+        }
+    }
+
+    struct MockCollection {
+        items: Items,
+    }
+
+    #[interface(name = "org.freedesktop.Secret.Collection")]
+    impl MockCollection {
+        async fn create_item(
+            &self,
+            #[zbus(object_server)] server: &ObjectServer,
+            properties: HashMap<String, OwnedValue>,
+            secret: SecretStruct,
+            replace: bool,
+        ) -> (OwnedObjectPath, OwnedObjectPath) {
+            assert!(replace);
+            let attributes: HashMap<String, String> = properties
+                ["org.freedesktop.Secret.Item.Attributes"]
+                .try_clone()
+                .expect("clone")
+                .try_into()
+                .expect("attributes");
+            let key = attributes["key"].clone();
+            assert_eq!(secret.0.as_str(), SESSION_PATH);
+            self.items
+                .lock()
+                .expect("lock")
+                .insert(key.clone(), secret.2);
+
+            let path = item_path(&key);
+            let _ = server
+                .at(
+                    path.clone(),
+                    MockItem {
+                        key,
+                        items: self.items.clone(),
+                    },
+                )
+                .await;
+            (path, OwnedObjectPath::try_from("/").expect("path"))
+        }
+    }
+
+    struct MockItem {
+        key: String,
+        items: Items,
+    }
+
+    #[interface(name = "org.freedesktop.Secret.Item")]
+    impl MockItem {
+        fn get_secret(&self, session: OwnedObjectPath) -> SecretStruct {
+            let value = self.items.lock().expect("lock")[&self.key].clone();
+            (session, Vec::new(), value, "text/plain".to_string())
+        }
+
+        fn delete(&self) -> OwnedObjectPath {
+            self.items.lock().expect("lock").remove(&self.key);
+            OwnedObjectPath::try_from("/").expect("path")
+        }
+    }
+
+    fn item_path(key: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!("{COLLECTION_PATH}/{}", key.replace('-', "_")))
+            .expect("path")
+    }
+
+    /// Runs a mock Secret Service that owns `org.freedesktop.secrets` on
+    /// `bus`, so the client finds it by name like the real one.
+    async fn mock_keyring(bus: &PrivateBus) -> (zbus::Connection, zbus::Connection, Items) {
+        let items: Items = Arc::default();
+        let server = bus
+            .connect()
+            .name("org.freedesktop.secrets")
+            .expect("name")
+            .serve_at(
+                "/org/freedesktop/secrets",
+                MockService {
+                    items: items.clone(),
+                },
+            )
+            .expect("service")
+            .serve_at(
+                COLLECTION_PATH,
+                MockCollection {
+                    items: items.clone(),
+                },
+            )
+            .expect("collection")
+            .build()
+            .await
+            .expect("server");
+        let client = bus.connect().build().await.expect("client");
+        (server, client, items)
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("notype-secret-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn keyring_roundtrip_and_delete() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("skipped: dbus-daemon is not installed");
+            return;
+        };
+        let (_server, client, items) = mock_keyring(&bus).await;
+        let dir = temp_dir();
+        let store =
+            SecretStore::with_connection(Some(client), dir.clone(), dir.join("data/master.key"));
+
+        let reference = store.store(LLM_API_KEY, "sk-test").await.expect("store");
+        assert_eq!(reference, "keyring:llm-api-key");
+        assert_eq!(
+            items.lock().expect("lock").get(LLM_API_KEY),
+            Some(&b"sk-test".to_vec())
+        );
+        assert!(!dir.join("llm-api-key.enc").exists());
+
+        assert_eq!(store.load(&reference).await.expect("load"), "sk-test");
+        store.delete(&reference).await.expect("delete");
+        assert!(items.lock().expect("lock").is_empty());
+        assert!(store.load(&reference).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_encrypted_file_without_keyring() {
+        let dir = temp_dir();
+        let key_path = dir.join("data/master.key");
+        let store = SecretStore::with_connection(None, dir.join("secrets"), key_path.clone());
+
+        let reference = store.store(LLM_API_KEY, "sk-file").await.expect("store");
+        assert_eq!(reference, "file:llm-api-key");
+        let secret = dir.join("secrets/llm-api-key.enc");
+        let raw = std::fs::read_to_string(&secret).expect("file");
+        assert!(raw.starts_with(FILE_VERSION));
+        assert!(!raw.contains("sk-file"));
+        for path in [&secret, &key_path] {
+            let mode = std::fs::metadata(path)
+                .expect("metadata")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert_eq!(store.load(&reference).await.expect("load"), "sk-file");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn tampered_file_or_damaged_key_is_rejected() {
+        let dir = temp_dir();
+        let key_path = dir.join("master.key");
+        file_store(&dir, &key_path, "k", "secret value").expect("store");
+        assert_eq!(
+            file_load(&dir, &key_path, "k").expect("load"),
+            "secret value"
+        );
+        let path = dir.join("k.enc");
+        let raw = std::fs::read_to_string(&path).expect("read");
+        let mut payload = BASE64
+            .decode(raw.trim().strip_prefix(FILE_VERSION).expect("version"))
+            .expect("base64");
+        payload[NONCE_LEN] ^= 1;
+        std::fs::write(&path, format!("{FILE_VERSION}{}", BASE64.encode(payload))).expect("write");
+
+        assert!(file_load(&dir, &key_path, "k").is_err());
+
+        // A damaged key is reported, never silently replaced.
+        std::fs::write(&key_path, b"short").expect("write");
+        assert!(file_store(&dir, &key_path, "other", "value").is_err());
+        assert_eq!(std::fs::read(&key_path).expect("read"), b"short");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn api_key_is_never_serialized() {
+        let cfg = AppConfig {
+            llm_api_key: "sk-secret".to_string(),
+            llm_api_key_ref: "keyring:llm-api-key".to_string(),
+            ..AppConfig::default()
+        };
+        let json = serde_json::to_string(&cfg).expect("json");
+        assert!(!json.contains("sk-secret"));
+        assert!(json.contains("keyring:llm-api-key"));
+
+        let legacy: AppConfig = serde_json::from_str(r#"{"llmApiKey":"sk-old"}"#).expect("parse");
+        assert_eq!(legacy.llm_api_key, "sk-old");
+    }
+}
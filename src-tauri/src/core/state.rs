@@ -1,21 +1,39 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum RuntimeState {
+    #[default]
     Idle,
     Recording,
     Processing,
+    /// The LLM is post-processing the transcript.
+    PostProcessing,
     Ready,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl RuntimeState {
+    /// Transcribing or post-processing; a new recording has to wait.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, Self::Processing | Self::PostProcessing)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TranscriptEvent {
     pub partial_text: String,
     pub final_text: Option<String>,
     pub state: RuntimeState,
     pub latency_ms: Option<u64>,
+    /// Whether whisper's translate task turned the speech into English.
+    pub translated: bool,
+    /// Language whisper detected (or was told) the speech was in.
+    pub source_language: Option<String>,
+    /// Filler words dropped from `final_text`.
+    pub fillers_removed: usize,
+    /// Injection backend that typed `final_text`, e.g. `wtype`.
+    pub injection_backend: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -25,6 +43,30 @@ pub struct ErrorEvent {
     pub details: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelledEvent {
+    pub reason: String,
+    /// State the session was in when it was cancelled.
+    pub during: RuntimeState,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredEvent {
+    pub reasons: Vec<String>,
+    pub original_text: String,
+}
+
+/// A spoken macro that ran, or would have run in dry-run mode.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MacroEvent {
+    pub phrase: String,
+    pub steps: Vec<String>,
+    pub dry_run: bool,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModelDownloadEvent {
@@ -0,0 +1,161 @@
+/// Longest `[KIND_n]` placeholder held back while it is still arriving.
+const PLACEHOLDER_MAX: usize = 32;
+
+/// Splits a `text/event-stream` body into the `data` of its events.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Feeds raw bytes, which may end mid-line or mid-character, and returns
+    /// the data of every event they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                events.extend(self.take_event());
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data
+                    .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // Comments, `event:`, `id:` and `retry:` lines are not needed.
+        }
+        events
+    }
+
+    /// The data of a last event that was not followed by a blank line.
+    pub fn finish(&mut self) -> Option<String> {
+        let mut events = if self.buffer.is_empty() {
+            Vec::new()
+        } else {
+            self.push(b"\n")
+        };
+        events.extend(self.take_event());
+        events.pop()
+    }
+
+    fn take_event(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+        let event = self.data.join("\n");
+        self.data.clear();
+        Some(event)
+    }
+}
+
+/// Buffers streamed text and releases it in pieces that are safe to type:
+/// whole ASCII words, single other characters, never trailing whitespace and
+/// never part of a `[KIND_n]` placeholder. Leading whitespace is dropped, so
+/// the pieces add up to the trimmed response.
+#[derive(Debug, Default)]
+pub struct ChunkBuffer {
+    pending: String,
+    started: bool,
+}
+
+impl ChunkBuffer {
+    pub fn push(&mut self, delta: &str) -> Option<String> {
+        self.pending.push_str(delta);
+        let cut = self.cut();
+        self.release(cut)
+    }
+
+    /// Whatever is left once the stream has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        let cut = self.pending.trim_end().len();
+        let rest = self.release(cut);
+        self.pending.clear();
+        rest
+    }
+
+    fn cut(&self) -> usize {
+        let held = self
+            .pending
+            .rfind('[')
+            .filter(|&i| {
+                let tail = &self.pending[i..];
+                !tail.contains(']') && tail.len() <= PLACEHOLDER_MAX
+            })
+            .unwrap_or(self.pending.len());
+        let mut cut = 0;
+        for (i, c) in self.pending[..held].char_indices() {
+            let end = i + c.len_utf8();
+            let next = self.pending[end..].chars().next();
+            let mid_word =
+                c.is_ascii_alphanumeric() && next.is_none_or(|n| n.is_ascii_alphanumeric());
+            if !c.is_whitespace() && !mid_word {
+                cut = end;
+            }
+        }
+        cut
+    }
+
+    fn release(&mut self, cut: usize) -> Option<String> {
+        let chunk: String = self.pending.drain(..cut).collect();
+        let chunk = if self.started {
+            chunk
+        } else {
+            chunk.trim_start().to_string()
+        };
+        if chunk.is_empty() {
+            return None;
+        }
+        self.started = true;
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_events_survive_split_reads() {
+        let mut decoder = SseDecoder::default();
+        let body =
+            "data: {\"a\":1}\r\n\r\n: keep-alive\n\ndata: 日本\ndata: 語\n\nevent: x\ndata: [DONE]";
+        let bytes = body.as_bytes();
+        let mut events = Vec::new();
+        // Byte-sized reads also split the multi-byte characters.
+        for byte in bytes {
+            events.extend(decoder.push(std::slice::from_ref(byte)));
+        }
+        events.extend(decoder.finish());
+        assert_eq!(events, ["{\"a\":1}", "日本\n語", "[DONE]"]);
+    }
+
+    fn chunks(deltas: &[&str]) -> Vec<String> {
+        let mut buffer = ChunkBuffer::default();
+        let mut out: Vec<String> = deltas.iter().filter_map(|d| buffer.push(d)).collect();
+        out.extend(buffer.finish());
+        out
+    }
+
+    #[test]
+    fn chunks_end_at_word_or_character_boundaries() {
+        assert_eq!(
+            chunks(&["  Hel", "lo wor", "ld", ".\n"]),
+            ["Hello", " world."]
+        );
+        assert_eq!(chunks(&["明日", "は晴", "れ"]), ["明日", "は晴", "れ"]);
+        assert_eq!(chunks(&["Tauri", "版です"]), ["Tauri版です"]);
+        assert_eq!(chunks(&["   ", "\n"]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn placeholders_are_held_until_complete() {
+        assert_eq!(
+            chunks(&["送り先は [EMA", "IL_1", "] です"]),
+            ["送り先は", " [EMAIL_1] です"]
+        );
+        let long = format!("[{}", "x".repeat(40));
+        assert_eq!(chunks(&[&long]).concat(), long);
+    }
+}
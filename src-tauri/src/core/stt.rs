@@ -3,16 +3,156 @@ use std::time::Instant;
 
 use directories::ProjectDirs;
 use futures_util::StreamExt;
+use serde::Deserialize;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 
-use super::config::ModelSize;
+use super::config::{AppConfig, ModelSize};
 use super::error::AppError;
+use super::normalize::join_lines;
 
 pub struct SttService {
     pub model: ModelSize,
     pub model_dir: PathBuf,
+    pub options: DecodeOptions,
+}
+
+/// Decoding parameters forwarded to whisper-cli on both partial and final runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeOptions {
+    /// Spoken language code passed to `-l`, or "auto" to let whisper detect it.
+    pub language: String,
+    /// Run whisper's translate task, which always produces English.
+    pub translate: bool,
+    /// Initial prompt used to bias recognition towards known vocabulary.
+    pub prompt: String,
+    pub perf: PerfParams,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            language: "ja".to_string(),
+            translate: false,
+            prompt: String::new(),
+            perf: PerfParams::default(),
+        }
+    }
+}
+
+impl DecodeOptions {
+    pub fn whisper_args(&self) -> Vec<String> {
+        let mut args = vec!["-l".to_string(), self.language.clone()];
+        if self.translate {
+            args.push("-tr".to_string());
+        }
+        if !self.prompt.is_empty() {
+            args.push("--prompt".to_string());
+            args.push(self.prompt.clone());
+        }
+        args.extend(self.perf.whisper_args());
+        args
+    }
+}
+
+/// Speed/accuracy trade-offs. Zero means "leave whisper-cli's default".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PerfParams {
+    pub threads: u32,
+    pub beam_size: u32,
+    pub best_of: u32,
+    /// Disable temperature fallback, which re-decodes low-confidence segments.
+    pub no_fallback: bool,
+    /// Encoder context size; below 1500 trades accuracy for speed.
+    pub audio_ctx: u32,
+}
+
+impl PerfParams {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            threads: config.whisper_threads,
+            beam_size: config.whisper_beam_size,
+            best_of: config.whisper_best_of,
+            no_fallback: config.whisper_no_fallback,
+            audio_ctx: config.whisper_audio_ctx,
+        }
+    }
+
+    pub fn write_to(&self, config: &mut AppConfig) {
+        config.whisper_threads = self.threads;
+        config.whisper_beam_size = self.beam_size;
+        config.whisper_best_of = self.best_of;
+        config.whisper_no_fallback = self.no_fallback;
+        config.whisper_audio_ctx = self.audio_ctx;
+    }
+
+    fn whisper_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (flag, value) in [
+            ("-t", self.threads),
+            ("-bs", self.beam_size),
+            ("-bo", self.best_of),
+            ("-ac", self.audio_ctx),
+        ] {
+            if value > 0 {
+                args.push(flag.to_string());
+                args.push(value.to_string());
+            }
+        }
+        if self.no_fallback {
+            args.push("-nf".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WhisperSegment {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+    /// Mean probability of the non-special tokens, when whisper reports them.
+    pub avg_token_prob: Option<f32>,
+    /// Only emitted by whisper-cli builds that expose the no-speech head.
+    pub no_speech_prob: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+    pub segments: Vec<WhisperSegment>,
+    /// Source language of the speech, as detected or forced via `-l`.
+    pub language: Option<String>,
+    pub translated: bool,
+}
+
+impl Transcription {
+    pub fn from_text(text: String) -> Self {
+        let segments = if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![WhisperSegment {
+                text: text.clone(),
+                ..WhisperSegment::default()
+            }]
+        };
+        Self {
+            text,
+            segments,
+            language: None,
+            translated: false,
+        }
+    }
+
+    /// Language of `text`, which differs from the source when translated.
+    pub fn output_language(&self) -> Option<&str> {
+        if self.translated {
+            Some("en")
+        } else {
+            self.language.as_deref()
+        }
+    }
 }
 
 impl SttService {
@@ -21,7 +161,19 @@ impl SttService {
             .map(PathBuf::from)
             .unwrap_or_else(|_| default_model_dir());
 
-        Self { model, model_dir }
+        Self {
+            model,
+            model_dir,
+            options: DecodeOptions::default(),
+        }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        let mut service = Self::new(config.model);
+        service.options.language = config.language.clone();
+        service.options.translate = config.translate;
+        service.options.perf = PerfParams::from_config(config);
+        service
     }
 
     pub fn model_filename(&self) -> &'static str {
@@ -35,6 +187,18 @@ impl SttService {
         self.model_dir.join(self.model_filename())
     }
 
+    fn whisper_command(&self, wav_path: &Path) -> Command {
+        let mut cmd = Command::new("whisper-cli");
+        cmd.arg("-m")
+            .arg(self.model_path())
+            .arg("-f")
+            .arg(wav_path)
+            .arg("-nt")
+            .args(self.options.whisper_args())
+            .kill_on_drop(true);
+        cmd
+    }
+
     pub async fn ensure_model(&self) -> Result<(), AppError> {
         self.ensure_model_with_progress(|_, _, _| {}).await
     }
@@ -125,24 +289,18 @@ impl SttService {
         Ok(())
     }
 
-    pub async fn transcribe_final(&self, wav_path: &Path) -> Result<(String, u64), AppError> {
+    pub async fn transcribe_final(
+        &self,
+        wav_path: &Path,
+    ) -> Result<(Transcription, u64), AppError> {
         self.ensure_model().await?;
         let started = Instant::now();
-        let txt_path = PathBuf::from(format!("{}.txt", wav_path.display()));
-        let _ = std::fs::remove_file(&txt_path);
+        let json_path = PathBuf::from(format!("{}.json", wav_path.display()));
+        let _ = std::fs::remove_file(&json_path);
 
         let output = timeout(
             Duration::from_secs(45),
-            Command::new("whisper-cli")
-                .arg("-m")
-                .arg(self.model_path())
-                .arg("-f")
-                .arg(wav_path)
-                .arg("-otxt")
-                .arg("-nt")
-                .arg("-l")
-                .arg("ja")
-                .output(),
+            self.whisper_command(wav_path).arg("-ojf").output(),
         )
         .await
         .map_err(|_| {
@@ -165,17 +323,24 @@ impl SttService {
             ));
         }
 
-        // whisper-cli with -otxt writes to "<audio_path>.txt". Prefer this file.
-        let mut text = std::fs::read_to_string(&txt_path)
-            .map(|s| s.trim().to_string())
+        // whisper-cli with -ojf writes to "<audio_path>.json". Prefer this file
+        // because it carries segment timing, token probabilities and language.
+        let mut transcription = std::fs::read_to_string(&json_path)
+            .ok()
+            .and_then(|raw| parse_whisper_json(&raw))
             .unwrap_or_default();
 
-        if text.is_empty() {
-            text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if transcription.text.is_empty() {
+            let raw = String::from_utf8_lossy(&output.stdout).to_string();
+            transcription = Transcription::from_text(parse_whisper_text(&raw));
+        }
+        if transcription.language.is_none() && self.options.language != "auto" {
+            transcription.language = Some(self.options.language.clone());
         }
+        transcription.translated = self.options.translate;
 
-        let _ = std::fs::remove_file(&txt_path);
-        Ok((text, started.elapsed().as_millis() as u64))
+        let _ = std::fs::remove_file(&json_path);
+        Ok((transcription, started.elapsed().as_millis() as u64))
     }
 
     pub async fn transcribe_partial_hint(
@@ -195,16 +360,7 @@ impl SttService {
         let txt_path = PathBuf::from(format!("{}.txt", wav_path.display()));
         let output = timeout(
             Duration::from_secs(5),
-            Command::new("whisper-cli")
-                .arg("-m")
-                .arg(self.model_path())
-                .arg("-f")
-                .arg(wav_path)
-                .arg("-otxt")
-                .arg("-nt")
-                .arg("-l")
-                .arg("ja")
-                .output(),
+            self.whisper_command(wav_path).arg("-otxt").output(),
         )
         .await
         .map_err(|_| {
@@ -246,7 +402,8 @@ fn default_model_dir() -> PathBuf {
 }
 
 fn parse_whisper_text(raw: &str) -> String {
-    raw.lines()
+    let lines = raw
+        .lines()
         .map(str::trim)
         .filter(|line| !line.is_empty())
         .map(|line| {
@@ -258,9 +415,90 @@ fn parse_whisper_text(raw: &str) -> String {
         })
         .filter(|line| !line.is_empty())
         .collect::<Vec<_>>()
-        .join(" ")
+        .join("\n");
+    join_lines(&lines)
+}
+
+#[derive(Deserialize)]
+struct WhisperJson {
+    #[serde(default)]
+    result: Option<WhisperJsonResult>,
+    #[serde(default)]
+    transcription: Vec<WhisperJsonSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonResult {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonSegment {
+    #[serde(default)]
+    offsets: WhisperJsonOffsets,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tokens: Vec<WhisperJsonToken>,
+    #[serde(default)]
+    no_speech_prob: Option<f32>,
+}
+
+#[derive(Default, Deserialize)]
+struct WhisperJsonOffsets {
+    from: u64,
+    to: u64,
+}
+
+#[derive(Deserialize)]
+struct WhisperJsonToken {
+    #[serde(default)]
+    text: String,
+    p: Option<f32>,
+}
+
+fn parse_whisper_json(raw: &str) -> Option<Transcription> {
+    let parsed: WhisperJson = serde_json::from_str(raw).ok()?;
+    let segments: Vec<WhisperSegment> = parsed
+        .transcription
+        .into_iter()
+        .map(|seg| {
+            // Special tokens such as "[_BEG_]" or "[_TT_150]" carry no speech signal.
+            let probs: Vec<f32> = seg
+                .tokens
+                .iter()
+                .filter(|t| !t.text.starts_with("[_"))
+                .filter_map(|t| t.p)
+                .collect();
+            let avg_token_prob = if probs.is_empty() {
+                None
+            } else {
+                Some(probs.iter().sum::<f32>() / probs.len() as f32)
+            };
+            WhisperSegment {
+                start_ms: seg.offsets.from,
+                end_ms: seg.offsets.to,
+                text: seg.text,
+                avg_token_prob,
+                no_speech_prob: seg.no_speech_prob,
+            }
+        })
+        .filter(|seg| !seg.text.trim().is_empty())
+        .collect();
+
+    let text = segments
+        .iter()
+        .map(|seg| seg.text.as_str())
+        .collect::<String>()
         .trim()
-        .to_string()
+        .to_string();
+
+    Some(Transcription {
+        text,
+        segments,
+        language: parsed.result.and_then(|r| r.language),
+        translated: false,
+    })
 }
 
 fn model_download_url(model: ModelSize) -> &'static str {
@@ -286,6 +524,75 @@ mod tests {
         assert_eq!(medium.model_filename(), "ggml-medium.bin");
     }
 
+    #[test]
+    fn whisper_json_keeps_segments_language_and_probabilities() {
+        let raw = r#"{
+            "result": {"language": "ja"},
+            "transcription": [
+                {
+                    "offsets": {"from": 0, "to": 1200},
+                    "text": "こんにちは",
+                    "tokens": [
+                        {"text": "[_BEG_]", "p": 0.1},
+                        {"text": "こんにちは", "p": 0.9},
+                        {"text": "[_TT_60]", "p": 0.2}
+                    ]
+                },
+                {
+                    "offsets": {"from": 1200, "to": 2000},
+                    "text": "世界",
+                    "no_speech_prob": 0.05
+                },
+                {"offsets": {"from": 2000, "to": 2100}, "text": "  "}
+            ]
+        }"#;
+
+        let parsed = parse_whisper_json(raw).expect("valid json");
+        assert_eq!(parsed.text, "こんにちは世界");
+        assert_eq!(parsed.language.as_deref(), Some("ja"));
+        assert_eq!(parsed.segments.len(), 2);
+        assert_eq!(parsed.segments[0].end_ms, 1200);
+        assert_eq!(parsed.segments[0].avg_token_prob, Some(0.9));
+        assert_eq!(parsed.segments[1].avg_token_prob, None);
+        assert_eq!(parsed.segments[1].no_speech_prob, Some(0.05));
+    }
+
+    #[test]
+    fn translated_output_is_english_regardless_of_source() {
+        let transcription = Transcription {
+            language: Some("ja".to_string()),
+            translated: true,
+            ..Transcription::default()
+        };
+        assert_eq!(transcription.output_language(), Some("en"));
+    }
+
+    #[test]
+    fn decode_args_only_pass_configured_knobs() {
+        let default_args = DecodeOptions::default().whisper_args();
+        assert_eq!(default_args, vec!["-l", "ja"]);
+
+        let options = DecodeOptions {
+            language: "auto".to_string(),
+            translate: true,
+            prompt: "notype".to_string(),
+            perf: PerfParams {
+                threads: 4,
+                beam_size: 1,
+                best_of: 0,
+                no_fallback: true,
+                audio_ctx: 768,
+            },
+        };
+        assert_eq!(
+            options.whisper_args(),
+            vec![
+                "-l", "auto", "-tr", "--prompt", "notype", "-t", "4", "-bs", "1", "-ac", "768",
+                "-nf"
+            ]
+        );
+    }
+
     #[test]
     fn model_download_url_is_defined() {
         assert!(model_download_url(ModelSize::Small).contains("ggml-small.bin"));
@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use super::config::AppConfig;
+use super::error::AppError;
+
+/// whisper only looks at the last n_text_ctx/2 prompt tokens, so keep the
+/// previous utterance short enough that vocabulary is not pushed out.
+const PREVIOUS_CONTEXT_CHARS: usize = 120;
+
+/// Builds the whisper initial prompt from the vocabulary list, the free-form
+/// prompt and, when enabled, the tail of the previous utterance. The previous
+/// utterance goes last because whisper reads the prompt as preceding text.
+pub fn build_prompt(config: &AppConfig, previous_text: &str) -> String {
+    let mut parts: Vec<String> = Vec::new();
+
+    let terms: Vec<&str> = config
+        .custom_vocabulary
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect();
+    if !terms.is_empty() {
+        parts.push(terms.join(", "));
+    }
+
+    let prompt = config.initial_prompt.trim();
+    if !prompt.is_empty() {
+        parts.push(prompt.to_string());
+    }
+
+    if config.use_previous_context {
+        let previous = previous_text.trim();
+        if !previous.is_empty() {
+            let skip = previous
+                .chars()
+                .count()
+                .saturating_sub(PREVIOUS_CONTEXT_CHARS);
+            parts.push(previous.chars().skip(skip).collect());
+        }
+    }
+
+    parts.join("\n")
+}
+
+/// Parses a plain text vocabulary list: one term per line, `#` starts a
+/// comment line and blank lines are ignored.
+pub fn parse_vocabulary(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn load_vocabulary_file(path: &Path) -> Result<Vec<String>, AppError> {
+    let raw = std::fs::read_to_string(path).map_err(|e| {
+        AppError::new(
+            "語彙ファイルの読み込みに失敗しました",
+            format!("read {}: {e}", path.display()),
+        )
+    })?;
+    Ok(parse_vocabulary(&raw))
+}
+
+/// Appends terms that are not already present, keeping the existing order.
+/// Returns how many terms were added.
+pub fn merge_vocabulary(vocabulary: &mut Vec<String>, terms: Vec<String>) -> usize {
+    let mut added = 0;
+    for term in terms {
+        let term = term.trim().to_string();
+        if term.is_empty() || vocabulary.contains(&term) {
+            continue;
+        }
+        vocabulary.push(term);
+        added += 1;
+    }
+    added
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_orders_vocabulary_prompt_then_previous() {
+        let cfg = AppConfig {
+            custom_vocabulary: vec!["Tauri".to_string(), " whisper.cpp ".to_string()],
+            initial_prompt: "技術的な議事録です。".to_string(),
+            use_previous_context: true,
+            ..AppConfig::default()
+        };
+
+        let prompt = build_prompt(&cfg, "前回の発話です。");
+        assert_eq!(
+            prompt,
+            "Tauri, whisper.cpp\n技術的な議事録です。\n前回の発話です。"
+        );
+    }
+
+    #[test]
+    fn previous_context_is_ignored_unless_enabled() {
+        let cfg = AppConfig {
+            initial_prompt: "prompt".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(build_prompt(&cfg, "previous"), "prompt");
+    }
+
+    #[test]
+    fn previous_context_keeps_only_the_tail() {
+        let cfg = AppConfig {
+            use_previous_context: true,
+            ..AppConfig::default()
+        };
+        let previous = "あ".repeat(PREVIOUS_CONTEXT_CHARS + 30);
+        assert_eq!(
+            build_prompt(&cfg, &previous).chars().count(),
+            PREVIOUS_CONTEXT_CHARS
+        );
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let raw = "# product names\nnotype\n\n  zbus  \n# people\n山田";
+        assert_eq!(parse_vocabulary(raw), vec!["notype", "zbus", "山田"]);
+    }
+
+    #[test]
+    fn merge_deduplicates_and_counts_additions() {
+        let mut vocabulary = vec!["notype".to_string()];
+        let added = merge_vocabulary(
+            &mut vocabulary,
+            vec!["notype".to_string(), "zbus".to_string(), " ".to_string()],
+        );
+        assert_eq!(added, 1);
+        assert_eq!(vocabulary, vec!["notype", "zbus"]);
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// XKB key codes are evdev codes plus 8.
+const EVDEV_OFFSET: u32 = 8;
+
+static KEYCODE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<([^>]+)>\s*=\s*(\d+)\s*;").expect("keycode pattern"));
+static ALIAS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"alias\s*<([^>]+)>\s*=\s*<([^>]+)>\s*;").expect("alias pattern"));
+static KEY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)\bkey\s*<([^>]+)>\s*\{(.*?)\}\s*;").expect("key pattern"));
+static GROUP1: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)symbols\[\s*group1\s*\]\s*=\s*\[([^\]]*)\]").expect("group pattern")
+});
+/// A symbol list written without `symbols[Group1]=`, first in the body or
+/// after a comma (unlike `type[Group1]= "..."`).
+static BARE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:^|,)\s*\[([^\]]*)\]").expect("bare pattern"));
+
+/// Which key types a character on the compositor's layout, read from the
+/// XKB keymap the EIS server sends. Only the first group and its first two
+/// levels are used, so characters behind AltGr are not typed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keymap {
+    keys: HashMap<char, (u16, bool)>,
+}
+
+impl Keymap {
+    /// Parses an `xkb_keymap` in the text format. Keys without a code or
+    /// symbols that are not characters are skipped.
+    pub fn parse(text: &str) -> Self {
+        let keycodes = section(text, "xkb_keycodes").unwrap_or_default();
+        let mut codes: HashMap<&str, u32> = KEYCODE
+            .captures_iter(keycodes)
+            .filter_map(|c| Some((c.get(1)?.as_str(), c[2].parse().ok()?)))
+            .collect();
+        for alias in ALIAS.captures_iter(keycodes) {
+            if let Some(code) = codes.get(&alias[2]).copied() {
+                codes.insert(alias.get(1).expect("alias name").as_str(), code);
+            }
+        }
+
+        let mut keys = HashMap::new();
+        let symbols = section(text, "xkb_symbols").unwrap_or_default();
+        let mut found: Vec<(u32, usize, char)> = Vec::new();
+        for key in KEY.captures_iter(symbols) {
+            let Some(code) = codes.get(&key[1]).copied() else {
+                continue;
+            };
+            let Some(evdev) = code.checked_sub(EVDEV_OFFSET) else {
+                continue;
+            };
+            let body = &key[2];
+            let levels = match GROUP1.captures(body) {
+                Some(group) => group.get(1),
+                None if !body.contains("symbols[") => {
+                    BARE.captures(body.trim()).and_then(|c| c.get(1))
+                }
+                None => None,
+            };
+            let Some(levels) = levels else {
+                continue;
+            };
+            for (level, name) in levels.as_str().split(',').take(2).enumerate() {
+                if let Some(c) = keysym_char(name.trim()) {
+                    found.push((evdev, level, c));
+                }
+            }
+        }
+        // Unshifted beats shifted, then the lowest code, e.g. `\` on JIS.
+        found.sort_by_key(|(code, level, _)| (*level, *code));
+        for (code, level, c) in found {
+            if let Ok(code) = u16::try_from(code) {
+                keys.entry(c).or_insert((code, level == 1));
+            }
+        }
+        Self { keys }
+    }
+
+    /// Evdev key code and whether Shift is held to type `c`.
+    pub fn key_for(&self, c: char) -> Option<(u16, bool)> {
+        self.keys.get(&c).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// The `{ ... }` body of the named section.
+fn section<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let start = text.find(name)?;
+    let open = start + text[start..].find('{')? + 1;
+    let mut depth = 1;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[open..open + i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// X11 keysym names of printable ASCII other than letters and digits.
+const ASCII_KEYSYMS: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("apostrophe", '\''),
+    ("quoteright", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("minus", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("quoteleft", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("yen", '¥'),
+    ("Return", '\n'),
+    ("Tab", '\t'),
+];
+
+/// The character a keysym name types: letters, digits, the names above and
+/// `U` + hex code point.
+fn keysym_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return c.is_ascii_alphanumeric().then_some(c);
+    }
+    if let Some((_, c)) = ASCII_KEYSYMS.iter().find(|(sym, _)| *sym == name) {
+        return Some(*c);
+    }
+    let hex = name.strip_prefix('U')?;
+    if hex.len() < 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Just enough of a Japanese (JIS) keymap for tests.
+#[cfg(test)]
+pub(crate) const JIS_SAMPLE: &str = r#"xkb_keymap {
+xkb_keycodes "evdev+aliases(qwerty)" {
+	minimum = 8;
+	maximum = 255;
+	<AE02>               = 11;
+	<AE13>               = 132;
+	<AD01>               = 24;
+	<AD11>               = 34;
+	<AC10>               = 47;
+	<AC11>               = 48;
+	<AB10>               = 61;
+	<AB11>               = 97;
+	<RTRN>               = 36;
+	alias <LatQ>         = <AD01>;
+};
+xkb_types "complete" {
+	type "ALPHABETIC" {
+		modifiers= Shift+Lock;
+		map[Shift]= Level2;
+	};
+};
+xkb_symbols "pc+jp+inet(evdev)" {
+	name[Group1]="Japanese";
+	key <AE02>               {	[               2,        quotedbl ] };
+	key <AE13>               {	[             yen,             bar ] };
+	key <LatQ>               {
+		type= "ALPHABETIC",
+		symbols[Group1]= [               q,               Q ]
+	};
+	key <AD11>               {	[              at,           grave ] };
+	key <AC10>               {	[       semicolon,            plus ] };
+	key <AC11>               {	[           colon,        asterisk ] };
+	key <AB10>               {	[           slash,        question ] };
+	key <AB11>               {	[       backslash,      underscore ] };
+	key <RTRN>               {	[          Return ] };
+	modifier_map Shift { <LFSH> };
+};
+};
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jis_symbols_come_from_the_keymap() {
+        let keymap = Keymap::parse(JIS_SAMPLE);
+        // On a US layout these are Shift+2, Shift+' and Shift+;.
+        assert_eq!(keymap.key_for('@'), Some((26, false)));
+        assert_eq!(keymap.key_for('"'), Some((3, true)));
+        assert_eq!(keymap.key_for(':'), Some((40, false)));
+        assert_eq!(keymap.key_for('*'), Some((40, true)));
+        assert_eq!(keymap.key_for('Q'), Some((16, true)));
+        assert_eq!(keymap.key_for('\\'), Some((89, false)));
+        assert_eq!(keymap.key_for('\n'), Some((28, false)));
+        assert_eq!(keymap.key_for('あ'), None);
+        assert!(Keymap::parse("garbage").is_empty());
+        assert_eq!(keysym_char("U00E9"), Some('é'));
+    }
+}
@@ -5,11 +5,16 @@ mod core;
 use std::sync::Arc;
 use std::{env, path::Path};
 
-use core::app::AppRuntime;
+use core::app::{AppRuntime, RetranscribeOverrides, SessionOverrides};
 use core::config::{load_config, AppConfig, PillPosition};
+use core::dictionary::{load_dictionary, Dictionary};
 use core::error::AppError;
-use core::ipc::{try_call_existing, IpcController, IpcService, BUS_NAME, OBJECT_PATH};
+use core::ipc::{
+    try_call_existing, try_call_existing_with, IpcController, IpcService, BUS_NAME, OBJECT_PATH,
+};
+use core::secret::resolve_llm_api_key;
 use core::state::{DependencyWarningEvent, RuntimeState};
+use core::templates::{load_templates, PromptTemplate};
 use tauri::Manager;
 use tauri::{Emitter, PhysicalPosition, Position, WindowEvent};
 use tokio::process::Command;
@@ -22,6 +27,7 @@ struct StartupFlags {
     show_settings: bool,
     initial_pill_position: Option<PillPosition>,
     toggle_on_startup: bool,
+    startup_overrides: SessionOverrides,
 }
 
 #[tauri::command]
@@ -37,6 +43,67 @@ async fn update_config(
     state.0.update_config(cfg).await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn get_dictionary() -> Result<Dictionary, String> {
+    load_dictionary().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn save_dictionary(dictionary: Dictionary) -> Result<(), String> {
+    core::dictionary::save_dictionary(&dictionary).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_prompt_templates() -> Result<Vec<PromptTemplate>, String> {
+    load_templates().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn clear_llm_api_key(state: tauri::State<'_, SharedRuntime>) -> Result<(), String> {
+    state.0.clear_llm_api_key().await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_vocabulary(state: tauri::State<'_, SharedRuntime>) -> Result<Vec<String>, String> {
+    Ok(state.0.get_vocabulary().await)
+}
+
+#[tauri::command]
+async fn add_vocabulary_term(
+    state: tauri::State<'_, SharedRuntime>,
+    term: String,
+) -> Result<Vec<String>, String> {
+    state
+        .0
+        .add_vocabulary_term(term)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn remove_vocabulary_term(
+    state: tauri::State<'_, SharedRuntime>,
+    term: String,
+) -> Result<Vec<String>, String> {
+    state
+        .0
+        .remove_vocabulary_term(term)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn import_vocabulary(
+    state: tauri::State<'_, SharedRuntime>,
+    path: String,
+) -> Result<usize, String> {
+    state
+        .0
+        .import_vocabulary(Path::new(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn get_pill_position(
     state: tauri::State<'_, SharedRuntime>,
@@ -100,6 +167,33 @@ async fn toggle_recording(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn cancel_session(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, SharedRuntime>,
+) -> Result<RuntimeState, String> {
+    state.0.cancel(&app).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn retranscribe_last(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, SharedRuntime>,
+    model: Option<String>,
+    language: Option<String>,
+) -> Result<String, String> {
+    let overrides = RetranscribeOverrides::parse(
+        model.as_deref().unwrap_or_default(),
+        language.as_deref().unwrap_or_default(),
+    )
+    .map_err(|e| e.to_string())?;
+    state
+        .0
+        .retranscribe_last(app, overrides)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn prepare_injection_target(app: tauri::AppHandle) -> Result<(), String> {
     if let Some(main) = app.get_webview_window("main") {
@@ -189,7 +283,18 @@ async fn main() {
     init_tracing();
 
     let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("benchmark") {
+        std::process::exit(core::benchmark::run_cli(&args[1..]).await);
+    }
+    if args.first().map(String::as_str) == Some("pipeline") {
+        std::process::exit(core::pipeline::run_cli(&args[1..]).await);
+    }
+
     let has_toggle = args.iter().any(|a| a == "--toggle");
+    let has_translate = args.iter().any(|a| a == "--translate");
+    let has_code = args.iter().any(|a| a == "--code");
+    let has_edit = args.iter().any(|a| a == "--edit");
+    let template = flag_value(&args, "--template");
 
     if !args.is_empty() {
         if args.iter().any(|a| a == "--quit") {
@@ -199,6 +304,36 @@ async fn main() {
             }
         }
 
+        if args.iter().any(|a| a == "--cancel") {
+            // Nothing to cancel when no instance is running.
+            let _ = try_call_existing("Cancel").await;
+            return;
+        }
+
+        if args.iter().any(|a| a == "--retranscribe") {
+            let body = (
+                flag_value(&args, "--model").unwrap_or_default(),
+                flag_value(&args, "--language").unwrap_or_default(),
+            );
+            let called = try_call_existing_with("RetranscribeLast", &body)
+                .await
+                .unwrap_or(false);
+            if !called {
+                eprintln!("notype is not running; nothing to re-transcribe");
+            }
+            return;
+        }
+
+        if args.iter().any(|a| a == "--undo-edit") {
+            let called = try_call_existing("UndoEditSelection")
+                .await
+                .unwrap_or(false);
+            if !called {
+                eprintln!("notype is not running; nothing to undo");
+            }
+            return;
+        }
+
         if args.iter().any(|a| a == "--settings") {
             let called = try_call_existing("ShowSettings").await.unwrap_or(false);
             if called {
@@ -207,8 +342,24 @@ async fn main() {
         }
 
         if has_toggle {
-            tracing::info!("toggle requested from cli");
-            let called = try_call_existing("ToggleRecording").await.unwrap_or(false);
+            tracing::info!(
+                "toggle requested from cli; translate={has_translate} code={has_code} edit={has_edit} template={template:?}"
+            );
+            let called = match &template {
+                Some(name) => try_call_existing_with("ToggleRecordingWithTemplate", &(name,))
+                    .await
+                    .unwrap_or(false),
+                None if has_translate => try_call_existing("ToggleRecordingTranslate")
+                    .await
+                    .unwrap_or(false),
+                None if has_code => try_call_existing("ToggleRecordingCode")
+                    .await
+                    .unwrap_or(false),
+                None if has_edit => try_call_existing("ToggleEditSelection")
+                    .await
+                    .unwrap_or(false),
+                None => try_call_existing("ToggleRecording").await.unwrap_or(false),
+            };
             if called {
                 tracing::info!("toggle routed to existing instance");
                 return;
@@ -222,13 +373,34 @@ async fn main() {
         }
     }
 
-    let config = load_config().unwrap_or_default();
+    let mut config = load_config().unwrap_or_default();
+    resolve_llm_api_key(&mut config).await;
     let initial_pill_position = config.pill_position;
     let runtime = SharedRuntime(Arc::new(AppRuntime::new(config)));
+    let mut startup_overrides = match template.as_deref() {
+        Some(name) => match SessionOverrides::with_template(name) {
+            Ok(overrides) => overrides,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(2);
+            }
+        },
+        None => SessionOverrides::default(),
+    };
+    if has_translate {
+        startup_overrides.translate = Some(true);
+    }
+    if has_code {
+        startup_overrides.code_mode = Some(true);
+    }
+    if has_edit {
+        startup_overrides.edit_selection = true;
+    }
     let flags = StartupFlags {
         show_settings: args.iter().any(|a| a == "--settings"),
         initial_pill_position,
         toggle_on_startup: has_toggle,
+        startup_overrides,
     };
 
     tauri::Builder::default()
@@ -237,11 +409,21 @@ async fn main() {
         .invoke_handler(tauri::generate_handler![
             get_config,
             update_config,
+            clear_llm_api_key,
+            list_prompt_templates,
+            get_dictionary,
+            save_dictionary,
+            get_vocabulary,
+            add_vocabulary_term,
+            remove_vocabulary_term,
+            import_vocabulary,
             get_pill_position,
             set_pill_position,
             start_recording,
             stop_recording,
             toggle_recording,
+            cancel_session,
+            retranscribe_last,
             prepare_injection_target,
             type_text,
             copy_text,
@@ -272,16 +454,18 @@ async fn main() {
             });
 
             let state = app.state::<std::sync::Mutex<StartupFlags>>();
-            let (show_settings, initial_pill_position, toggle_on_startup) = state
-                .lock()
-                .map(|s| {
-                    (
-                        s.show_settings,
-                        s.initial_pill_position,
-                        s.toggle_on_startup,
-                    )
-                })
-                .unwrap_or((false, None, false));
+            let (show_settings, initial_pill_position, toggle_on_startup, startup_overrides) =
+                state
+                    .lock()
+                    .map(|s| {
+                        (
+                            s.show_settings,
+                            s.initial_pill_position,
+                            s.toggle_on_startup,
+                            s.startup_overrides.clone(),
+                        )
+                    })
+                    .unwrap_or((false, None, false, SessionOverrides::default()));
 
             if show_settings {
                 if let Some(win) = app.get_webview_window("settings") {
@@ -311,7 +495,9 @@ async fn main() {
                 let runtime = app.state::<SharedRuntime>().0.clone();
                 tauri::async_runtime::spawn(async move {
                     tracing::info!("toggle_on_startup: starting recording");
-                    let _ = runtime.start_recording(app_handle).await;
+                    let _ = runtime
+                        .start_recording_with(app_handle, startup_overrides)
+                        .await;
                 });
             }
 
@@ -321,6 +507,14 @@ async fn main() {
         .expect("error while running tauri application");
 }
 
+/// Value following `flag` on the command line, e.g. `--model medium`.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
 fn init_tracing() {
     let _ = tracing_subscriber::fmt()
         .with_env_filter(